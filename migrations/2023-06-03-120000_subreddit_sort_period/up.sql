@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TABLE subreddit ADD COLUMN sort_period TEXT NOT NULL DEFAULT "day";
\ No newline at end of file
@@ -0,0 +1,11 @@
+-- Your SQL goes here
+CREATE TABLE skipped_submission (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL UNIQUE,
+    channel_id INTEGER NOT NULL,
+    fullname TEXT NOT NULL,
+    reason TEXT NOT NULL,
+    detail TEXT,
+    skipped_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (channel_id, fullname),
+    FOREIGN KEY (channel_id) REFERENCES channel(id) ON DELETE CASCADE
+);
\ No newline at end of file
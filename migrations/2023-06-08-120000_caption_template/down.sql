@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE channel_subreddit DROP COLUMN caption_template;
+ALTER TABLE channel DROP COLUMN caption_template;
\ No newline at end of file
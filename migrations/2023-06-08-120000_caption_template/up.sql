@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TABLE channel ADD COLUMN caption_template TEXT;
+ALTER TABLE channel_subreddit ADD COLUMN caption_template TEXT;
\ No newline at end of file
@@ -0,0 +1,6 @@
+-- Your SQL goes here
+CREATE TABLE dialogue_state (
+    chat_id BIGINT PRIMARY KEY NOT NULL,
+    state TEXT NOT NULL,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
\ No newline at end of file
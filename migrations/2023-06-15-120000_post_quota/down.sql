@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE channel_subreddit DROP COLUMN posted_today_on;
+ALTER TABLE channel_subreddit DROP COLUMN posted_today;
+ALTER TABLE channel_subreddit DROP COLUMN quota_policy;
+ALTER TABLE channel_subreddit DROP COLUMN daily_limit;
\ No newline at end of file
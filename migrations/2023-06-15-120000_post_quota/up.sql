@@ -0,0 +1,5 @@
+-- Your SQL goes here
+ALTER TABLE channel_subreddit ADD COLUMN daily_limit INTEGER;
+ALTER TABLE channel_subreddit ADD COLUMN quota_policy TEXT NOT NULL DEFAULT 'defer';
+ALTER TABLE channel_subreddit ADD COLUMN posted_today INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE channel_subreddit ADD COLUMN posted_today_on DATE;
\ No newline at end of file
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE queued_post;
+ALTER TABLE channel DROP COLUMN posting_times;
+ALTER TABLE channel DROP COLUMN posting_windows;
+ALTER TABLE channel DROP COLUMN timezone;
\ No newline at end of file
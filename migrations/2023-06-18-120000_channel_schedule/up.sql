@@ -0,0 +1,15 @@
+-- Your SQL goes here
+ALTER TABLE channel ADD COLUMN timezone TEXT NOT NULL DEFAULT 'UTC';
+ALTER TABLE channel ADD COLUMN posting_windows TEXT;
+ALTER TABLE channel ADD COLUMN posting_times TEXT;
+CREATE TABLE queued_post (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL UNIQUE,
+    channel_id INTEGER NOT NULL,
+    subreddit_id INTEGER NOT NULL,
+    fullname TEXT NOT NULL,
+    submission TEXT NOT NULL,
+    queued_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (channel_id, fullname),
+    FOREIGN KEY (channel_id) REFERENCES channel(id) ON DELETE CASCADE,
+    FOREIGN KEY (subreddit_id) REFERENCES subreddit(id) ON DELETE CASCADE
+);
\ No newline at end of file
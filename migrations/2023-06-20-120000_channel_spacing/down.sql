@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE channel DROP COLUMN spread_posts;
+ALTER TABLE channel DROP COLUMN min_post_interval;
\ No newline at end of file
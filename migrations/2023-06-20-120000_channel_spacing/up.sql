@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TABLE channel ADD COLUMN min_post_interval INTEGER;
+ALTER TABLE channel ADD COLUMN spread_posts BOOLEAN NOT NULL DEFAULT FALSE;
\ No newline at end of file
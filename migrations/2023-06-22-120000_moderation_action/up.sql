@@ -0,0 +1,12 @@
+-- Your SQL goes here
+CREATE TABLE moderation_action (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL UNIQUE,
+    channel_id INTEGER NOT NULL,
+    fullname TEXT NOT NULL,
+    reason TEXT NOT NULL,
+    action TEXT NOT NULL,
+    detail TEXT,
+    acted_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (channel_id, fullname),
+    FOREIGN KEY (channel_id) REFERENCES channel(id) ON DELETE CASCADE
+);
\ No newline at end of file
@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE posted_submission DROP COLUMN subreddit_id;
+ALTER TABLE channel_subreddit DROP COLUMN comment_depth;
+ALTER TABLE channel_subreddit DROP COLUMN comment_min_score;
+ALTER TABLE channel_subreddit DROP COLUMN comment_limit;
+ALTER TABLE channel_subreddit DROP COLUMN comments_enabled;
\ No newline at end of file
@@ -0,0 +1,6 @@
+-- Your SQL goes here
+ALTER TABLE channel_subreddit ADD COLUMN comments_enabled BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE channel_subreddit ADD COLUMN comment_limit INTEGER NOT NULL DEFAULT 3;
+ALTER TABLE channel_subreddit ADD COLUMN comment_min_score INTEGER;
+ALTER TABLE channel_subreddit ADD COLUMN comment_depth INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE posted_submission ADD COLUMN subreddit_id INTEGER REFERENCES subreddit(id) ON DELETE SET NULL;
\ No newline at end of file
@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE channel DROP COLUMN buttons;
\ No newline at end of file
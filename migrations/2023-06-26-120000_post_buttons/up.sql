@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TABLE channel ADD COLUMN buttons TEXT NOT NULL DEFAULT '';
\ No newline at end of file
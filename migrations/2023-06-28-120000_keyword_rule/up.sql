@@ -0,0 +1,13 @@
+-- Your SQL goes here
+CREATE TABLE keyword_rule (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL UNIQUE,
+    channel_id INTEGER NOT NULL,
+    subreddit_id INTEGER NOT NULL,
+    kind TEXT NOT NULL,
+    pattern TEXT NOT NULL,
+    is_regex BOOLEAN NOT NULL DEFAULT FALSE,
+    case_sensitive BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (channel_id) REFERENCES channel(id) ON DELETE CASCADE,
+    FOREIGN KEY (subreddit_id) REFERENCES subreddit(id) ON DELETE CASCADE
+);
\ No newline at end of file
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE flair_rule;
+DROP TABLE seen_flair;
\ No newline at end of file
@@ -0,0 +1,22 @@
+-- Your SQL goes here
+CREATE TABLE seen_flair (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL UNIQUE,
+    subreddit_id INTEGER NOT NULL,
+    text TEXT NOT NULL,
+    template_id TEXT,
+    last_seen_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (subreddit_id, text),
+    FOREIGN KEY (subreddit_id) REFERENCES subreddit(id) ON DELETE CASCADE
+);
+CREATE TABLE flair_rule (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL UNIQUE,
+    channel_id INTEGER NOT NULL,
+    subreddit_id INTEGER NOT NULL,
+    list TEXT NOT NULL,
+    flair_text TEXT NOT NULL,
+    template_id TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (channel_id, subreddit_id, flair_text),
+    FOREIGN KEY (channel_id) REFERENCES channel(id) ON DELETE CASCADE,
+    FOREIGN KEY (subreddit_id) REFERENCES subreddit(id) ON DELETE CASCADE
+);
\ No newline at end of file
@@ -0,0 +1,21 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE subreddit ADD COLUMN post_limit INTEGER;
+ALTER TABLE subreddit ADD COLUMN respect_external_content_flag BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE subreddit ADD COLUMN min_score INTEGER;
+ALTER TABLE subreddit ADD COLUMN allow_nsfw BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE subreddit ADD COLUMN show_spoilers BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE subreddit ADD COLUMN medias_only BOOLEAN NOT NULL DEFAULT FALSE;
+-- Links of the same subreddit may disagree, the oldest one wins.
+UPDATE subreddit SET
+    post_limit = (SELECT post_limit FROM channel_subreddit WHERE channel_subreddit.subreddit_id = subreddit.id ORDER BY id LIMIT 1),
+    respect_external_content_flag = COALESCE((SELECT respect_external_content_flag FROM channel_subreddit WHERE channel_subreddit.subreddit_id = subreddit.id ORDER BY id LIMIT 1), FALSE),
+    min_score = (SELECT min_score FROM channel_subreddit WHERE channel_subreddit.subreddit_id = subreddit.id ORDER BY id LIMIT 1),
+    allow_nsfw = COALESCE((SELECT allow_nsfw FROM channel_subreddit WHERE channel_subreddit.subreddit_id = subreddit.id ORDER BY id LIMIT 1), FALSE),
+    show_spoilers = COALESCE((SELECT show_spoilers FROM channel_subreddit WHERE channel_subreddit.subreddit_id = subreddit.id ORDER BY id LIMIT 1), FALSE),
+    medias_only = COALESCE((SELECT medias_only FROM channel_subreddit WHERE channel_subreddit.subreddit_id = subreddit.id ORDER BY id LIMIT 1), FALSE);
+ALTER TABLE channel_subreddit DROP COLUMN post_limit;
+ALTER TABLE channel_subreddit DROP COLUMN respect_external_content_flag;
+ALTER TABLE channel_subreddit DROP COLUMN min_score;
+ALTER TABLE channel_subreddit DROP COLUMN allow_nsfw;
+ALTER TABLE channel_subreddit DROP COLUMN show_spoilers;
+ALTER TABLE channel_subreddit DROP COLUMN medias_only;
\ No newline at end of file
@@ -0,0 +1,20 @@
+-- Your SQL goes here
+ALTER TABLE channel_subreddit ADD COLUMN post_limit INTEGER;
+ALTER TABLE channel_subreddit ADD COLUMN respect_external_content_flag BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE channel_subreddit ADD COLUMN min_score INTEGER;
+ALTER TABLE channel_subreddit ADD COLUMN allow_nsfw BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE channel_subreddit ADD COLUMN show_spoilers BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE channel_subreddit ADD COLUMN medias_only BOOLEAN NOT NULL DEFAULT FALSE;
+UPDATE channel_subreddit SET
+    post_limit = (SELECT post_limit FROM subreddit WHERE subreddit.id = channel_subreddit.subreddit_id),
+    respect_external_content_flag = COALESCE((SELECT respect_external_content_flag FROM subreddit WHERE subreddit.id = channel_subreddit.subreddit_id), FALSE),
+    min_score = (SELECT min_score FROM subreddit WHERE subreddit.id = channel_subreddit.subreddit_id),
+    allow_nsfw = COALESCE((SELECT allow_nsfw FROM subreddit WHERE subreddit.id = channel_subreddit.subreddit_id), FALSE),
+    show_spoilers = COALESCE((SELECT show_spoilers FROM subreddit WHERE subreddit.id = channel_subreddit.subreddit_id), FALSE),
+    medias_only = COALESCE((SELECT medias_only FROM subreddit WHERE subreddit.id = channel_subreddit.subreddit_id), FALSE);
+ALTER TABLE subreddit DROP COLUMN post_limit;
+ALTER TABLE subreddit DROP COLUMN respect_external_content_flag;
+ALTER TABLE subreddit DROP COLUMN min_score;
+ALTER TABLE subreddit DROP COLUMN allow_nsfw;
+ALTER TABLE subreddit DROP COLUMN show_spoilers;
+ALTER TABLE subreddit DROP COLUMN medias_only;
\ No newline at end of file
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE posted_submission DROP COLUMN media_id;
+ALTER TABLE posted_submission DROP COLUMN crosspost_parent;
+ALTER TABLE posted_submission DROP COLUMN canonical_url;
\ No newline at end of file
@@ -0,0 +1,4 @@
+-- Your SQL goes here
+ALTER TABLE posted_submission ADD COLUMN canonical_url TEXT;
+ALTER TABLE posted_submission ADD COLUMN crosspost_parent TEXT;
+ALTER TABLE posted_submission ADD COLUMN media_id TEXT;
\ No newline at end of file
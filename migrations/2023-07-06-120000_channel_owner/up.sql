@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TABLE channel ADD COLUMN owner_id BIGINT;
\ No newline at end of file
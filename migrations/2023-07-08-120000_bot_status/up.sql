@@ -0,0 +1,7 @@
+-- Your SQL goes here
+CREATE TABLE bot_status (
+    id INTEGER PRIMARY KEY NOT NULL CHECK (id = 1),
+    paused BOOLEAN NOT NULL DEFAULT FALSE,
+    paused_at TIMESTAMP
+);
+INSERT INTO bot_status (id) VALUES (1);
\ No newline at end of file
@@ -0,0 +1,11 @@
+-- Your SQL goes here
+CREATE TABLE audit_log (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL UNIQUE,
+    actor_id BIGINT,
+    action TEXT NOT NULL,
+    chat_id BIGINT,
+    subreddit TEXT,
+    old_value TEXT,
+    new_value TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
\ No newline at end of file
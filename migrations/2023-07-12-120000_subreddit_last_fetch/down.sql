@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE subreddit DROP COLUMN last_fetched_at;
\ No newline at end of file
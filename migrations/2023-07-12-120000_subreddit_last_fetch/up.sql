@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TABLE subreddit ADD COLUMN last_fetched_at TIMESTAMP;
\ No newline at end of file
@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE queued_post DROP COLUMN deferred;
\ No newline at end of file
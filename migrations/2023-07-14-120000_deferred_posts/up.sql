@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TABLE queued_post ADD COLUMN deferred BOOLEAN NOT NULL DEFAULT FALSE;
\ No newline at end of file
@@ -1,13 +1,86 @@
 pub mod models;
 pub mod schema;
 
+use std::error::Error;
+
+use diesel::migration::MigrationSource;
 use diesel::prelude::*;
-use diesel::sqlite::SqliteConnection;
+use diesel::sqlite::{Sqlite, SqliteConnection};
+use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
+use log::info;
 
 use crate::settings::SETTINGS_INSTANCE;
 
+pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!();
+
+type MigrationResult<T> = Result<T, Box<dyn Error + Send + Sync>>;
+
+/// Connects to the configured database and brings its schema up to date.
+///
+/// Panics when the database was migrated by a newer version of the bot, since running
+/// against a schema this binary doesn't know about could corrupt the data.
 pub fn establish_connection() -> SqliteConnection {
+    let database_url = &SETTINGS_INSTANCE.database.url;
+    let mut conn = connect();
+    run_migrations(&mut conn).unwrap_or_else(|error| {
+        panic!("Couldn't migrate the database {}: {}", database_url, error)
+    });
+    conn
+}
+
+fn connect() -> SqliteConnection {
     let database_url = &SETTINGS_INSTANCE.database.url;
     SqliteConnection::establish(database_url)
         .unwrap_or_else(|_| panic!("Error connecting to {}", database_url))
 }
+
+fn run_migrations(conn: &mut SqliteConnection) -> MigrationResult<()> {
+    let unknown_versions = unknown_migrations(conn)?;
+    if !unknown_versions.is_empty() {
+        return Err(format!(
+            "the database schema is newer than this binary (unknown migrations: {}). Upgrade the bot before starting it",
+            unknown_versions.join(", ")
+        )
+        .into());
+    }
+    for version in conn.run_pending_migrations(MIGRATIONS)? {
+        info!("Applied database migration {}", version);
+    }
+    Ok(())
+}
+
+/// Versions applied to the database that aren't embedded in this binary.
+fn unknown_migrations(conn: &mut SqliteConnection) -> MigrationResult<Vec<String>> {
+    let embedded_versions: Vec<String> = MigrationSource::<Sqlite>::migrations(&MIGRATIONS)?
+        .iter()
+        .map(|migration| migration.name().version().to_string())
+        .collect();
+    Ok(conn
+        .applied_migrations()?
+        .iter()
+        .map(|version| version.to_string())
+        .filter(|version| !embedded_versions.contains(version))
+        .collect())
+}
+
+/// Prints which embedded migrations are applied to the configured database, without changing it.
+pub fn print_migration_status() -> MigrationResult<()> {
+    let mut conn = connect();
+    let applied_versions: Vec<String> = conn
+        .applied_migrations()?
+        .iter()
+        .map(|version| version.to_string())
+        .collect();
+    for migration in MigrationSource::<Sqlite>::migrations(&MIGRATIONS)? {
+        let status = if applied_versions.contains(&migration.name().version().to_string()) {
+            "applied"
+        } else {
+            "pending"
+        };
+        println!("[{}] {}", status, migration.name());
+    }
+    for version in unknown_migrations(&mut conn)? {
+        println!("[unknown] {}", version);
+    }
+    Ok(())
+}
@@ -1,14 +1,19 @@
 use super::schema::*;
+use chrono::{NaiveDate, NaiveDateTime, Utc};
 use diesel::{
     deserialize::{FromSql, FromSqlRow},
     dsl::count,
+    expression::AsExpression,
     prelude::*,
+    serialize::{self, IsNull, Output, ToSql},
     sql_types::{self, Text},
     sqlite::{Sqlite, SqliteValue},
 };
-use teloxide::types::ChatId;
+use serde_derive::{Deserialize, Serialize};
+use std::{error::Error, fmt, str::FromStr};
+use teloxide::types::{ChatId, MessageId, UserId};
 
-#[derive(Queryable, Selectable, Identifiable, Clone)]
+#[derive(Queryable, Selectable, Identifiable, Clone, Serialize, Deserialize)]
 #[diesel(table_name = channel)]
 pub struct Channel {
     pub id: i32,
@@ -17,14 +22,70 @@ pub struct Channel {
     pub title: String,
     pub username: Option<String>,
     pub invite_link: Option<String>,
+    pub caption_template: Option<String>,
+    pub timezone: String,
+    pub posting_windows: Option<String>,
+    pub posting_times: Option<String>,
+    pub min_post_interval: Option<i32>,
+    pub spread_posts: bool,
+    pub buttons: String,
+    /// Telegram user who linked the channel, unknown for channels linked before it was recorded.
+    pub owner_id: Option<i64>,
 }
 
 impl Channel {
-    pub fn delete(chat_id: i64, conn: &mut SqliteConnection) -> QueryResult<usize> {
-        use super::schema::channel::dsl as channel_dsl;
-        diesel::delete(channel_dsl::channel)
-            .filter(channel_dsl::chat_id.eq(chat_id))
-            .execute(conn)
+    /// Unlinks the channel from its subreddits and deletes it along with everything
+    /// recorded for it.
+    pub fn delete(
+        chat_id: i64,
+        actor: Option<UserId>,
+        conn: &mut SqliteConnection,
+    ) -> QueryResult<usize> {
+        use super::schema::{
+            channel::dsl as channel_dsl, moderation_action::dsl as moderation_dsl,
+            posted_submission::dsl as posted_dsl, queued_post::dsl as queued_dsl,
+            skipped_submission::dsl as skipped_dsl,
+        };
+        conn.transaction(|conn| {
+            let channel = match Channel::get_by_chat_id(ChatId(chat_id), conn).optional()? {
+                Some(channel) => channel,
+                None => return Ok(0),
+            };
+            for subreddit in Subreddit::get_by_channel(channel.clone(), conn)? {
+                ChannelSubreddit::delete(&channel, &subreddit, actor, conn)?;
+            }
+            diesel::delete(moderation_dsl::moderation_action)
+                .filter(moderation_dsl::channel_id.eq(channel.id))
+                .execute(conn)?;
+            diesel::delete(posted_dsl::posted_submission)
+                .filter(posted_dsl::channel_id.eq(channel.id))
+                .execute(conn)?;
+            diesel::delete(queued_dsl::queued_post)
+                .filter(queued_dsl::channel_id.eq(channel.id))
+                .execute(conn)?;
+            diesel::delete(skipped_dsl::skipped_submission)
+                .filter(skipped_dsl::channel_id.eq(channel.id))
+                .execute(conn)?;
+            let deleted = diesel::delete(channel_dsl::channel)
+                .filter(channel_dsl::id.eq(channel.id))
+                .execute(conn)?;
+            audit(
+                actor,
+                AuditAction::UnlinkChannel,
+                Some(chat_id),
+                None,
+                Some(&channel),
+                None,
+                conn,
+            )?;
+            Ok(deleted)
+        })
+    }
+    pub fn get_by_id(id: i32, conn: &mut SqliteConnection) -> QueryResult<Channel> {
+        use crate::db::schema::channel::dsl as channel_dsl;
+        channel_dsl::channel
+            .filter(channel_dsl::id.eq(id))
+            .first::<Channel>(conn)
     }
     pub fn get_by_chat_id(chat_id: ChatId, conn: &mut SqliteConnection) -> QueryResult<Channel> {
         use crate::db::schema::channel::dsl as channel_dsl;
@@ -32,6 +93,131 @@ impl Channel {
             .filter(channel_dsl::chat_id.eq(&chat_id.0))
             .first::<Channel>(conn)
     }
+    pub fn set_caption_template(
+        &self,
+        template: Option<&str>,
+        actor: Option<UserId>,
+        conn: &mut SqliteConnection,
+    ) -> QueryResult<usize> {
+        use crate::db::schema::channel::dsl as channel_dsl;
+        let updated = diesel::update(channel_dsl::channel)
+            .filter(channel_dsl::id.eq(self.id))
+            .set(channel_dsl::caption_template.eq(template))
+            .execute(conn)?;
+        self.audit_edit(actor, conn)?;
+        Ok(updated)
+    }
+    /// Records the changes made to the stored channel since `self` was loaded.
+    fn audit_edit(
+        &self,
+        actor: Option<UserId>,
+        conn: &mut SqliteConnection,
+    ) -> QueryResult<Channel> {
+        let updated = Channel::get_by_id(self.id, conn)?;
+        audit(
+            actor,
+            AuditAction::EditChannel,
+            Some(self.chat_id),
+            None,
+            Some(self),
+            Some(&updated),
+            conn,
+        )?;
+        Ok(updated)
+    }
+    /// The buttons attached under every post, in the order they are shown.
+    pub fn post_buttons(&self) -> Vec<PostButton> {
+        self.buttons
+            .split(',')
+            .filter_map(|button| button.parse().ok())
+            .collect()
+    }
+    pub fn set_post_buttons(
+        &self,
+        buttons: &[PostButton],
+        actor: Option<UserId>,
+        conn: &mut SqliteConnection,
+    ) -> QueryResult<Channel> {
+        use crate::db::schema::channel::dsl as channel_dsl;
+        let buttons = buttons
+            .iter()
+            .map(PostButton::as_str)
+            .collect::<Vec<_>>()
+            .join(",");
+        diesel::update(channel_dsl::channel)
+            .filter(channel_dsl::id.eq(self.id))
+            .set(channel_dsl::buttons.eq(buttons))
+            .execute(conn)?;
+        self.audit_edit(actor, conn)
+    }
+    /// Validates and applies a change to the posting schedule, returning the updated channel.
+    pub fn update_schedule(
+        &self,
+        setting: ScheduleSetting,
+        actor: Option<UserId>,
+        conn: &mut SqliteConnection,
+    ) -> Result<Channel, SettingError> {
+        use crate::db::schema::channel::dsl as channel_dsl;
+        use crate::mirror::schedule::{parse_times, parse_timezone, parse_windows};
+        let target = diesel::update(channel_dsl::channel).filter(channel_dsl::id.eq(self.id));
+        match setting {
+            ScheduleSetting::Timezone(timezone) => {
+                let timezone = parse_timezone(&timezone).map_err(SettingError::Invalid)?;
+                target
+                    .set(channel_dsl::timezone.eq(timezone.name()))
+                    .execute(conn)?
+            }
+            ScheduleSetting::Windows(windows) => {
+                if let Some(windows) = &windows {
+                    parse_windows(windows).map_err(SettingError::Invalid)?;
+                }
+                target
+                    .set(channel_dsl::posting_windows.eq(windows))
+                    .execute(conn)?
+            }
+            ScheduleSetting::Times(times) => {
+                if let Some(times) = &times {
+                    parse_times(times).map_err(SettingError::Invalid)?;
+                }
+                target
+                    .set(channel_dsl::posting_times.eq(times))
+                    .execute(conn)?
+            }
+            ScheduleSetting::MinInterval(min_interval) => {
+                if min_interval
+                    .is_some_and(|min_interval| !(1..=MAX_POST_INTERVAL).contains(&min_interval))
+                {
+                    return Err(SettingError::Invalid(format!(
+                        "The minimum interval must be between 1 and {} seconds",
+                        MAX_POST_INTERVAL
+                    )));
+                }
+                target
+                    .set(channel_dsl::min_post_interval.eq(min_interval))
+                    .execute(conn)?
+            }
+            ScheduleSetting::Spread(spread) => target
+                .set(channel_dsl::spread_posts.eq(spread))
+                .execute(conn)?,
+        };
+        Ok(self.audit_edit(actor, conn)?)
+    }
+    pub fn get_all(conn: &mut SqliteConnection) -> QueryResult<Vec<Channel>> {
+        use crate::db::schema::channel::dsl as channel_dsl;
+        channel_dsl::channel
+            .order(channel_dsl::title.asc())
+            .load::<Channel>(conn)
+    }
+    /// Telegram ids of the users who linked at least one channel.
+    pub fn get_owner_ids(conn: &mut SqliteConnection) -> QueryResult<Vec<i64>> {
+        use crate::db::schema::channel::dsl as channel_dsl;
+        channel_dsl::channel
+            .select(channel_dsl::owner_id)
+            .filter(channel_dsl::owner_id.is_not_null())
+            .distinct()
+            .load::<Option<i64>>(conn)
+            .map(|owner_ids| owner_ids.into_iter().flatten().collect())
+    }
     pub fn get_by_subreddit(
         related_subreddit: Subreddit,
         conn: &mut SqliteConnection,
@@ -45,6 +231,58 @@ impl Channel {
     }
 }
 
+/// A single change to the posting schedule of a [`Channel`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ScheduleSetting {
+    /// IANA name of the time zone the windows and times are read in.
+    Timezone(String),
+    /// Comma separated `HH:MM-HH:MM` spans, or `None` to post at any time.
+    Windows(Option<String>),
+    /// `;` separated cron expressions, or `None` to post as soon as possible.
+    Times(Option<String>),
+    /// Seconds to wait at least between two posts.
+    MinInterval(Option<i32>),
+    /// Whether queued posts are spaced evenly over the poll interval.
+    Spread(bool),
+}
+
+/// Longest minimum interval between two posts of a channel, a day.
+pub const MAX_POST_INTERVAL: i32 = 86400;
+
+/// An inline button a [`Channel`] can attach under its posts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PostButton {
+    /// Opens the submission on Reddit.
+    Reddit,
+    /// Opens the comments of the submission, showing how many there are.
+    Comments,
+    /// Opens the linked page of link posts.
+    Source,
+}
+
+impl PostButton {
+    pub const ALL: [PostButton; 3] = [PostButton::Reddit, PostButton::Comments, PostButton::Source];
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PostButton::Reddit => "reddit",
+            PostButton::Comments => "comments",
+            PostButton::Source => "source",
+        }
+    }
+}
+
+impl FromStr for PostButton {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim().to_lowercase();
+        PostButton::ALL
+            .into_iter()
+            .find(|button| button.as_str() == value)
+            .ok_or_else(|| format!("Unknown button \"{}\"", value))
+    }
+}
+
 #[derive(Insertable)]
 #[diesel(table_name = channel)]
 pub struct NewChannel<'a> {
@@ -52,6 +290,7 @@ pub struct NewChannel<'a> {
     pub title: &'a str,
     pub username: Option<&'a str>,
     pub invite_link: Option<&'a str>,
+    pub owner_id: i64,
 }
 
 impl<'a> NewChannel<'a> {
@@ -60,27 +299,83 @@ impl<'a> NewChannel<'a> {
         title: &'a str,
         username: Option<&'a str>,
         invite_link: Option<&'a str>,
+        owner_id: UserId,
     ) -> Self {
         NewChannel {
             chat_id,
             title,
             username,
             invite_link,
+            owner_id: owner_id.0 as i64,
         }
     }
     pub fn insert(self, conn: &mut SqliteConnection) -> QueryResult<Channel> {
         use crate::db::schema::channel::dsl::*;
         diesel::insert_into(channel).values(&self).execute(conn)?;
-        channel.order(id.desc()).first(conn)
+        let inserted: Channel = channel.order(id.desc()).first(conn)?;
+        audit(
+            Some(UserId(self.owner_id as u64)),
+            AuditAction::LinkChannel,
+            Some(inserted.chat_id),
+            None,
+            None,
+            Some(&inserted),
+            conn,
+        )?;
+        Ok(inserted)
     }
 }
 
-#[derive(Clone, FromSqlRow)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, FromSqlRow, AsExpression, Serialize, Deserialize)]
+#[diesel(sql_type = Text)]
 pub enum SortType {
     Hot,
     Rising,
     Top,
     Latest,
+    Controversial,
+}
+
+impl SortType {
+    pub const ALL: [SortType; 5] = [
+        SortType::Hot,
+        SortType::Rising,
+        SortType::Top,
+        SortType::Latest,
+        SortType::Controversial,
+    ];
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SortType::Hot => "hot",
+            SortType::Rising => "rising",
+            SortType::Top => "top",
+            SortType::Latest => "latest",
+            SortType::Controversial => "controversial",
+        }
+    }
+    /// Name of the Reddit listing endpoint serving this sort.
+    pub fn listing(&self) -> &'static str {
+        match self {
+            SortType::Latest => "new",
+            _ => self.as_str(),
+        }
+    }
+    /// Whether the listing is restricted to a [`SortPeriod`].
+    pub fn uses_period(&self) -> bool {
+        matches!(self, SortType::Top | SortType::Controversial)
+    }
+}
+
+impl FromStr for SortType {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim().to_lowercase();
+        SortType::ALL
+            .into_iter()
+            .find(|sort_type| sort_type.as_str() == value)
+            .ok_or_else(|| format!("Unknown subreddit sort \"{}\"", value))
+    }
 }
 
 impl FromSql<sql_types::Text, Sqlite> for SortType
@@ -88,19 +383,94 @@ where
     String: FromSql<sql_types::Text, Sqlite>,
 {
     fn from_sql(value: SqliteValue) -> diesel::deserialize::Result<Self> {
-        let value = <String as FromSql<Text, Sqlite>>::from_sql(value)?
-            .trim()
-            .to_lowercase();
-        Ok(match value.as_str() {
-            "hot" => SortType::Hot,
-            "rising" => SortType::Hot,
-            "top" => SortType::Hot,
-            "latest" => SortType::Hot,
-            _ => return Err("Encountered unexpected subreddit sort state in database.".into()),
+        let value = <String as FromSql<Text, Sqlite>>::from_sql(value)?;
+        value.parse().map_err(|_| {
+            format!(
+                "Encountered unexpected subreddit sort state in database: \"{}\". Expected one of: hot, rising, top, latest, controversial.",
+                value
+            )
+            .into()
+        })
+    }
+}
+
+impl ToSql<sql_types::Text, Sqlite> for SortType {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> serialize::Result {
+        out.set_value(self.as_str());
+        Ok(IsNull::No)
+    }
+}
+
+/// Time window of the `top` and `controversial` listings.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, FromSqlRow, AsExpression, Serialize, Deserialize)]
+#[diesel(sql_type = Text)]
+pub enum SortPeriod {
+    Hour,
+    Day,
+    Week,
+    Month,
+    Year,
+    All,
+}
+
+impl SortPeriod {
+    pub const ALL: [SortPeriod; 6] = [
+        SortPeriod::Hour,
+        SortPeriod::Day,
+        SortPeriod::Week,
+        SortPeriod::Month,
+        SortPeriod::Year,
+        SortPeriod::All,
+    ];
+    /// Value of the `t` query parameter Reddit expects.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SortPeriod::Hour => "hour",
+            SortPeriod::Day => "day",
+            SortPeriod::Week => "week",
+            SortPeriod::Month => "month",
+            SortPeriod::Year => "year",
+            SortPeriod::All => "all",
+        }
+    }
+}
+
+impl FromStr for SortPeriod {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim().to_lowercase();
+        SortPeriod::ALL
+            .into_iter()
+            .find(|period| period.as_str() == value)
+            .ok_or_else(|| format!("Unknown sort time range \"{}\"", value))
+    }
+}
+
+impl FromSql<sql_types::Text, Sqlite> for SortPeriod
+where
+    String: FromSql<sql_types::Text, Sqlite>,
+{
+    fn from_sql(value: SqliteValue) -> diesel::deserialize::Result<Self> {
+        let value = <String as FromSql<Text, Sqlite>>::from_sql(value)?;
+        value.parse().map_err(|_| {
+            format!(
+                "Encountered unexpected subreddit sort time range in database: \"{}\". Expected one of: hour, day, week, month, year, all.",
+                value
+            )
+            .into()
         })
     }
 }
-#[derive(Queryable, Selectable, Identifiable, Clone)]
+
+impl ToSql<sql_types::Text, Sqlite> for SortPeriod {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> serialize::Result {
+        out.set_value(self.as_str());
+        Ok(IsNull::No)
+    }
+}
+
+#[derive(Queryable, Selectable, Identifiable, Clone, Serialize, Deserialize)]
 #[diesel(table_name = subreddit)]
 pub struct Subreddit {
     pub id: i32,
@@ -108,15 +478,20 @@ pub struct Subreddit {
     pub subreddit_id: String,
     pub name: String,
     pub sorting: SortType,
-    pub post_limit: Option<i32>,
-    pub respect_external_content_flag: bool,
-    pub min_score: Option<i32>,
-    pub allow_nsfw: bool,
-    pub show_spoilers: bool,
-    pub medias_only: bool,
+    pub sort_period: SortPeriod,
+    /// When the posts of the subreddit were last fetched without an error. Kept out of the
+    /// dialogue state and the audit log, it changes on every poll.
+    #[serde(skip)]
+    pub last_fetched_at: Option<NaiveDateTime>,
 }
 
 impl Subreddit {
+    pub fn get_enabled(conn: &mut SqliteConnection) -> QueryResult<Vec<Subreddit>> {
+        use crate::db::schema::subreddit::dsl as sub_dsl;
+        sub_dsl::subreddit
+            .filter(sub_dsl::disabled.eq(false))
+            .load::<Subreddit>(conn)
+    }
     pub fn get_by_sub_id(
         subreddit_id: &String,
         conn: &mut SqliteConnection,
@@ -146,6 +521,66 @@ impl Subreddit {
             .select(Subreddit::as_select())
             .load(conn)
     }
+    fn set_sorting(
+        &self,
+        sorting: SortType,
+        sort_period: SortPeriod,
+        conn: &mut SqliteConnection,
+    ) -> QueryResult<usize> {
+        use crate::db::schema::subreddit::dsl as sub_dsl;
+        diesel::update(sub_dsl::subreddit)
+            .filter(sub_dsl::id.eq(self.id))
+            .set((
+                sub_dsl::sorting.eq(sorting),
+                sub_dsl::sort_period.eq(sort_period),
+            ))
+            .execute(conn)
+    }
+    pub fn get_by_id(id: i32, conn: &mut SqliteConnection) -> QueryResult<Subreddit> {
+        use crate::db::schema::subreddit::dsl as sub_dsl;
+        sub_dsl::subreddit
+            .filter(sub_dsl::id.eq(id))
+            .first::<Subreddit>(conn)
+    }
+    /// Applies a single change to how the subreddit is fetched, returning the updated subreddit.
+    ///
+    /// These settings are shared by every channel linked to the subreddit.
+    pub fn update(
+        &self,
+        setting: SubredditSetting,
+        actor: Option<UserId>,
+        conn: &mut SqliteConnection,
+    ) -> Result<Subreddit, SettingError> {
+        use crate::db::schema::subreddit::dsl as sub_dsl;
+        let target = diesel::update(sub_dsl::subreddit).filter(sub_dsl::id.eq(self.id));
+        match setting {
+            SubredditSetting::Sorting(sorting, sort_period) => {
+                self.set_sorting(sorting, sort_period, conn)?
+            }
+            SubredditSetting::Disabled(disabled) => {
+                target.set(sub_dsl::disabled.eq(disabled)).execute(conn)?
+            }
+        };
+        let updated = Subreddit::get_by_id(self.id, conn)?;
+        audit(
+            actor,
+            AuditAction::EditSubreddit,
+            None,
+            Some(&self.name),
+            Some(self),
+            Some(&updated),
+            conn,
+        )?;
+        Ok(updated)
+    }
+    /// Marks the subreddit as fetched successfully just now.
+    pub fn record_fetch(&self, conn: &mut SqliteConnection) -> QueryResult<usize> {
+        use crate::db::schema::subreddit::dsl as sub_dsl;
+        diesel::update(sub_dsl::subreddit)
+            .filter(sub_dsl::id.eq(self.id))
+            .set(sub_dsl::last_fetched_at.eq(Utc::now().naive_utc()))
+            .execute(conn)
+    }
     pub fn delete(subreddit: Subreddit, conn: &mut SqliteConnection) -> QueryResult<usize> {
         use crate::db::schema::subreddit::dsl as sub_dsl;
         diesel::delete(sub_dsl::subreddit)
@@ -154,6 +589,66 @@ impl Subreddit {
     }
 }
 
+/// Largest number of posts Reddit returns for a single listing request.
+pub const MAX_POST_LIMIT: i32 = 100;
+
+/// A single change to how a [`Subreddit`] is fetched.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SubredditSetting {
+    Sorting(SortType, SortPeriod),
+    Disabled(bool),
+}
+
+/// A single change to the filters a [`ChannelSubreddit`] link applies to fetched posts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LinkSetting {
+    PostLimit(Option<i32>),
+    MinScore(Option<i32>),
+    AllowNsfw(bool),
+    ShowSpoilers(bool),
+    MediasOnly(bool),
+    RespectExternalContentFlag(bool),
+}
+
+impl LinkSetting {
+    fn validate(&self) -> Result<(), SettingError> {
+        match self {
+            LinkSetting::PostLimit(Some(post_limit))
+                if !(1..=MAX_POST_LIMIT).contains(post_limit) =>
+            {
+                Err(SettingError::Invalid(format!(
+                    "The post limit must be between 1 and {}",
+                    MAX_POST_LIMIT
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SettingError {
+    Invalid(String),
+    Query(diesel::result::Error),
+}
+
+impl fmt::Display for SettingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SettingError::Invalid(reason) => f.write_str(reason),
+            SettingError::Query(error) => write!(f, "Couldn't save the setting: {}", error),
+        }
+    }
+}
+
+impl Error for SettingError {}
+
+impl From<diesel::result::Error> for SettingError {
+    fn from(error: diesel::result::Error) -> Self {
+        SettingError::Query(error)
+    }
+}
+
 #[derive(Insertable)]
 #[diesel(table_name = subreddit)]
 pub struct NewSubreddit<'a> {
@@ -169,7 +664,62 @@ impl<'a> NewSubreddit<'a> {
     }
 }
 
-#[derive(Identifiable, Selectable, Queryable, Associations, Debug)]
+/// What happens to a post that would exceed a posting limit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, FromSqlRow, AsExpression, Serialize, Deserialize)]
+#[diesel(sql_type = Text)]
+pub enum QuotaPolicy {
+    /// The post is never mirrored.
+    Drop,
+    /// The post waits in the queue until a later poll has room for it.
+    Defer,
+}
+
+impl QuotaPolicy {
+    pub const ALL: [QuotaPolicy; 2] = [QuotaPolicy::Drop, QuotaPolicy::Defer];
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            QuotaPolicy::Drop => "drop",
+            QuotaPolicy::Defer => "defer",
+        }
+    }
+}
+
+impl FromStr for QuotaPolicy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim().to_lowercase();
+        QuotaPolicy::ALL
+            .into_iter()
+            .find(|policy| policy.as_str() == value)
+            .ok_or_else(|| format!("Unknown quota policy \"{}\"", value))
+    }
+}
+
+impl FromSql<sql_types::Text, Sqlite> for QuotaPolicy
+where
+    String: FromSql<sql_types::Text, Sqlite>,
+{
+    fn from_sql(value: SqliteValue) -> diesel::deserialize::Result<Self> {
+        let value = <String as FromSql<Text, Sqlite>>::from_sql(value)?;
+        value.parse().map_err(|_| {
+            format!(
+                "Encountered unexpected quota policy in database: \"{}\". Expected one of: drop, defer.",
+                value
+            )
+            .into()
+        })
+    }
+}
+
+impl ToSql<sql_types::Text, Sqlite> for QuotaPolicy {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> serialize::Result {
+        out.set_value(self.as_str());
+        Ok(IsNull::No)
+    }
+}
+
+#[derive(Identifiable, Selectable, Queryable, Associations, Clone, Debug, Serialize)]
 #[diesel(belongs_to(Subreddit))]
 #[diesel(belongs_to(Channel))]
 #[diesel(table_name = channel_subreddit)]
@@ -177,31 +727,260 @@ pub struct ChannelSubreddit {
     pub id: Option<i32>,
     pub channel_id: i32,
     pub subreddit_id: i32,
+    pub caption_template: Option<String>,
+    pub daily_limit: Option<i32>,
+    pub quota_policy: QuotaPolicy,
+    // The daily counters aren't settings, the audit log leaves them out.
+    #[serde(skip)]
+    pub posted_today: i32,
+    #[serde(skip)]
+    pub posted_today_on: Option<NaiveDate>,
+    pub comments_enabled: bool,
+    pub comment_limit: i32,
+    pub comment_min_score: Option<i32>,
+    pub comment_depth: i32,
+    pub post_limit: Option<i32>,
+    pub respect_external_content_flag: bool,
+    pub min_score: Option<i32>,
+    pub allow_nsfw: bool,
+    pub show_spoilers: bool,
+    pub medias_only: bool,
 }
 
+/// Most comments mirrored under a single post.
+pub const MAX_COMMENT_LIMIT: i32 = 10;
+/// Deepest reply level mirrored, top-level comments being level 0.
+pub const MAX_COMMENT_DEPTH: i32 = 5;
+
 impl ChannelSubreddit {
+    pub fn get(
+        channel: &Channel,
+        subreddit: &Subreddit,
+        conn: &mut SqliteConnection,
+    ) -> QueryResult<ChannelSubreddit> {
+        ChannelSubreddit::get_by_ids(channel.id, subreddit.id, conn)
+    }
+    fn get_by_ids(
+        channel_id: i32,
+        subreddit_id: i32,
+        conn: &mut SqliteConnection,
+    ) -> QueryResult<ChannelSubreddit> {
+        use crate::db::schema::channel_subreddit::dsl as channel_sub_dsl;
+        channel_sub_dsl::channel_subreddit
+            .filter(channel_sub_dsl::channel_id.eq(channel_id))
+            .filter(channel_sub_dsl::subreddit_id.eq(subreddit_id))
+            .first::<ChannelSubreddit>(conn)
+    }
+    pub fn set_caption_template(
+        &self,
+        template: Option<&str>,
+        actor: Option<UserId>,
+        conn: &mut SqliteConnection,
+    ) -> QueryResult<usize> {
+        use crate::db::schema::channel_subreddit::dsl as channel_sub_dsl;
+        let updated = diesel::update(channel_sub_dsl::channel_subreddit)
+            .filter(channel_sub_dsl::channel_id.eq(self.channel_id))
+            .filter(channel_sub_dsl::subreddit_id.eq(self.subreddit_id))
+            .set(channel_sub_dsl::caption_template.eq(template))
+            .execute(conn)?;
+        self.audit_edit(actor, conn)?;
+        Ok(updated)
+    }
+    /// Records the changes made to the stored link since `self` was loaded.
+    fn audit_edit(
+        &self,
+        actor: Option<UserId>,
+        conn: &mut SqliteConnection,
+    ) -> QueryResult<ChannelSubreddit> {
+        let updated = ChannelSubreddit::get_by_ids(self.channel_id, self.subreddit_id, conn)?;
+        let (chat_id, subreddit) = link_target(self.channel_id, self.subreddit_id, conn)?;
+        audit(
+            actor,
+            AuditAction::EditLink,
+            Some(chat_id),
+            Some(&subreddit),
+            Some(self),
+            Some(&updated),
+            conn,
+        )?;
+        Ok(updated)
+    }
+    /// Posts counted against the daily limit on the given day.
+    pub fn posted_on(&self, day: NaiveDate) -> i32 {
+        if self.posted_today_on == Some(day) {
+            self.posted_today
+        } else {
+            0
+        }
+    }
+    /// Counts one more delivered post against the daily limit of `day`.
+    pub fn count_post(&self, day: NaiveDate, conn: &mut SqliteConnection) -> QueryResult<usize> {
+        use crate::db::schema::channel_subreddit::dsl as channel_sub_dsl;
+        // Re-read the counter, the link may have been loaded before earlier posts of the day.
+        let posted =
+            ChannelSubreddit::get_by_ids(self.channel_id, self.subreddit_id, conn)?.posted_on(day);
+        diesel::update(channel_sub_dsl::channel_subreddit)
+            .filter(channel_sub_dsl::channel_id.eq(self.channel_id))
+            .filter(channel_sub_dsl::subreddit_id.eq(self.subreddit_id))
+            .set((
+                channel_sub_dsl::posted_today.eq(posted + 1),
+                channel_sub_dsl::posted_today_on.eq(day),
+            ))
+            .execute(conn)
+    }
+    pub fn set_quota(
+        &self,
+        daily_limit: Option<i32>,
+        quota_policy: QuotaPolicy,
+        actor: Option<UserId>,
+        conn: &mut SqliteConnection,
+    ) -> Result<usize, SettingError> {
+        use crate::db::schema::channel_subreddit::dsl as channel_sub_dsl;
+        if daily_limit.is_some_and(|daily_limit| daily_limit < 1) {
+            return Err(SettingError::Invalid(
+                "The daily limit must be at least 1".to_owned(),
+            ));
+        }
+        let updated = diesel::update(channel_sub_dsl::channel_subreddit)
+            .filter(channel_sub_dsl::channel_id.eq(self.channel_id))
+            .filter(channel_sub_dsl::subreddit_id.eq(self.subreddit_id))
+            .set((
+                channel_sub_dsl::daily_limit.eq(daily_limit),
+                channel_sub_dsl::quota_policy.eq(quota_policy),
+            ))
+            .execute(conn)?;
+        self.audit_edit(actor, conn)?;
+        Ok(updated)
+    }
+    /// Changes which Reddit comments are mirrored into the channel's discussion group.
+    pub fn set_comments(
+        &self,
+        enabled: bool,
+        limit: i32,
+        min_score: Option<i32>,
+        depth: i32,
+        actor: Option<UserId>,
+        conn: &mut SqliteConnection,
+    ) -> Result<usize, SettingError> {
+        use crate::db::schema::channel_subreddit::dsl as channel_sub_dsl;
+        if !(1..=MAX_COMMENT_LIMIT).contains(&limit) {
+            return Err(SettingError::Invalid(format!(
+                "The number of comments must be between 1 and {}",
+                MAX_COMMENT_LIMIT
+            )));
+        }
+        if !(0..=MAX_COMMENT_DEPTH).contains(&depth) {
+            return Err(SettingError::Invalid(format!(
+                "The comment depth must be between 0 and {}",
+                MAX_COMMENT_DEPTH
+            )));
+        }
+        let updated = diesel::update(channel_sub_dsl::channel_subreddit)
+            .filter(channel_sub_dsl::channel_id.eq(self.channel_id))
+            .filter(channel_sub_dsl::subreddit_id.eq(self.subreddit_id))
+            .set((
+                channel_sub_dsl::comments_enabled.eq(enabled),
+                channel_sub_dsl::comment_limit.eq(limit),
+                channel_sub_dsl::comment_min_score.eq(min_score),
+                channel_sub_dsl::comment_depth.eq(depth),
+            ))
+            .execute(conn)?;
+        self.audit_edit(actor, conn)?;
+        Ok(updated)
+    }
+    /// Validates and applies a single filter change, returning the updated link.
+    pub fn update(
+        &self,
+        setting: LinkSetting,
+        actor: Option<UserId>,
+        conn: &mut SqliteConnection,
+    ) -> Result<ChannelSubreddit, SettingError> {
+        use crate::db::schema::channel_subreddit::dsl as channel_sub_dsl;
+        setting.validate()?;
+        let target = diesel::update(channel_sub_dsl::channel_subreddit)
+            .filter(channel_sub_dsl::channel_id.eq(self.channel_id))
+            .filter(channel_sub_dsl::subreddit_id.eq(self.subreddit_id));
+        match setting {
+            LinkSetting::PostLimit(post_limit) => target
+                .set(channel_sub_dsl::post_limit.eq(post_limit))
+                .execute(conn)?,
+            LinkSetting::MinScore(min_score) => target
+                .set(channel_sub_dsl::min_score.eq(min_score))
+                .execute(conn)?,
+            LinkSetting::AllowNsfw(allow_nsfw) => target
+                .set(channel_sub_dsl::allow_nsfw.eq(allow_nsfw))
+                .execute(conn)?,
+            LinkSetting::ShowSpoilers(show_spoilers) => target
+                .set(channel_sub_dsl::show_spoilers.eq(show_spoilers))
+                .execute(conn)?,
+            LinkSetting::MediasOnly(medias_only) => target
+                .set(channel_sub_dsl::medias_only.eq(medias_only))
+                .execute(conn)?,
+            LinkSetting::RespectExternalContentFlag(respect) => target
+                .set(channel_sub_dsl::respect_external_content_flag.eq(respect))
+                .execute(conn)?,
+        };
+        Ok(self.audit_edit(actor, conn)?)
+    }
     pub fn insert(
         new_relation: &NewChannelSubreddit,
+        actor: Option<UserId>,
         conn: &mut SqliteConnection,
     ) -> QueryResult<ChannelSubreddit> {
         use crate::db::schema::channel_subreddit::dsl as channel_sub_dsl;
         diesel::insert_into(channel_sub_dsl::channel_subreddit)
             .values(new_relation)
             .execute(conn)?;
-        channel_sub_dsl::channel_subreddit
+        let inserted = channel_sub_dsl::channel_subreddit
             .order(channel_sub_dsl::id.desc())
-            .first::<ChannelSubreddit>(conn)
+            .first::<ChannelSubreddit>(conn)?;
+        let (chat_id, subreddit) = link_target(inserted.channel_id, inserted.subreddit_id, conn)?;
+        audit(
+            actor,
+            AuditAction::LinkSubreddit,
+            Some(chat_id),
+            Some(&subreddit),
+            None,
+            Some(&inserted),
+            conn,
+        )?;
+        Ok(inserted)
     }
     pub fn delete(
         channel: &Channel,
         subreddit: &Subreddit,
+        actor: Option<UserId>,
         conn: &mut SqliteConnection,
     ) -> QueryResult<usize> {
-        use crate::db::schema::channel_subreddit::dsl as channel_sub_dsl;
-        diesel::delete(channel_sub_dsl::channel_subreddit)
+        use crate::db::schema::{
+            channel_subreddit::dsl as channel_sub_dsl, flair_rule::dsl as flair_dsl,
+            keyword_rule::dsl as keyword_dsl,
+        };
+        let link = ChannelSubreddit::get(channel, subreddit, conn).optional()?;
+        diesel::delete(keyword_dsl::keyword_rule)
+            .filter(keyword_dsl::channel_id.eq(channel.id))
+            .filter(keyword_dsl::subreddit_id.eq(subreddit.id))
+            .execute(conn)?;
+        diesel::delete(flair_dsl::flair_rule)
+            .filter(flair_dsl::channel_id.eq(channel.id))
+            .filter(flair_dsl::subreddit_id.eq(subreddit.id))
+            .execute(conn)?;
+        let deleted = diesel::delete(channel_sub_dsl::channel_subreddit)
             .filter(channel_sub_dsl::channel_id.eq(channel.id))
             .filter(channel_sub_dsl::subreddit_id.eq(subreddit.id))
-            .execute(conn)
+            .execute(conn)?;
+        if let Some(link) = link {
+            audit(
+                actor,
+                AuditAction::UnlinkSubreddit,
+                Some(channel.chat_id),
+                Some(&subreddit.name),
+                Some(&link),
+                None,
+                conn,
+            )?;
+        }
+        Ok(deleted)
     }
     pub fn are_related(
         channel: &Channel,
@@ -238,3 +1017,1253 @@ impl NewChannelSubreddit {
         }
     }
 }
+
+/// Whether a [`KeywordRule`] lets matching posts through or holds them back.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, FromSqlRow, AsExpression, Serialize, Deserialize)]
+#[diesel(sql_type = Text)]
+pub enum RuleKind {
+    /// Posts have to match at least one include rule, if the link has any.
+    Include,
+    /// Posts matching an exclude rule are skipped.
+    Exclude,
+}
+
+impl RuleKind {
+    pub const ALL: [RuleKind; 2] = [RuleKind::Include, RuleKind::Exclude];
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RuleKind::Include => "include",
+            RuleKind::Exclude => "exclude",
+        }
+    }
+}
+
+impl FromStr for RuleKind {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim().to_lowercase();
+        RuleKind::ALL
+            .into_iter()
+            .find(|kind| kind.as_str() == value)
+            .ok_or_else(|| format!("Unknown rule kind \"{}\"", value))
+    }
+}
+
+impl FromSql<sql_types::Text, Sqlite> for RuleKind
+where
+    String: FromSql<sql_types::Text, Sqlite>,
+{
+    fn from_sql(value: SqliteValue) -> diesel::deserialize::Result<Self> {
+        let value = <String as FromSql<Text, Sqlite>>::from_sql(value)?;
+        value.parse().map_err(|_| {
+            format!(
+                "Encountered unexpected rule kind in database: \"{}\". Expected one of: include, exclude.",
+                value
+            )
+            .into()
+        })
+    }
+}
+
+impl ToSql<sql_types::Text, Sqlite> for RuleKind {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> serialize::Result {
+        out.set_value(self.as_str());
+        Ok(IsNull::No)
+    }
+}
+
+/// A word or regular expression matched against the title and self text of the posts of a
+/// channel–subreddit link.
+#[derive(Identifiable, Selectable, Queryable, Associations, Clone, Debug, Serialize)]
+#[diesel(belongs_to(Channel))]
+#[diesel(belongs_to(Subreddit))]
+#[diesel(table_name = keyword_rule)]
+pub struct KeywordRule {
+    pub id: i32,
+    pub channel_id: i32,
+    pub subreddit_id: i32,
+    pub kind: RuleKind,
+    pub pattern: String,
+    pub is_regex: bool,
+    pub case_sensitive: bool,
+    #[serde(skip)]
+    pub created_at: NaiveDateTime,
+}
+
+impl KeywordRule {
+    /// Rules of the link, oldest first.
+    pub fn get_by_link(
+        link: &ChannelSubreddit,
+        conn: &mut SqliteConnection,
+    ) -> QueryResult<Vec<KeywordRule>> {
+        use crate::db::schema::keyword_rule::dsl as keyword_dsl;
+        keyword_dsl::keyword_rule
+            .filter(keyword_dsl::channel_id.eq(link.channel_id))
+            .filter(keyword_dsl::subreddit_id.eq(link.subreddit_id))
+            .order(keyword_dsl::id.asc())
+            .load::<KeywordRule>(conn)
+    }
+    pub fn delete(&self, actor: Option<UserId>, conn: &mut SqliteConnection) -> QueryResult<usize> {
+        use crate::db::schema::keyword_rule::dsl as keyword_dsl;
+        let deleted = diesel::delete(keyword_dsl::keyword_rule)
+            .filter(keyword_dsl::id.eq(self.id))
+            .execute(conn)?;
+        let (chat_id, subreddit) = link_target(self.channel_id, self.subreddit_id, conn)?;
+        audit(
+            actor,
+            AuditAction::DeleteKeywordRule,
+            Some(chat_id),
+            Some(&subreddit),
+            Some(self),
+            None,
+            conn,
+        )?;
+        Ok(deleted)
+    }
+}
+
+impl fmt::Display for KeywordRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_regex {
+            write!(f, "{} /{}/", self.kind.as_str(), self.pattern)?;
+        } else {
+            write!(f, "{} \"{}\"", self.kind.as_str(), self.pattern)?;
+        }
+        if self.case_sensitive {
+            write!(f, " (case-sensitive)")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = keyword_rule)]
+pub struct NewKeywordRule<'a> {
+    channel_id: i32,
+    subreddit_id: i32,
+    kind: RuleKind,
+    pattern: &'a str,
+    is_regex: bool,
+    case_sensitive: bool,
+}
+
+impl<'a> NewKeywordRule<'a> {
+    pub fn new(
+        link: &ChannelSubreddit,
+        kind: RuleKind,
+        pattern: &'a str,
+        is_regex: bool,
+        case_sensitive: bool,
+    ) -> Self {
+        NewKeywordRule {
+            channel_id: link.channel_id,
+            subreddit_id: link.subreddit_id,
+            kind,
+            pattern,
+            is_regex,
+            case_sensitive,
+        }
+    }
+    /// Stores the rule once its pattern is known to compile.
+    pub fn insert(
+        self,
+        actor: Option<UserId>,
+        conn: &mut SqliteConnection,
+    ) -> Result<KeywordRule, SettingError> {
+        use crate::db::schema::keyword_rule::dsl::*;
+        use crate::mirror::filter::compile_rule;
+        compile_rule(self.pattern, self.is_regex, self.case_sensitive)
+            .map_err(SettingError::Invalid)?;
+        diesel::insert_into(keyword_rule)
+            .values(&self)
+            .execute(conn)?;
+        let rule: KeywordRule = keyword_rule.order(id.desc()).first(conn)?;
+        let (chat_id, subreddit) = link_target(rule.channel_id, rule.subreddit_id, conn)?;
+        audit(
+            actor,
+            AuditAction::AddKeywordRule,
+            Some(chat_id),
+            Some(&subreddit),
+            None,
+            Some(&rule),
+            conn,
+        )?;
+        Ok(rule)
+    }
+}
+
+/// Which flair list of a channel–subreddit link a [`FlairRule`] belongs to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, FromSqlRow, AsExpression, Serialize, Deserialize)]
+#[diesel(sql_type = Text)]
+pub enum FlairList {
+    /// Only posts with one of these flairs are mirrored, if the list isn't empty.
+    Allow,
+    /// Posts with one of these flairs are skipped.
+    Block,
+}
+
+impl FlairList {
+    pub const ALL: [FlairList; 2] = [FlairList::Allow, FlairList::Block];
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FlairList::Allow => "allow",
+            FlairList::Block => "block",
+        }
+    }
+}
+
+impl FromStr for FlairList {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim().to_lowercase();
+        FlairList::ALL
+            .into_iter()
+            .find(|list| list.as_str() == value)
+            .ok_or_else(|| format!("Unknown flair list \"{}\"", value))
+    }
+}
+
+impl FromSql<sql_types::Text, Sqlite> for FlairList
+where
+    String: FromSql<sql_types::Text, Sqlite>,
+{
+    fn from_sql(value: SqliteValue) -> diesel::deserialize::Result<Self> {
+        let value = <String as FromSql<Text, Sqlite>>::from_sql(value)?;
+        value.parse().map_err(|_| {
+            format!(
+                "Encountered unexpected flair list in database: \"{}\". Expected one of: allow, block.",
+                value
+            )
+            .into()
+        })
+    }
+}
+
+impl ToSql<sql_types::Text, Sqlite> for FlairList {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> serialize::Result {
+        out.set_value(self.as_str());
+        Ok(IsNull::No)
+    }
+}
+
+/// A flair on the allowlist or blocklist of a channel–subreddit link.
+///
+/// Rules picked from the seen flairs also keep the flair template id, which still matches
+/// after the moderators rename the flair.
+#[derive(Identifiable, Selectable, Queryable, Associations, Clone, Debug, Serialize)]
+#[diesel(belongs_to(Channel))]
+#[diesel(belongs_to(Subreddit))]
+#[diesel(table_name = flair_rule)]
+pub struct FlairRule {
+    pub id: i32,
+    pub channel_id: i32,
+    pub subreddit_id: i32,
+    pub list: FlairList,
+    pub flair_text: String,
+    pub template_id: Option<String>,
+    #[serde(skip)]
+    pub created_at: NaiveDateTime,
+}
+
+impl FlairRule {
+    pub fn get_by_link(
+        link: &ChannelSubreddit,
+        conn: &mut SqliteConnection,
+    ) -> QueryResult<Vec<FlairRule>> {
+        use crate::db::schema::flair_rule::dsl as flair_dsl;
+        flair_dsl::flair_rule
+            .filter(flair_dsl::channel_id.eq(link.channel_id))
+            .filter(flair_dsl::subreddit_id.eq(link.subreddit_id))
+            .order(flair_dsl::id.asc())
+            .load::<FlairRule>(conn)
+    }
+    /// Moves a flair to the given list of the link, or off both lists with `None`.
+    pub fn set_list(
+        link: &ChannelSubreddit,
+        flair_text: &str,
+        template_id: Option<&str>,
+        list: Option<FlairList>,
+        actor: Option<UserId>,
+        conn: &mut SqliteConnection,
+    ) -> QueryResult<usize> {
+        use crate::db::schema::flair_rule::dsl as flair_dsl;
+        let rule_query = flair_dsl::flair_rule
+            .filter(flair_dsl::channel_id.eq(link.channel_id))
+            .filter(flair_dsl::subreddit_id.eq(link.subreddit_id))
+            .filter(flair_dsl::flair_text.eq(flair_text));
+        let before = rule_query.first::<FlairRule>(conn).optional()?;
+        let mut changed = diesel::delete(rule_query).execute(conn)?;
+        if let Some(list) = list {
+            changed = diesel::insert_into(flair_dsl::flair_rule)
+                .values((
+                    flair_dsl::channel_id.eq(link.channel_id),
+                    flair_dsl::subreddit_id.eq(link.subreddit_id),
+                    flair_dsl::list.eq(list),
+                    flair_dsl::flair_text.eq(flair_text),
+                    flair_dsl::template_id.eq(template_id),
+                ))
+                .execute(conn)?;
+        }
+        let after = rule_query.first::<FlairRule>(conn).optional()?;
+        let (chat_id, subreddit) = link_target(link.channel_id, link.subreddit_id, conn)?;
+        audit(
+            actor,
+            AuditAction::EditFlairRule,
+            Some(chat_id),
+            Some(&subreddit),
+            before.as_ref(),
+            after.as_ref(),
+            conn,
+        )?;
+        Ok(changed)
+    }
+}
+
+/// A flair recently seen on the posts of a subreddit, offered when editing the flair lists.
+#[derive(Identifiable, Selectable, Queryable, Associations, Clone, Debug)]
+#[diesel(belongs_to(Subreddit))]
+#[diesel(table_name = seen_flair)]
+pub struct SeenFlair {
+    pub id: i32,
+    pub subreddit_id: i32,
+    pub text: String,
+    pub template_id: Option<String>,
+    pub last_seen_at: NaiveDateTime,
+}
+
+impl SeenFlair {
+    /// The most recently seen flairs of the subreddit.
+    pub fn get_latest(
+        related_subreddit: &Subreddit,
+        limit: i64,
+        conn: &mut SqliteConnection,
+    ) -> QueryResult<Vec<SeenFlair>> {
+        use crate::db::schema::seen_flair::dsl as seen_dsl;
+        SeenFlair::belonging_to(related_subreddit)
+            .order(seen_dsl::last_seen_at.desc())
+            .limit(limit)
+            .load::<SeenFlair>(conn)
+    }
+    pub fn get_by_subreddit(
+        related_subreddit: &Subreddit,
+        conn: &mut SqliteConnection,
+    ) -> QueryResult<Vec<SeenFlair>> {
+        SeenFlair::belonging_to(related_subreddit).load::<SeenFlair>(conn)
+    }
+    pub fn get_by_id(id: i32, conn: &mut SqliteConnection) -> QueryResult<SeenFlair> {
+        use crate::db::schema::seen_flair::dsl as seen_dsl;
+        seen_dsl::seen_flair
+            .filter(seen_dsl::id.eq(id))
+            .first::<SeenFlair>(conn)
+    }
+    /// Records that the flair was just seen, keeping its id stable across sightings.
+    pub fn record(
+        related_subreddit: &Subreddit,
+        text: &str,
+        template_id: Option<&str>,
+        conn: &mut SqliteConnection,
+    ) -> QueryResult<usize> {
+        use crate::db::schema::seen_flair::dsl as seen_dsl;
+        let now = Utc::now().naive_utc();
+        diesel::insert_into(seen_dsl::seen_flair)
+            .values((
+                seen_dsl::subreddit_id.eq(related_subreddit.id),
+                seen_dsl::text.eq(text),
+                seen_dsl::template_id.eq(template_id),
+                seen_dsl::last_seen_at.eq(now),
+            ))
+            .on_conflict((seen_dsl::subreddit_id, seen_dsl::text))
+            .do_update()
+            .set((
+                seen_dsl::template_id.eq(template_id),
+                seen_dsl::last_seen_at.eq(now),
+            ))
+            .execute(conn)
+    }
+}
+
+#[derive(Identifiable, Selectable, Queryable, Associations, Clone, Debug)]
+#[diesel(belongs_to(Channel))]
+#[diesel(table_name = posted_submission)]
+pub struct PostedSubmission {
+    pub id: i32,
+    pub channel_id: i32,
+    pub fullname: String,
+    pub message_ids: String,
+    pub posted_at: NaiveDateTime,
+    pub score: i32,
+    pub subreddit_id: Option<i32>,
+    pub canonical_url: Option<String>,
+    pub crosspost_parent: Option<String>,
+    pub media_id: Option<String>,
+}
+
+/// What identifies the content of a submission, so that cross-posts and reposts of it can be
+/// recognized.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ContentKeys {
+    /// The linked URL without scheme, `www.` prefix, fragment and tracking parameters.
+    pub canonical_url: Option<String>,
+    /// Fullname of the original submission when this one is a cross-post.
+    pub crosspost_parent: Option<String>,
+    /// Id of the media Reddit hosts for the submission, e.g. an `i.redd.it` image.
+    pub media_id: Option<String>,
+}
+
+impl PostedSubmission {
+    /// Telegram messages that make up this post, in the order they were sent.
+    pub fn message_ids(&self) -> Vec<MessageId> {
+        self.message_ids
+            .split(',')
+            .filter_map(|message_id| message_id.parse().ok())
+            .map(MessageId)
+            .collect()
+    }
+    pub fn is_posted(
+        channel: &Channel,
+        fullname: &str,
+        conn: &mut SqliteConnection,
+    ) -> QueryResult<bool> {
+        use crate::db::schema::posted_submission::dsl as posted_dsl;
+        posted_dsl::posted_submission
+            .select(count(posted_dsl::id))
+            .filter(posted_dsl::channel_id.eq(channel.id))
+            .filter(posted_dsl::fullname.eq(fullname))
+            .first::<i64>(conn)
+            .map(|count| count > 0)
+    }
+    /// When the channel received its most recent post.
+    pub fn last_posted_at(
+        channel: &Channel,
+        conn: &mut SqliteConnection,
+    ) -> QueryResult<Option<NaiveDateTime>> {
+        use crate::db::schema::posted_submission::dsl as posted_dsl;
+        PostedSubmission::belonging_to(channel)
+            .select(posted_dsl::posted_at)
+            .order(posted_dsl::posted_at.desc())
+            .first::<NaiveDateTime>(conn)
+            .optional()
+    }
+    /// The post of the channel that starts with the given message.
+    pub fn get_by_first_message(
+        channel: &Channel,
+        message_id: MessageId,
+        conn: &mut SqliteConnection,
+    ) -> QueryResult<Option<PostedSubmission>> {
+        use crate::db::schema::posted_submission::dsl as posted_dsl;
+        let first_id = message_id.0.to_string();
+        PostedSubmission::belonging_to(channel)
+            .filter(
+                posted_dsl::message_ids
+                    .eq(&first_id)
+                    .or(posted_dsl::message_ids.like(format!("{},%", first_id))),
+            )
+            .first::<PostedSubmission>(conn)
+            .optional()
+    }
+    /// An earlier post of the channel, made after `since`, sharing the content of the given
+    /// submission: the original of a cross-post, another cross-post of the same original, or
+    /// a submission of the same URL or Reddit media.
+    pub fn find_duplicate(
+        channel: &Channel,
+        fullname: &str,
+        keys: &ContentKeys,
+        since: NaiveDateTime,
+        conn: &mut SqliteConnection,
+    ) -> QueryResult<Option<PostedSubmission>> {
+        use crate::db::schema::posted_submission::dsl as posted_dsl;
+        let origin = keys.crosspost_parent.as_deref().unwrap_or(fullname);
+        // Comparing against a missing key yields NULL, which never matches.
+        PostedSubmission::belonging_to(channel)
+            .filter(posted_dsl::posted_at.gt(since))
+            .filter(posted_dsl::fullname.ne(fullname))
+            .filter(
+                posted_dsl::fullname
+                    .eq(origin)
+                    .or(posted_dsl::crosspost_parent.eq(origin))
+                    .or(posted_dsl::canonical_url.eq(keys.canonical_url.as_deref()))
+                    .or(posted_dsl::media_id.eq(keys.media_id.as_deref())),
+            )
+            .order(posted_dsl::posted_at.asc())
+            .first::<PostedSubmission>(conn)
+            .optional()
+    }
+    pub fn get_by_fullname(
+        fullname: &str,
+        conn: &mut SqliteConnection,
+    ) -> QueryResult<Vec<PostedSubmission>> {
+        use crate::db::schema::posted_submission::dsl as posted_dsl;
+        posted_dsl::posted_submission
+            .filter(posted_dsl::fullname.eq(fullname))
+            .load::<PostedSubmission>(conn)
+    }
+    /// Submissions posted after `cutoff` that weren't taken down yet.
+    pub fn get_posted_since(
+        cutoff: NaiveDateTime,
+        conn: &mut SqliteConnection,
+    ) -> QueryResult<Vec<PostedSubmission>> {
+        use crate::db::schema::{
+            moderation_action::dsl as action_dsl, posted_submission::dsl as posted_dsl,
+        };
+        use diesel::dsl::{exists, not};
+        posted_dsl::posted_submission
+            .filter(posted_dsl::posted_at.gt(cutoff))
+            .filter(not(exists(
+                action_dsl::moderation_action
+                    .filter(action_dsl::channel_id.eq(posted_dsl::channel_id))
+                    .filter(action_dsl::fullname.eq(posted_dsl::fullname)),
+            )))
+            .load::<PostedSubmission>(conn)
+    }
+    pub fn get_by_channel(
+        related_channel: &Channel,
+        conn: &mut SqliteConnection,
+    ) -> QueryResult<Vec<PostedSubmission>> {
+        use crate::db::schema::posted_submission::dsl as posted_dsl;
+        PostedSubmission::belonging_to(related_channel)
+            .order(posted_dsl::posted_at.desc())
+            .load::<PostedSubmission>(conn)
+    }
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = posted_submission)]
+pub struct NewPostedSubmission<'a> {
+    channel_id: i32,
+    fullname: &'a str,
+    message_ids: String,
+    score: i32,
+    subreddit_id: i32,
+    canonical_url: Option<&'a str>,
+    crosspost_parent: Option<&'a str>,
+    media_id: Option<&'a str>,
+}
+
+impl<'a> NewPostedSubmission<'a> {
+    pub fn new(
+        channel: &Channel,
+        subreddit: &Subreddit,
+        fullname: &'a str,
+        keys: &'a ContentKeys,
+        message_ids: &[MessageId],
+        score: i32,
+    ) -> Self {
+        NewPostedSubmission {
+            channel_id: channel.id,
+            fullname,
+            message_ids: message_ids
+                .iter()
+                .map(|message_id| message_id.0.to_string())
+                .collect::<Vec<_>>()
+                .join(","),
+            score,
+            subreddit_id: subreddit.id,
+            canonical_url: keys.canonical_url.as_deref(),
+            crosspost_parent: keys.crosspost_parent.as_deref(),
+            media_id: keys.media_id.as_deref(),
+        }
+    }
+    pub fn insert(self, conn: &mut SqliteConnection) -> QueryResult<PostedSubmission> {
+        use crate::db::schema::posted_submission::dsl::*;
+        diesel::insert_into(posted_submission)
+            .values(&self)
+            .execute(conn)?;
+        posted_submission.order(id.desc()).first(conn)
+    }
+}
+
+#[derive(Identifiable, Selectable, Queryable, Associations, Clone, Debug)]
+#[diesel(belongs_to(Channel))]
+#[diesel(belongs_to(Subreddit))]
+#[diesel(table_name = queued_post)]
+pub struct QueuedPost {
+    pub id: i32,
+    pub channel_id: i32,
+    pub subreddit_id: i32,
+    pub fullname: String,
+    pub submission: String,
+    pub queued_at: NaiveDateTime,
+    /// Held back by the link's post limit until a later poll has room for it.
+    pub deferred: bool,
+}
+
+impl QueuedPost {
+    pub fn is_queued(
+        channel: &Channel,
+        fullname: &str,
+        conn: &mut SqliteConnection,
+    ) -> QueryResult<bool> {
+        use crate::db::schema::queued_post::dsl as queued_dsl;
+        queued_dsl::queued_post
+            .select(count(queued_dsl::id))
+            .filter(queued_dsl::channel_id.eq(channel.id))
+            .filter(queued_dsl::fullname.eq(fullname))
+            .first::<i64>(conn)
+            .map(|count| count > 0)
+    }
+    /// Posts waiting for the channel, oldest first, leaving out deferred ones.
+    pub fn get_by_channel(
+        related_channel: &Channel,
+        conn: &mut SqliteConnection,
+    ) -> QueryResult<Vec<QueuedPost>> {
+        use crate::db::schema::queued_post::dsl as queued_dsl;
+        QueuedPost::belonging_to(related_channel)
+            .filter(queued_dsl::deferred.eq(false))
+            .order(queued_dsl::id.asc())
+            .load::<QueuedPost>(conn)
+    }
+    /// Posts the link deferred, oldest first.
+    pub fn get_deferred(
+        link: &ChannelSubreddit,
+        conn: &mut SqliteConnection,
+    ) -> QueryResult<Vec<QueuedPost>> {
+        use crate::db::schema::queued_post::dsl as queued_dsl;
+        queued_dsl::queued_post
+            .filter(queued_dsl::channel_id.eq(link.channel_id))
+            .filter(queued_dsl::subreddit_id.eq(link.subreddit_id))
+            .filter(queued_dsl::deferred.eq(true))
+            .order(queued_dsl::id.asc())
+            .load::<QueuedPost>(conn)
+    }
+    /// Channels with at least one post that isn't deferred.
+    pub fn get_channels(conn: &mut SqliteConnection) -> QueryResult<Vec<Channel>> {
+        use crate::db::schema::{channel::dsl as channel_dsl, queued_post::dsl as queued_dsl};
+        channel_dsl::channel
+            .filter(
+                channel_dsl::id.eq_any(
+                    queued_dsl::queued_post
+                        .filter(queued_dsl::deferred.eq(false))
+                        .select(queued_dsl::channel_id),
+                ),
+            )
+            .load::<Channel>(conn)
+    }
+    /// Hands a deferred post over to the channel's schedule.
+    pub fn release(&self, conn: &mut SqliteConnection) -> QueryResult<usize> {
+        use crate::db::schema::queued_post::dsl as queued_dsl;
+        diesel::update(queued_dsl::queued_post)
+            .filter(queued_dsl::id.eq(self.id))
+            .set(queued_dsl::deferred.eq(false))
+            .execute(conn)
+    }
+    pub fn delete(&self, conn: &mut SqliteConnection) -> QueryResult<usize> {
+        use crate::db::schema::queued_post::dsl as queued_dsl;
+        diesel::delete(queued_dsl::queued_post)
+            .filter(queued_dsl::id.eq(self.id))
+            .execute(conn)
+    }
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = queued_post)]
+pub struct NewQueuedPost<'a> {
+    channel_id: i32,
+    subreddit_id: i32,
+    fullname: &'a str,
+    submission: String,
+    deferred: bool,
+}
+
+impl<'a> NewQueuedPost<'a> {
+    pub fn new(
+        channel: &Channel,
+        subreddit: &Subreddit,
+        fullname: &'a str,
+        submission: String,
+        deferred: bool,
+    ) -> Self {
+        NewQueuedPost {
+            channel_id: channel.id,
+            subreddit_id: subreddit.id,
+            fullname,
+            submission,
+            deferred,
+        }
+    }
+    /// Queues the post unless the channel already has it queued.
+    pub fn insert(self, conn: &mut SqliteConnection) -> QueryResult<bool> {
+        use crate::db::schema::queued_post::dsl::*;
+        diesel::insert_or_ignore_into(queued_post)
+            .values(&self)
+            .execute(conn)
+            .map(|inserted_rows| inserted_rows > 0)
+    }
+}
+
+#[derive(Identifiable, Selectable, Queryable, Associations, Clone, Debug)]
+#[diesel(belongs_to(Channel))]
+#[diesel(table_name = skipped_submission)]
+pub struct SkippedSubmission {
+    pub id: i32,
+    pub channel_id: i32,
+    pub fullname: String,
+    pub reason: String,
+    pub detail: Option<String>,
+    pub skipped_at: NaiveDateTime,
+}
+
+impl SkippedSubmission {
+    /// Number of skipped submissions per reason, most frequent first.
+    pub fn count_by_reason(
+        related_channel: &Channel,
+        conn: &mut SqliteConnection,
+    ) -> QueryResult<Vec<(String, i64)>> {
+        use crate::db::schema::skipped_submission::dsl as skipped_dsl;
+        SkippedSubmission::belonging_to(related_channel)
+            .group_by(skipped_dsl::reason)
+            .select((skipped_dsl::reason, count(skipped_dsl::id)))
+            .order(count(skipped_dsl::id).desc())
+            .load::<(String, i64)>(conn)
+    }
+    pub fn get_latest(
+        related_channel: &Channel,
+        limit: i64,
+        conn: &mut SqliteConnection,
+    ) -> QueryResult<Vec<SkippedSubmission>> {
+        use crate::db::schema::skipped_submission::dsl as skipped_dsl;
+        SkippedSubmission::belonging_to(related_channel)
+            .order(skipped_dsl::skipped_at.desc())
+            .limit(limit)
+            .load::<SkippedSubmission>(conn)
+    }
+    /// Whether the submission was skipped in the channel for one of the given reasons.
+    pub fn is_skipped_for(
+        channel: &Channel,
+        fullname: &str,
+        reasons: &[&str],
+        conn: &mut SqliteConnection,
+    ) -> QueryResult<bool> {
+        use crate::db::schema::skipped_submission::dsl as skipped_dsl;
+        skipped_dsl::skipped_submission
+            .select(count(skipped_dsl::id))
+            .filter(skipped_dsl::channel_id.eq(channel.id))
+            .filter(skipped_dsl::fullname.eq(fullname))
+            .filter(skipped_dsl::reason.eq_any(reasons))
+            .first::<i64>(conn)
+            .map(|count| count > 0)
+    }
+    /// Drops the skip record of a submission that made it through on a later poll.
+    pub fn forget(
+        channel: &Channel,
+        fullname: &str,
+        conn: &mut SqliteConnection,
+    ) -> QueryResult<usize> {
+        use crate::db::schema::skipped_submission::dsl as skipped_dsl;
+        diesel::delete(skipped_dsl::skipped_submission)
+            .filter(skipped_dsl::channel_id.eq(channel.id))
+            .filter(skipped_dsl::fullname.eq(fullname))
+            .execute(conn)
+    }
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = skipped_submission)]
+pub struct NewSkippedSubmission<'a> {
+    channel_id: i32,
+    fullname: &'a str,
+    reason: &'a str,
+    detail: Option<String>,
+}
+
+impl<'a> NewSkippedSubmission<'a> {
+    pub fn new(
+        channel: &Channel,
+        fullname: &'a str,
+        reason: &'a str,
+        detail: Option<String>,
+    ) -> Self {
+        NewSkippedSubmission {
+            channel_id: channel.id,
+            fullname,
+            reason,
+            detail,
+        }
+    }
+    /// Records the skip unless the submission was already skipped in this channel.
+    ///
+    /// Returns whether a new record was created.
+    pub fn insert(self, conn: &mut SqliteConnection) -> QueryResult<bool> {
+        use crate::db::schema::skipped_submission::dsl::*;
+        diesel::insert_or_ignore_into(skipped_submission)
+            .values(&self)
+            .execute(conn)
+            .map(|inserted_rows| inserted_rows > 0)
+    }
+}
+
+/// What was done to the messages of a mirrored submission that disappeared from Reddit.
+#[derive(Identifiable, Selectable, Queryable, Associations, Clone, Debug)]
+#[diesel(belongs_to(Channel))]
+#[diesel(table_name = moderation_action)]
+pub struct ModerationAction {
+    pub id: i32,
+    pub channel_id: i32,
+    pub fullname: String,
+    pub reason: String,
+    pub action: String,
+    pub detail: Option<String>,
+    pub acted_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = moderation_action)]
+pub struct NewModerationAction<'a> {
+    channel_id: i32,
+    fullname: &'a str,
+    reason: &'a str,
+    action: &'a str,
+    detail: Option<String>,
+}
+
+impl<'a> NewModerationAction<'a> {
+    pub fn new(
+        posted: &'a PostedSubmission,
+        reason: &'a str,
+        action: &'a str,
+        detail: Option<String>,
+    ) -> Self {
+        NewModerationAction {
+            channel_id: posted.channel_id,
+            fullname: &posted.fullname,
+            reason,
+            action,
+            detail,
+        }
+    }
+    pub fn insert(self, conn: &mut SqliteConnection) -> QueryResult<ModerationAction> {
+        use crate::db::schema::moderation_action::dsl::*;
+        diesel::insert_into(moderation_action)
+            .values(&self)
+            .execute(conn)?;
+        moderation_action.order(id.desc()).first(conn)
+    }
+}
+
+#[derive(Identifiable, Selectable, Queryable, Debug)]
+#[diesel(primary_key(chat_id))]
+#[diesel(table_name = dialogue_state)]
+pub struct DialogueState {
+    pub chat_id: i64,
+    pub state: String,
+    pub updated_at: NaiveDateTime,
+}
+
+impl DialogueState {
+    pub fn get(chat_id: ChatId, conn: &mut SqliteConnection) -> QueryResult<Option<DialogueState>> {
+        use crate::db::schema::dialogue_state::dsl as dialogue_dsl;
+        dialogue_dsl::dialogue_state
+            .filter(dialogue_dsl::chat_id.eq(chat_id.0))
+            .first::<DialogueState>(conn)
+            .optional()
+    }
+    pub fn save(chat_id: ChatId, state: &str, conn: &mut SqliteConnection) -> QueryResult<usize> {
+        use crate::db::schema::dialogue_state::dsl as dialogue_dsl;
+        diesel::replace_into(dialogue_dsl::dialogue_state)
+            .values((
+                dialogue_dsl::chat_id.eq(chat_id.0),
+                dialogue_dsl::state.eq(state),
+                dialogue_dsl::updated_at.eq(Utc::now().naive_utc()),
+            ))
+            .execute(conn)
+    }
+    pub fn delete(chat_id: ChatId, conn: &mut SqliteConnection) -> QueryResult<usize> {
+        use crate::db::schema::dialogue_state::dsl as dialogue_dsl;
+        diesel::delete(dialogue_dsl::dialogue_state)
+            .filter(dialogue_dsl::chat_id.eq(chat_id.0))
+            .execute(conn)
+    }
+    pub fn delete_updated_before(
+        cutoff: NaiveDateTime,
+        conn: &mut SqliteConnection,
+    ) -> QueryResult<usize> {
+        use crate::db::schema::dialogue_state::dsl as dialogue_dsl;
+        diesel::delete(dialogue_dsl::dialogue_state)
+            .filter(dialogue_dsl::updated_at.lt(cutoff))
+            .execute(conn)
+    }
+}
+
+/// The single row holding bot-wide switches.
+#[derive(Identifiable, Selectable, Queryable, Debug)]
+#[diesel(table_name = bot_status)]
+pub struct BotStatus {
+    pub id: i32,
+    /// Set while the superadmins stopped all mirroring.
+    pub paused: bool,
+    pub paused_at: Option<NaiveDateTime>,
+}
+
+impl BotStatus {
+    pub fn get(conn: &mut SqliteConnection) -> QueryResult<BotStatus> {
+        use crate::db::schema::bot_status::dsl as status_dsl;
+        status_dsl::bot_status.first::<BotStatus>(conn)
+    }
+    pub fn is_paused(conn: &mut SqliteConnection) -> QueryResult<bool> {
+        BotStatus::get(conn).map(|status| status.paused)
+    }
+    pub fn set_paused(
+        paused: bool,
+        actor: Option<UserId>,
+        conn: &mut SqliteConnection,
+    ) -> QueryResult<usize> {
+        use crate::db::schema::bot_status::dsl as status_dsl;
+        let updated = diesel::update(status_dsl::bot_status)
+            .set((
+                status_dsl::paused.eq(paused),
+                status_dsl::paused_at.eq(paused.then(|| Utc::now().naive_utc())),
+            ))
+            .execute(conn)?;
+        let action = if paused {
+            AuditAction::PauseAll
+        } else {
+            AuditAction::ResumeAll
+        };
+        audit::<()>(actor, action, None, None, None, None, conn)?;
+        Ok(updated)
+    }
+}
+
+/// Counters describing the whole instance, shown to the superadmins.
+#[derive(Debug)]
+pub struct InstanceStats {
+    pub channels: i64,
+    pub paused_channels: i64,
+    pub subreddits: i64,
+    pub paused_subreddits: i64,
+    pub links: i64,
+    pub queued_posts: i64,
+    pub posted_total: i64,
+    pub posted_since: i64,
+}
+
+impl InstanceStats {
+    /// Collects the counters, `posted_since` counting the posts delivered after `cutoff`.
+    pub fn collect(cutoff: NaiveDateTime, conn: &mut SqliteConnection) -> QueryResult<Self> {
+        use crate::db::schema::{
+            channel::dsl as channel_dsl, channel_subreddit::dsl as channel_sub_dsl,
+            posted_submission::dsl as posted_dsl, queued_post::dsl as queued_dsl,
+            subreddit::dsl as sub_dsl,
+        };
+        Ok(InstanceStats {
+            channels: channel_dsl::channel.count().get_result(conn)?,
+            paused_channels: channel_dsl::channel
+                .filter(channel_dsl::disabled.eq(true))
+                .count()
+                .get_result(conn)?,
+            subreddits: sub_dsl::subreddit.count().get_result(conn)?,
+            paused_subreddits: sub_dsl::subreddit
+                .filter(sub_dsl::disabled.eq(true))
+                .count()
+                .get_result(conn)?,
+            links: channel_sub_dsl::channel_subreddit
+                .count()
+                .get_result(conn)?,
+            queued_posts: queued_dsl::queued_post.count().get_result(conn)?,
+            posted_total: posted_dsl::posted_submission.count().get_result(conn)?,
+            posted_since: posted_dsl::posted_submission
+                .filter(posted_dsl::posted_at.gt(cutoff))
+                .count()
+                .get_result(conn)?,
+        })
+    }
+}
+
+/// What an [`AuditEntry`] records.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, FromSqlRow, AsExpression, Serialize, Deserialize)]
+#[diesel(sql_type = Text)]
+pub enum AuditAction {
+    LinkChannel,
+    UnlinkChannel,
+    LinkSubreddit,
+    UnlinkSubreddit,
+    /// Settings of a channel, e.g. its schedule or caption template.
+    EditChannel,
+    /// Settings of a subreddit shared by every channel linked to it.
+    EditSubreddit,
+    /// Settings of a single channel–subreddit link.
+    EditLink,
+    AddKeywordRule,
+    DeleteKeywordRule,
+    EditFlairRule,
+    PauseAll,
+    ResumeAll,
+}
+
+impl AuditAction {
+    pub const ALL: [AuditAction; 12] = [
+        AuditAction::LinkChannel,
+        AuditAction::UnlinkChannel,
+        AuditAction::LinkSubreddit,
+        AuditAction::UnlinkSubreddit,
+        AuditAction::EditChannel,
+        AuditAction::EditSubreddit,
+        AuditAction::EditLink,
+        AuditAction::AddKeywordRule,
+        AuditAction::DeleteKeywordRule,
+        AuditAction::EditFlairRule,
+        AuditAction::PauseAll,
+        AuditAction::ResumeAll,
+    ];
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::LinkChannel => "link_channel",
+            AuditAction::UnlinkChannel => "unlink_channel",
+            AuditAction::LinkSubreddit => "link_subreddit",
+            AuditAction::UnlinkSubreddit => "unlink_subreddit",
+            AuditAction::EditChannel => "edit_channel",
+            AuditAction::EditSubreddit => "edit_subreddit",
+            AuditAction::EditLink => "edit_link",
+            AuditAction::AddKeywordRule => "add_keyword_rule",
+            AuditAction::DeleteKeywordRule => "delete_keyword_rule",
+            AuditAction::EditFlairRule => "edit_flair_rule",
+            AuditAction::PauseAll => "pause_all",
+            AuditAction::ResumeAll => "resume_all",
+        }
+    }
+    /// Human readable description, `{}` standing for the subreddit name.
+    fn describe(&self) -> &'static str {
+        match self {
+            AuditAction::LinkChannel => "linked the channel",
+            AuditAction::UnlinkChannel => "unlinked the channel",
+            AuditAction::LinkSubreddit => "linked r/{}",
+            AuditAction::UnlinkSubreddit => "unlinked r/{}",
+            AuditAction::EditChannel => "changed the channel settings",
+            AuditAction::EditSubreddit => "changed the settings of r/{} shared by all channels",
+            AuditAction::EditLink => "changed the settings of r/{}",
+            AuditAction::AddKeywordRule => "added a keyword rule to r/{}",
+            AuditAction::DeleteKeywordRule => "deleted a keyword rule of r/{}",
+            AuditAction::EditFlairRule => "changed the flair rules of r/{}",
+            AuditAction::PauseAll => "paused all mirroring",
+            AuditAction::ResumeAll => "resumed all mirroring",
+        }
+    }
+    /// Whether the values of added or removed rows are worth showing, unlike whole channels.
+    fn shows_rows(&self) -> bool {
+        matches!(
+            self,
+            AuditAction::AddKeywordRule
+                | AuditAction::DeleteKeywordRule
+                | AuditAction::EditFlairRule
+        )
+    }
+}
+
+impl FromStr for AuditAction {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        AuditAction::ALL
+            .into_iter()
+            .find(|action| action.as_str() == value)
+            .ok_or_else(|| format!("Unknown audit action \"{}\"", value))
+    }
+}
+
+impl FromSql<sql_types::Text, Sqlite> for AuditAction
+where
+    String: FromSql<sql_types::Text, Sqlite>,
+{
+    fn from_sql(value: SqliteValue) -> diesel::deserialize::Result<Self> {
+        let value = <String as FromSql<Text, Sqlite>>::from_sql(value)?;
+        value.parse().map_err(|_| {
+            format!(
+                "Encountered unexpected audit action in database: \"{}\".",
+                value
+            )
+            .into()
+        })
+    }
+}
+
+impl ToSql<sql_types::Text, Sqlite> for AuditAction {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> serialize::Result {
+        out.set_value(self.as_str());
+        Ok(IsNull::No)
+    }
+}
+
+/// A change made to the configuration, kept to tell who did what.
+#[derive(Identifiable, Selectable, Queryable, Clone, Debug)]
+#[diesel(table_name = audit_log)]
+pub struct AuditEntry {
+    pub id: i32,
+    /// Telegram user who made the change, unknown for changes made by the bot itself.
+    pub actor_id: Option<i64>,
+    pub action: AuditAction,
+    pub chat_id: Option<i64>,
+    pub subreddit: Option<String>,
+    /// JSON object of the changed fields before the change, or of the removed row.
+    pub old_value: Option<String>,
+    /// JSON object of the changed fields after the change, or of the added row.
+    pub new_value: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+impl AuditEntry {
+    /// The latest entries about a channel, including the shared settings of the subreddits
+    /// currently linked to it.
+    pub fn get_latest(
+        channel: &Channel,
+        limit: i64,
+        conn: &mut SqliteConnection,
+    ) -> QueryResult<Vec<AuditEntry>> {
+        use crate::db::schema::audit_log::dsl as audit_dsl;
+        let subreddits: Vec<String> = Subreddit::get_by_channel(channel.clone(), conn)?
+            .into_iter()
+            .map(|subreddit| subreddit.name)
+            .collect();
+        audit_dsl::audit_log
+            .filter(
+                audit_dsl::chat_id.eq(channel.chat_id).or(audit_dsl::chat_id
+                    .is_null()
+                    .and(audit_dsl::subreddit.eq_any(subreddits))),
+            )
+            .order(audit_dsl::id.desc())
+            .limit(limit)
+            .load::<AuditEntry>(conn)
+    }
+}
+
+/// Renders the fields of a JSON object as `key: value` pairs, leaving out row ids.
+fn describe_fields(value: &Option<String>) -> Vec<(String, String)> {
+    let fields = match value
+        .as_deref()
+        .and_then(|value| serde_json::from_str::<serde_json::Value>(value).ok())
+    {
+        Some(serde_json::Value::Object(fields)) => fields,
+        _ => return Vec::new(),
+    };
+    fields
+        .into_iter()
+        .filter(|(key, _)| !matches!(key.as_str(), "id" | "channel_id" | "subreddit_id"))
+        .map(|(key, value)| (key, value.to_string()))
+        .collect()
+}
+
+impl fmt::Display for AuditEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} by {}: {}",
+            self.created_at.format("%Y-%m-%d %H:%M"),
+            self.actor_id
+                .map(|actor_id| format!("user {}", actor_id))
+                .unwrap_or_else(|| "the bot".to_owned()),
+            self.action
+                .describe()
+                .replace("{}", self.subreddit.as_deref().unwrap_or("?"))
+        )?;
+        let old = describe_fields(&self.old_value);
+        let new = describe_fields(&self.new_value);
+        match (self.old_value.is_some(), self.new_value.is_some()) {
+            (true, true) => {
+                for (key, new_value) in &new {
+                    let old_value = old
+                        .iter()
+                        .find(|(old_key, _)| old_key == key)
+                        .map(|(_, old_value)| old_value.as_str())
+                        .unwrap_or("null");
+                    write!(f, "\n  {}: {} → {}", key, old_value, new_value)?;
+                }
+                Ok(())
+            }
+            _ if self.action.shows_rows() => {
+                for (key, value) in old.iter().chain(&new) {
+                    write!(f, "\n  {}: {}", key, value)?;
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = audit_log)]
+pub struct NewAuditEntry<'a> {
+    actor_id: Option<i64>,
+    action: AuditAction,
+    chat_id: Option<i64>,
+    subreddit: Option<&'a str>,
+    old_value: Option<String>,
+    new_value: Option<String>,
+}
+
+impl<'a> NewAuditEntry<'a> {
+    /// Describes the change of a row from `before` to `after`, keeping only the fields that
+    /// differ. Either side is `None` when the row was added or removed.
+    ///
+    /// Returns `None` when nothing changed.
+    pub fn new<T: serde::Serialize>(
+        actor: Option<UserId>,
+        action: AuditAction,
+        chat_id: Option<i64>,
+        subreddit: Option<&'a str>,
+        before: Option<&T>,
+        after: Option<&T>,
+    ) -> Option<Self> {
+        let to_object = |row: &T| match serde_json::to_value(row) {
+            Ok(serde_json::Value::Object(fields)) => fields,
+            _ => serde_json::Map::new(),
+        };
+        let (old_value, new_value) = match (before.map(to_object), after.map(to_object)) {
+            (Some(before), Some(after)) => {
+                let (old, new): (serde_json::Map<_, _>, serde_json::Map<_, _>) = after
+                    .into_iter()
+                    .filter(|(key, value)| before.get(key) != Some(value))
+                    .map(|(key, value)| {
+                        let old = before.get(&key).cloned().unwrap_or_default();
+                        ((key.clone(), old), (key, value))
+                    })
+                    .unzip();
+                if new.is_empty() {
+                    return None;
+                }
+                (Some(old), Some(new))
+            }
+            (before, after) => (before, after),
+        };
+        Some(NewAuditEntry {
+            actor_id: actor.map(|actor| actor.0 as i64),
+            action,
+            chat_id,
+            subreddit,
+            old_value: old_value.map(|fields| serde_json::Value::Object(fields).to_string()),
+            new_value: new_value.map(|fields| serde_json::Value::Object(fields).to_string()),
+        })
+    }
+    pub fn insert(self, conn: &mut SqliteConnection) -> QueryResult<usize> {
+        use crate::db::schema::audit_log::dsl::*;
+        diesel::insert_into(audit_log).values(&self).execute(conn)
+    }
+}
+
+/// Stores the change of a row in the audit log, unless nothing changed.
+fn audit<T: serde::Serialize>(
+    actor: Option<UserId>,
+    action: AuditAction,
+    chat_id: Option<i64>,
+    subreddit: Option<&str>,
+    before: Option<&T>,
+    after: Option<&T>,
+    conn: &mut SqliteConnection,
+) -> QueryResult<()> {
+    if let Some(entry) = NewAuditEntry::new(actor, action, chat_id, subreddit, before, after) {
+        entry.insert(conn)?;
+    }
+    Ok(())
+}
+
+/// The channel and subreddit a link connects, as the audit log names them.
+fn link_target(
+    channel_id: i32,
+    subreddit_id: i32,
+    conn: &mut SqliteConnection,
+) -> QueryResult<(i64, String)> {
+    Ok((
+        Channel::get_by_id(channel_id, conn)?.chat_id,
+        Subreddit::get_by_id(subreddit_id, conn)?.name,
+    ))
+}
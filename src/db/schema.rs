@@ -1,5 +1,26 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    audit_log (id) {
+        id -> Integer,
+        actor_id -> Nullable<BigInt>,
+        action -> Text,
+        chat_id -> Nullable<BigInt>,
+        subreddit -> Nullable<Text>,
+        old_value -> Nullable<Text>,
+        new_value -> Nullable<Text>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    bot_status (id) {
+        id -> Integer,
+        paused -> Bool,
+        paused_at -> Nullable<Timestamp>,
+    }
+}
+
 diesel::table! {
     channel (id) {
         id -> Integer,
@@ -8,6 +29,14 @@ diesel::table! {
         title -> Text,
         username -> Nullable<Text>,
         invite_link -> Nullable<Text>,
+        caption_template -> Nullable<Text>,
+        timezone -> Text,
+        posting_windows -> Nullable<Text>,
+        posting_times -> Nullable<Text>,
+        min_post_interval -> Nullable<Integer>,
+        spread_posts -> Bool,
+        buttons -> Text,
+        owner_id -> Nullable<BigInt>,
     }
 }
 
@@ -16,6 +45,114 @@ diesel::table! {
         id -> Nullable<Integer>,
         channel_id -> Integer,
         subreddit_id -> Integer,
+        caption_template -> Nullable<Text>,
+        daily_limit -> Nullable<Integer>,
+        quota_policy -> Text,
+        posted_today -> Integer,
+        posted_today_on -> Nullable<Date>,
+        comments_enabled -> Bool,
+        comment_limit -> Integer,
+        comment_min_score -> Nullable<Integer>,
+        comment_depth -> Integer,
+        post_limit -> Nullable<Integer>,
+        respect_external_content_flag -> Bool,
+        min_score -> Nullable<Integer>,
+        allow_nsfw -> Bool,
+        show_spoilers -> Bool,
+        medias_only -> Bool,
+    }
+}
+
+diesel::table! {
+    dialogue_state (chat_id) {
+        chat_id -> BigInt,
+        state -> Text,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    flair_rule (id) {
+        id -> Integer,
+        channel_id -> Integer,
+        subreddit_id -> Integer,
+        list -> Text,
+        flair_text -> Text,
+        template_id -> Nullable<Text>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    keyword_rule (id) {
+        id -> Integer,
+        channel_id -> Integer,
+        subreddit_id -> Integer,
+        kind -> Text,
+        pattern -> Text,
+        is_regex -> Bool,
+        case_sensitive -> Bool,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    moderation_action (id) {
+        id -> Integer,
+        channel_id -> Integer,
+        fullname -> Text,
+        reason -> Text,
+        action -> Text,
+        detail -> Nullable<Text>,
+        acted_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    posted_submission (id) {
+        id -> Integer,
+        channel_id -> Integer,
+        fullname -> Text,
+        message_ids -> Text,
+        posted_at -> Timestamp,
+        score -> Integer,
+        subreddit_id -> Nullable<Integer>,
+        canonical_url -> Nullable<Text>,
+        crosspost_parent -> Nullable<Text>,
+        media_id -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    queued_post (id) {
+        id -> Integer,
+        channel_id -> Integer,
+        subreddit_id -> Integer,
+        fullname -> Text,
+        submission -> Text,
+        queued_at -> Timestamp,
+        deferred -> Bool,
+    }
+}
+
+diesel::table! {
+    seen_flair (id) {
+        id -> Integer,
+        subreddit_id -> Integer,
+        text -> Text,
+        template_id -> Nullable<Text>,
+        last_seen_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    skipped_submission (id) {
+        id -> Integer,
+        channel_id -> Integer,
+        fullname -> Text,
+        reason -> Text,
+        detail -> Nullable<Text>,
+        skipped_at -> Timestamp,
     }
 }
 
@@ -26,20 +163,37 @@ diesel::table! {
         subreddit_id -> Text,
         name -> Text,
         sorting -> Text,
-        post_limit -> Nullable<Integer>,
-        respect_external_content_flag -> Bool,
-        min_score -> Nullable<Integer>,
-        allow_nsfw -> Bool,
-        show_spoilers -> Bool,
-        medias_only -> Bool,
+        sort_period -> Text,
+        last_fetched_at -> Nullable<Timestamp>,
     }
 }
 
 diesel::joinable!(channel_subreddit -> channel (channel_id));
 diesel::joinable!(channel_subreddit -> subreddit (subreddit_id));
+diesel::joinable!(flair_rule -> channel (channel_id));
+diesel::joinable!(flair_rule -> subreddit (subreddit_id));
+diesel::joinable!(keyword_rule -> channel (channel_id));
+diesel::joinable!(keyword_rule -> subreddit (subreddit_id));
+diesel::joinable!(moderation_action -> channel (channel_id));
+diesel::joinable!(posted_submission -> channel (channel_id));
+diesel::joinable!(posted_submission -> subreddit (subreddit_id));
+diesel::joinable!(queued_post -> channel (channel_id));
+diesel::joinable!(queued_post -> subreddit (subreddit_id));
+diesel::joinable!(seen_flair -> subreddit (subreddit_id));
+diesel::joinable!(skipped_submission -> channel (channel_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    audit_log,
+    bot_status,
     channel,
     channel_subreddit,
+    dialogue_state,
+    flair_rule,
+    keyword_rule,
+    moderation_action,
+    posted_submission,
+    queued_post,
+    seen_flair,
+    skipped_submission,
     subreddit,
 );
@@ -1,20 +1,34 @@
 mod db;
+mod mirror;
 mod reddit_bot;
 mod settings;
 mod teloxide;
 
+use std::sync::{Arc, Mutex};
+
 use crate::teloxide::setup_teloxide;
-use db::establish_connection;
-use diesel_migrations::{embed_migrations, EmbeddedMigrations};
+use ::teloxide::Bot;
+use db::{establish_connection, print_migration_status};
+use mirror::{outbox::Outbox, setup_mirror};
 use settings::SETTINGS_INSTANCE;
 
-pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!();
-
 #[tokio::main]
 async fn main() {
+    pretty_env_logger::init();
+    if std::env::args().any(|arg| arg == "--migration-status") {
+        print_migration_status().expect("Couldn't read the migration status");
+        return;
+    }
+    let db = Arc::new(Mutex::new(establish_connection()));
     let reddit_bot = reddit_bot::setup_roux(&SETTINGS_INSTANCE.reddit)
         .await
         .expect("Couldn't instantiate Reddit API connection");
-    let db = establish_connection();
-    setup_teloxide(reddit_bot, db).await;
+    let bot = Bot::new(&SETTINGS_INSTANCE.teloxide.token);
+    // Shared so that messages sent from commands count towards the same flood limits.
+    let outbox = Outbox::new(
+        SETTINGS_INSTANCE.mirror.messages_per_second,
+        SETTINGS_INSTANCE.mirror.messages_per_minute_per_chat,
+    );
+    setup_mirror(bot.clone(), outbox.clone(), reddit_bot.clone(), db.clone());
+    setup_teloxide(bot, outbox, reddit_bot, db).await;
 }
@@ -0,0 +1,413 @@
+mod buttons;
+pub mod caption;
+pub mod comments;
+mod delivery;
+mod duplicate;
+pub mod filter;
+mod markdown;
+mod media;
+pub mod outbox;
+mod recheck;
+pub mod schedule;
+
+use std::{
+    error::Error,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use caption::Template;
+use chrono::Utc;
+use diesel::{OptionalExtension, SqliteConnection};
+use filter::{KeywordFilter, SkipReason};
+use futures::future::join_all;
+use log::{info, warn};
+use outbox::Outbox;
+use schedule::Schedule;
+use teloxide::Bot;
+
+use crate::{
+    db::models::{
+        BotStatus, Channel, ChannelSubreddit, FlairRule, KeywordRule, NewPostedSubmission,
+        NewQueuedPost, NewSkippedSubmission, PostedSubmission, QueuedPost, QuotaPolicy, SeenFlair,
+        SkippedSubmission, Subreddit,
+    },
+    reddit_bot::{fetch_submissions, submission::Submission},
+    settings::SETTINGS_INSTANCE,
+};
+
+type MirrorResult<T> = Result<T, Box<dyn Error + Send + Sync>>;
+
+/// Spawns the background tasks that poll every enabled subreddit, queue new submissions
+/// for the channels linked to it, deliver the queued posts on each channel's schedule and
+/// take down posts that were removed from Reddit.
+pub fn setup_mirror(
+    bot: Bot,
+    outbox: Outbox,
+    reddit_bot: roux::Me,
+    conn: Arc<Mutex<SqliteConnection>>,
+) {
+    let (poll_reddit_bot, poll_conn) = (reddit_bot.clone(), conn.clone());
+    tokio::spawn(async move {
+        let mut interval =
+            tokio::time::interval(Duration::from_secs(SETTINGS_INSTANCE.mirror.poll_interval));
+        loop {
+            interval.tick().await;
+            if let Err(error) = mirror_cycle(&poll_reddit_bot, &poll_conn).await {
+                warn!("Mirroring cycle failed: {}", error);
+            }
+        }
+    });
+    let (recheck_bot, recheck_outbox, recheck_conn) = (bot.clone(), outbox.clone(), conn.clone());
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(
+            SETTINGS_INSTANCE.mirror.recheck_interval,
+        ));
+        loop {
+            interval.tick().await;
+            if let Err(error) =
+                recheck::recheck_cycle(&recheck_bot, &recheck_outbox, &reddit_bot, &recheck_conn)
+                    .await
+            {
+                warn!("Re-checking mirrored submissions failed: {}", error);
+            }
+        }
+    });
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(
+            SETTINGS_INSTANCE.mirror.dispatch_interval,
+        ));
+        loop {
+            interval.tick().await;
+            if let Err(error) = dispatch_cycle(&bot, &outbox, &conn).await {
+                warn!("Dispatching queued posts failed: {}", error);
+            }
+        }
+    });
+}
+
+/// Fetches every enabled subreddit and queues the submissions that passed the filters.
+async fn mirror_cycle(
+    reddit_bot: &roux::Me,
+    conn: &Arc<Mutex<SqliteConnection>>,
+) -> MirrorResult<()> {
+    // Stopped by the superadmins with /pause_all.
+    if BotStatus::is_paused(&mut conn.lock().unwrap())? {
+        return Ok(());
+    }
+    let subreddits = Subreddit::get_enabled(&mut conn.lock().unwrap())?;
+    for subreddit in subreddits {
+        let submissions =
+            match fetch_submissions(reddit_bot, &subreddit, SETTINGS_INSTANCE.mirror.fetch_limit)
+                .await
+            {
+                Ok(submissions) => submissions,
+                Err(error) => {
+                    warn!("Couldn't fetch r/{}: {}", subreddit.name, error);
+                    continue;
+                }
+            };
+        {
+            let conn = &mut conn.lock().unwrap();
+            subreddit.record_fetch(conn)?;
+            for submission in &submissions {
+                if let Some(flair) = submission
+                    .link_flair_text
+                    .as_deref()
+                    .map(str::trim)
+                    .filter(|flair| !flair.is_empty())
+                {
+                    SeenFlair::record(
+                        &subreddit,
+                        flair,
+                        submission.link_flair_template_id.as_deref(),
+                        conn,
+                    )?;
+                }
+            }
+        }
+        let channels: Vec<Channel> =
+            Channel::get_by_subreddit(subreddit.clone(), &mut conn.lock().unwrap())?
+                .into_iter()
+                .filter(|channel| !channel.disabled)
+                .collect();
+        for channel in &channels {
+            let conn = &mut conn.lock().unwrap();
+            let link = ChannelSubreddit::get(channel, &subreddit, conn)?;
+            let keywords = KeywordFilter::new(&KeywordRule::get_by_link(&link, conn)?);
+            let flair_rules = FlairRule::get_by_link(&link, conn)?;
+            let mut queued = 0;
+            // Posts deferred by earlier polls take up this poll's limit first.
+            for deferred_post in QueuedPost::get_deferred(&link, conn)? {
+                if filter::check_quota(link.post_limit, None, queued, 0).is_err() {
+                    break;
+                }
+                deferred_post.release(conn)?;
+                queued += 1;
+            }
+            // Listings are newest first, post in chronological order instead.
+            for submission in submissions.iter().rev() {
+                if PostedSubmission::is_posted(channel, &submission.name, conn)?
+                    || QueuedPost::is_queued(channel, &submission.name, conn)?
+                    || SkippedSubmission::is_skipped_for(
+                        channel,
+                        &submission.name,
+                        &SkipReason::QUOTA_CODES,
+                        conn,
+                    )?
+                {
+                    continue;
+                }
+                if let Some(original) = duplicate::find_duplicate(conn, channel, submission)? {
+                    let reason = SkipReason::Duplicate {
+                        original: original.fullname,
+                    };
+                    record_channel_skip(conn, channel, submission, &reason)?;
+                    continue;
+                }
+                if let Err(reason) = filter::check(&link, submission)
+                    .and_then(|()| keywords.check(submission))
+                    .and_then(|()| filter::check_flair(&flair_rules, submission))
+                {
+                    record_channel_skip(conn, channel, submission, &reason)?;
+                    continue;
+                }
+                let deferred = match filter::check_quota(link.post_limit, None, queued, 0) {
+                    Ok(()) => false,
+                    Err(reason) if link.quota_policy == QuotaPolicy::Drop => {
+                        record_drop(conn, channel, submission, &reason)?;
+                        continue;
+                    }
+                    Err(_) => true,
+                };
+                if !deferred {
+                    queued += 1;
+                }
+                NewQueuedPost::new(
+                    channel,
+                    &subreddit,
+                    &submission.name,
+                    serde_json::to_string(submission)?,
+                    deferred,
+                )
+                .insert(conn)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Delivers the queued posts of every channel whose schedule allows it.
+async fn dispatch_cycle(
+    bot: &Bot,
+    outbox: &Outbox,
+    conn: &Arc<Mutex<SqliteConnection>>,
+) -> MirrorResult<()> {
+    if BotStatus::is_paused(&mut conn.lock().unwrap())? {
+        return Ok(());
+    }
+    let channels = QueuedPost::get_channels(&mut conn.lock().unwrap())?;
+    // Channels are served side by side, the outbox keeps them within Telegram's limits.
+    join_all(
+        channels
+            .into_iter()
+            .filter(|channel| !channel.disabled)
+            .map(|channel| async move {
+                if let Err(error) = dispatch_channel(bot, outbox, conn, &channel).await {
+                    warn!(
+                        "Couldn't dispatch the queue of channel {}: {}",
+                        channel.chat_id, error
+                    );
+                }
+            }),
+    )
+    .await;
+    Ok(())
+}
+
+/// Delivers the queued posts of one channel in order, for as long as its schedule allows.
+async fn dispatch_channel(
+    bot: &Bot,
+    outbox: &Outbox,
+    conn: &Arc<Mutex<SqliteConnection>>,
+    channel: &Channel,
+) -> MirrorResult<()> {
+    let schedule = Schedule::for_channel(channel)?;
+    let queued_posts = QueuedPost::get_by_channel(channel, &mut conn.lock().unwrap())?;
+    let queued_count = queued_posts.len();
+    for (position, queued_post) in queued_posts.into_iter().enumerate() {
+        let last_posted_at = PostedSubmission::last_posted_at(channel, &mut conn.lock().unwrap())?;
+        if !schedule.is_due(Utc::now(), last_posted_at, queued_count - position) {
+            break;
+        }
+        let (subreddit, link) = {
+            let conn = &mut conn.lock().unwrap();
+            let subreddit = Subreddit::get_by_id(queued_post.subreddit_id, conn)?;
+            match ChannelSubreddit::get(channel, &subreddit, conn).optional()? {
+                Some(link) => (subreddit, link),
+                None => {
+                    // The subreddit was unlinked while the post waited.
+                    queued_post.delete(conn)?;
+                    continue;
+                }
+            }
+        };
+        let submission: Submission = match serde_json::from_str(&queued_post.submission) {
+            Ok(submission) => submission,
+            Err(error) => {
+                warn!(
+                    "Dropping unreadable queued post {} of channel {}: {}",
+                    queued_post.fullname, channel.chat_id, error
+                );
+                queued_post.delete(&mut conn.lock().unwrap())?;
+                continue;
+            }
+        };
+        {
+            // Another submission of the same content may have been delivered while this one
+            // waited in the queue.
+            let conn = &mut conn.lock().unwrap();
+            if let Some(original) = duplicate::find_duplicate(conn, channel, &submission)? {
+                let reason = SkipReason::Duplicate {
+                    original: original.fullname,
+                };
+                record_channel_skip(conn, channel, &submission, &reason)?;
+                queued_post.delete(conn)?;
+                continue;
+            }
+        }
+        let today = Utc::now().date_naive();
+        if let Err(reason) = filter::check_quota(None, link.daily_limit, 0, link.posted_on(today)) {
+            // Deferred posts stay queued until the next day.
+            if link.quota_policy == QuotaPolicy::Drop {
+                let conn = &mut conn.lock().unwrap();
+                record_drop(conn, channel, &submission, &reason)?;
+                queued_post.delete(conn)?;
+            }
+            continue;
+        }
+        let template = caption_template(conn, channel, &subreddit)?;
+        match deliver_once(
+            bot,
+            outbox,
+            conn,
+            channel,
+            &subreddit,
+            &submission,
+            &template,
+        )
+        .await
+        {
+            Ok(Some(_)) => {
+                info!(
+                    "Mirrored {} from r/{} to channel {}",
+                    submission.name, subreddit.name, channel.chat_id
+                );
+                let conn = &mut conn.lock().unwrap();
+                link.count_post(today, conn)?;
+                queued_post.delete(conn)?;
+            }
+            Ok(None) => {
+                queued_post.delete(&mut conn.lock().unwrap())?;
+            }
+            // The post stays queued and is retried on the next dispatch.
+            Err(error) => warn!(
+                "Couldn't mirror {} to channel {}: {}",
+                submission.name, channel.chat_id, error
+            ),
+        }
+    }
+    Ok(())
+}
+
+/// Picks the template of the channel–subreddit link, falling back to the channel's one.
+fn caption_template(
+    conn: &Arc<Mutex<SqliteConnection>>,
+    channel: &Channel,
+    subreddit: &Subreddit,
+) -> MirrorResult<Template> {
+    let link = ChannelSubreddit::get(channel, subreddit, &mut conn.lock().unwrap())?;
+    let source = link
+        .caption_template
+        .or_else(|| channel.caption_template.clone());
+    Ok(match source {
+        Some(source) => source.parse().unwrap_or_else(|error| {
+            warn!(
+                "Invalid caption template for channel {}: {}. Using the default one.",
+                channel.chat_id, error
+            );
+            Template::default()
+        }),
+        None => Template::default(),
+    })
+}
+
+/// Stores why a submission was held back from a single channel.
+fn record_channel_skip(
+    conn: &mut SqliteConnection,
+    channel: &Channel,
+    submission: &Submission,
+    reason: &SkipReason,
+) -> MirrorResult<()> {
+    let newly_skipped =
+        NewSkippedSubmission::new(channel, &submission.name, reason.code(), reason.detail())
+            .insert(conn)?;
+    if newly_skipped {
+        info!(
+            "Skipped {} for channel {}: {}",
+            submission.name, channel.chat_id, reason
+        );
+    }
+    Ok(())
+}
+
+/// Records a post a [`QuotaPolicy::Drop`] link gave up on, so later polls leave it alone.
+fn record_drop(
+    conn: &mut SqliteConnection,
+    channel: &Channel,
+    submission: &Submission,
+    reason: &SkipReason,
+) -> MirrorResult<()> {
+    // An earlier skip for another reason would otherwise keep the drop from being stored.
+    SkippedSubmission::forget(channel, &submission.name, conn)?;
+    NewSkippedSubmission::new(channel, &submission.name, reason.code(), reason.detail())
+        .insert(conn)?;
+    info!(
+        "Dropped {} for channel {}: {}",
+        submission.name, channel.chat_id, reason
+    );
+    Ok(())
+}
+
+/// Delivers a submission to a channel unless the posted history says it's already there.
+///
+/// Returns `None` when the submission was delivered to this channel before.
+async fn deliver_once(
+    bot: &Bot,
+    outbox: &Outbox,
+    conn: &Arc<Mutex<SqliteConnection>>,
+    channel: &Channel,
+    subreddit: &Subreddit,
+    submission: &Submission,
+    template: &Template,
+) -> MirrorResult<Option<PostedSubmission>> {
+    if PostedSubmission::is_posted(channel, &submission.name, &mut conn.lock().unwrap())? {
+        return Ok(None);
+    }
+    let message_ids = delivery::deliver(bot, outbox, channel, submission, template).await?;
+    let score = submission.score.clamp(i32::MIN as i64, i32::MAX as i64) as i32;
+    let conn = &mut conn.lock().unwrap();
+    SkippedSubmission::forget(channel, &submission.name, conn)?;
+    let keys = duplicate::content_keys(submission);
+    NewPostedSubmission::new(
+        channel,
+        subreddit,
+        &submission.name,
+        &keys,
+        &message_ids,
+        score,
+    )
+    .insert(conn)
+    .map(Some)
+    .map_err(|x| x.into())
+}
@@ -0,0 +1,45 @@
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup};
+use url::Url;
+
+use crate::{db::models::PostButton, reddit_bot::submission::Submission};
+
+/// Hosts of Reddit itself and of the media it hosts, which don't count as a source.
+const REDDIT_HOSTS: [&str; 2] = ["reddit.com", "redd.it"];
+
+/// The page an external link post points to.
+fn source_url(submission: &Submission) -> Option<Url> {
+    if submission.is_self {
+        return None;
+    }
+    let url = Url::parse(submission.url.as_deref()?).ok()?;
+    let host = url.host_str()?;
+    let is_reddit = REDDIT_HOSTS
+        .iter()
+        .any(|reddit_host| host == *reddit_host || host.ends_with(&format!(".{}", reddit_host)));
+    (!is_reddit).then_some(url)
+}
+
+/// Builds the row of buttons shown under a post, leaving out the ones that don't apply.
+pub(super) fn keyboard(
+    buttons: &[PostButton],
+    submission: &Submission,
+) -> Option<InlineKeyboardMarkup> {
+    let permalink = Url::parse(&submission.reddit_link()).ok()?;
+    let row: Vec<InlineKeyboardButton> = buttons
+        .iter()
+        .filter_map(|button| match button {
+            PostButton::Reddit => Some(InlineKeyboardButton::url(
+                "Open on Reddit",
+                permalink.clone(),
+            )),
+            PostButton::Comments => Some(InlineKeyboardButton::url(
+                format!("Comments ({})", submission.num_comments),
+                permalink.clone(),
+            )),
+            PostButton::Source => {
+                source_url(submission).map(|url| InlineKeyboardButton::url("Source", url))
+            }
+        })
+        .collect();
+    (!row.is_empty()).then(|| InlineKeyboardMarkup::new([row]))
+}
@@ -0,0 +1,681 @@
+use std::{error::Error, fmt, str::FromStr};
+
+use teloxide::utils::html::escape;
+
+use super::markdown::to_telegram_html;
+use crate::{db::models::Channel, reddit_bot::submission::Submission};
+
+/// Used by channels that haven't configured a template of their own.
+pub const DEFAULT_TEMPLATE: &str = "<b>{title}</b>{?link}\n\n{url}{/link}\n\n{permalink}";
+
+/// Shown to admins when they're about to write a template.
+pub const TEMPLATE_HELP: &str = "Placeholders: {title}, {author}, {subreddit}, {score}, {permalink}, {flair}, {url}, {channel_username}, {selftext}.
+Conditionals: {?nsfw}...{/nsfw}, {?spoiler}...{/spoiler}, {?flair}...{/flair} and {?link}...{/link} (external link posts). Use {!nsfw}...{/nsfw} etc. for the opposite.
+Telegram HTML tags like <b>, <i>, <u>, <s>, <code>, <a href=\"...\"> and <tg-spoiler> can be used for formatting. Write {{ and }} for literal braces.";
+
+/// Tags Telegram accepts in HTML formatted messages.
+const ALLOWED_TAGS: [&str; 13] = [
+    "b",
+    "strong",
+    "i",
+    "em",
+    "u",
+    "ins",
+    "s",
+    "strike",
+    "del",
+    "code",
+    "pre",
+    "a",
+    "tg-spoiler",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Title,
+    Author,
+    Subreddit,
+    Score,
+    Permalink,
+    Flair,
+    Url,
+    ChannelUsername,
+    Selftext,
+}
+
+impl FromStr for Field {
+    type Err = TemplateError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Ok(match value {
+            "title" => Field::Title,
+            "author" => Field::Author,
+            "subreddit" => Field::Subreddit,
+            "score" => Field::Score,
+            "permalink" => Field::Permalink,
+            "flair" => Field::Flair,
+            "url" => Field::Url,
+            "channel_username" => Field::ChannelUsername,
+            "selftext" => Field::Selftext,
+            _ => return Err(TemplateError(format!("Unknown placeholder {{{}}}", value))),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Condition {
+    Nsfw,
+    Spoiler,
+    Flair,
+    Link,
+}
+
+impl FromStr for Condition {
+    type Err = TemplateError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Ok(match value {
+            "nsfw" => Condition::Nsfw,
+            "spoiler" => Condition::Spoiler,
+            "flair" => Condition::Flair,
+            "link" => Condition::Link,
+            _ => return Err(TemplateError(format!("Unknown condition \"{}\"", value))),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Segment {
+    /// Template text, already validated Telegram HTML.
+    Html(String),
+    Field(Field),
+    Conditional {
+        condition: Condition,
+        negated: bool,
+        body: Vec<Segment>,
+    },
+}
+
+/// An open conditional (negated or not) and the segments parsed inside it so far.
+type Frame = (Option<(Condition, bool)>, Vec<Segment>);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TemplateError(String);
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Error for TemplateError {}
+
+/// A parsed caption template, rendered into Telegram HTML for every mirrored post.
+#[derive(Debug, Clone)]
+pub struct Template {
+    segments: Vec<Segment>,
+}
+
+impl Default for Template {
+    fn default() -> Self {
+        DEFAULT_TEMPLATE
+            .parse()
+            .expect("The default caption template is valid")
+    }
+}
+
+impl FromStr for Template {
+    type Err = TemplateError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        let mut stack: Vec<Frame> = vec![(None, Vec::new())];
+        let mut literal = String::new();
+        let mut chars = source.chars().peekable();
+        while let Some(current) = chars.next() {
+            match current {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '}' => return Err(TemplateError("Unmatched \"}\", write }} instead".into())),
+                '{' => {
+                    let mut token = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(token_char) => token.push(token_char),
+                            None => {
+                                return Err(TemplateError(format!(
+                                    "Unclosed placeholder \"{{{}\"",
+                                    token
+                                )))
+                            }
+                        }
+                    }
+                    let frame = stack.last_mut().expect("The root frame is never popped");
+                    if !literal.is_empty() {
+                        frame.1.push(Segment::Html(std::mem::take(&mut literal)));
+                    }
+                    let token = token.trim().to_lowercase();
+                    if let Some(name) = token.strip_prefix('?') {
+                        stack.push((Some((name.parse()?, false)), Vec::new()));
+                    } else if let Some(name) = token.strip_prefix('!') {
+                        stack.push((Some((name.parse()?, true)), Vec::new()));
+                    } else if let Some(name) = token.strip_prefix('/') {
+                        let closed: Condition = name.parse()?;
+                        match stack.pop() {
+                            Some((Some((condition, negated)), body)) if condition == closed => {
+                                stack
+                                    .last_mut()
+                                    .expect("The root frame is never popped")
+                                    .1
+                                    .push(Segment::Conditional {
+                                        condition,
+                                        negated,
+                                        body,
+                                    });
+                            }
+                            _ => {
+                                return Err(TemplateError(format!(
+                                    "{{/{}}} doesn't close an open conditional",
+                                    name
+                                )))
+                            }
+                        }
+                    } else {
+                        frame.1.push(Segment::Field(token.parse()?));
+                    }
+                }
+                _ => literal.push(current),
+            }
+        }
+        if !literal.is_empty() {
+            stack
+                .last_mut()
+                .expect("The root frame is never popped")
+                .1
+                .push(Segment::Html(literal));
+        }
+        if stack.len() > 1 {
+            return Err(TemplateError("A conditional is never closed".into()));
+        }
+        let (_, mut segments) = stack.pop().expect("The root frame is never popped");
+        sanitize(&mut segments)?;
+        Ok(Template { segments })
+    }
+}
+
+/// Checks that the HTML of every conditional body is balanced on its own and escapes stray
+/// `&` and `>` characters so Telegram accepts them.
+fn sanitize(segments: &mut [Segment]) -> Result<(), TemplateError> {
+    let mut open_tags: Vec<String> = Vec::new();
+    for segment in segments.iter_mut() {
+        match segment {
+            Segment::Html(html) => *html = sanitize_html(html, &mut open_tags)?,
+            Segment::Conditional { body, .. } => sanitize(body)?,
+            Segment::Field(_) => (),
+        }
+    }
+    match open_tags.pop() {
+        Some(tag) => Err(TemplateError(format!("Tag <{}> is never closed", tag))),
+        None => Ok(()),
+    }
+}
+
+fn sanitize_html(html: &str, open_tags: &mut Vec<String>) -> Result<String, TemplateError> {
+    let mut sanitized = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(current) = rest.chars().next() {
+        match current {
+            '<' => {
+                let end = rest.find('>').ok_or_else(|| {
+                    TemplateError("Unclosed \"<\", write &lt; for a literal one".into())
+                })?;
+                let tag = &rest[1..end];
+                if let Some(name) = tag.strip_prefix('/') {
+                    let name = name.trim().to_lowercase();
+                    match open_tags.pop() {
+                        Some(open) if open == name => (),
+                        _ => {
+                            return Err(TemplateError(format!(
+                                "Closing tag </{}> doesn't match an open tag",
+                                name
+                            )))
+                        }
+                    }
+                } else {
+                    let name = tag
+                        .split_whitespace()
+                        .next()
+                        .unwrap_or_default()
+                        .to_lowercase();
+                    let attributes = tag[name.len()..].trim();
+                    let valid = match name.as_str() {
+                        "a" => valid_href(attributes),
+                        "span" => attributes == "class=\"tg-spoiler\"",
+                        "code" | "pre" => {
+                            attributes.is_empty() || attributes.starts_with("class=\"language-")
+                        }
+                        _ => ALLOWED_TAGS.contains(&name.as_str()) && attributes.is_empty(),
+                    };
+                    if !valid {
+                        return Err(TemplateError(format!(
+                            "Telegram doesn't support the tag <{}>",
+                            tag
+                        )));
+                    }
+                    open_tags.push(name);
+                }
+                sanitized += &rest[..=end];
+                rest = &rest[end + 1..];
+                continue;
+            }
+            '>' => sanitized += "&gt;",
+            '&' if !starts_with_entity(rest) => sanitized += "&amp;",
+            _ => sanitized.push(current),
+        }
+        rest = &rest[current.len_utf8()..];
+    }
+    Ok(sanitized)
+}
+
+/// Telegram only accepts a single quoted `href` pointing to a web page or a `tg://` link.
+fn valid_href(attributes: &str) -> bool {
+    let Some(value) = attributes.strip_prefix("href=") else {
+        return false;
+    };
+    let quote = match value.chars().next() {
+        Some(quote @ ('"' | '\'')) => quote,
+        _ => return false,
+    };
+    let Some(url) = value[1..].strip_suffix(quote) else {
+        return false;
+    };
+    let url = url.to_lowercase();
+    !url.contains(quote)
+        && ["http://", "https://", "tg://"]
+            .iter()
+            .any(|scheme| url.starts_with(scheme) && url.len() > scheme.len())
+}
+
+fn starts_with_entity(text: &str) -> bool {
+    match text.find(';') {
+        Some(end) if end > 1 => text[1..end]
+            .trim_start_matches('#')
+            .chars()
+            .all(|entity_char| entity_char.is_ascii_alphanumeric()),
+        _ => false,
+    }
+}
+
+/// The values a template is rendered with.
+#[derive(Debug, Clone)]
+pub struct CaptionContext {
+    pub title: String,
+    pub author: String,
+    pub subreddit: String,
+    pub score: i64,
+    pub permalink: String,
+    pub flair: Option<String>,
+    pub url: String,
+    pub channel_username: Option<String>,
+    pub selftext: String,
+    pub nsfw: bool,
+    pub spoiler: bool,
+    pub is_link: bool,
+}
+
+impl CaptionContext {
+    pub fn new(submission: &Submission, channel: &Channel) -> Self {
+        let permalink = submission.reddit_link();
+        let url = submission.url.clone().unwrap_or_else(|| permalink.clone());
+        CaptionContext {
+            title: submission.title.clone(),
+            author: submission.author.clone(),
+            subreddit: submission.subreddit.clone(),
+            score: submission.score,
+            is_link: !submission.is_self && !url.contains(&submission.permalink),
+            permalink,
+            flair: submission
+                .link_flair_text
+                .clone()
+                .filter(|flair| !flair.is_empty()),
+            url,
+            channel_username: channel.username.clone(),
+            selftext: submission.selftext.clone(),
+            nsfw: submission.over_18,
+            spoiler: submission.spoiler,
+        }
+    }
+    /// Made up values used to preview templates before saving them.
+    pub fn sample(channel: &Channel) -> Self {
+        CaptionContext {
+            title: "Look at this <cat> & its friends".into(),
+            author: "example_user".into(),
+            subreddit: "pics".into(),
+            score: 1234,
+            permalink: "https://www.reddit.com/r/pics/comments/abc123/look_at_this_cat/".into(),
+            flair: Some("Cats".into()),
+            url: "https://example.com/cat".into(),
+            channel_username: channel.username.clone(),
+            selftext: "Some **bold** text with a [link](https://example.com).".into(),
+            nsfw: false,
+            spoiler: false,
+            is_link: true,
+        }
+    }
+}
+
+impl Template {
+    /// Renders the template into Telegram HTML no longer than `limit` visible characters,
+    /// shortening the self text first and the title second when needed.
+    pub fn render(&self, context: &CaptionContext, limit: usize) -> String {
+        let selftext_len = context.selftext.chars().count();
+        for selftext_limit in [selftext_len, 600, 300, 100, 0] {
+            if selftext_limit > selftext_len {
+                continue;
+            }
+            let selftext = truncate(&context.selftext, selftext_limit);
+            let rendered = self.render_with(context, &context.title, &selftext);
+            if visible_len(&rendered) <= limit {
+                return rendered;
+            }
+        }
+        let rendered = self.render_with(context, &context.title, "");
+        let overflow = visible_len(&rendered).saturating_sub(limit);
+        let title_len = context.title.chars().count();
+        if overflow < title_len {
+            let title = truncate(&context.title, title_len - overflow);
+            return self.render_with(context, &title, "");
+        }
+        // Cut before escaping, an entity cut in half is invalid HTML.
+        escape(&truncate(
+            &format!("{}\n\n{}", context.title, context.permalink),
+            limit,
+        ))
+    }
+
+    fn render_with(&self, context: &CaptionContext, title: &str, selftext: &str) -> String {
+        let mut rendered = String::new();
+        render_segments(&self.segments, context, title, selftext, &mut rendered);
+        rendered.trim().to_owned()
+    }
+}
+
+fn render_segments(
+    segments: &[Segment],
+    context: &CaptionContext,
+    title: &str,
+    selftext: &str,
+    rendered: &mut String,
+) {
+    for segment in segments {
+        match segment {
+            Segment::Html(html) => *rendered += html,
+            Segment::Field(field) => {
+                *rendered += &match field {
+                    Field::Title => escape(title),
+                    Field::Author => escape(&context.author),
+                    Field::Subreddit => escape(&context.subreddit),
+                    Field::Score => context.score.to_string(),
+                    Field::Permalink => escape(&context.permalink),
+                    Field::Flair => escape(context.flair.as_deref().unwrap_or_default()),
+                    Field::Url => escape(&context.url),
+                    Field::ChannelUsername => context
+                        .channel_username
+                        .as_ref()
+                        .map(|username| format!("@{}", escape(username)))
+                        .unwrap_or_default(),
+                    Field::Selftext => to_telegram_html(selftext),
+                }
+            }
+            Segment::Conditional {
+                condition,
+                negated,
+                body,
+            } => {
+                let holds = match condition {
+                    Condition::Nsfw => context.nsfw,
+                    Condition::Spoiler => context.spoiler,
+                    Condition::Flair => context.flair.is_some(),
+                    Condition::Link => context.is_link,
+                };
+                if holds != *negated {
+                    render_segments(body, context, title, selftext, rendered);
+                }
+            }
+        }
+    }
+}
+
+pub(super) fn truncate(text: &str, limit: usize) -> String {
+    if text.chars().count() <= limit {
+        return text.to_owned();
+    }
+    if limit == 0 {
+        return String::new();
+    }
+    text.chars().take(limit - 1).chain(['…']).collect()
+}
+
+/// Length of the text Telegram displays for the given HTML, which is what its limits apply to.
+fn visible_len(html: &str) -> usize {
+    let mut length = 0;
+    let mut in_tag = false;
+    let mut in_entity = false;
+    for current in html.chars() {
+        match current {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            _ if in_tag => (),
+            '&' => {
+                in_entity = true;
+                length += 1;
+            }
+            ';' if in_entity => in_entity = false,
+            _ if in_entity => (),
+            _ => length += 1,
+        }
+    }
+    length
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context() -> CaptionContext {
+        CaptionContext {
+            title: "Cats & dogs".into(),
+            author: "someone".into(),
+            subreddit: "pics".into(),
+            score: 42,
+            permalink: "https://www.reddit.com/r/pics/comments/abc123/cats/".into(),
+            flair: None,
+            url: "https://example.com/?a=1&b=2".into(),
+            channel_username: Some("channel".into()),
+            selftext: String::new(),
+            nsfw: false,
+            spoiler: false,
+            is_link: true,
+        }
+    }
+
+    fn render(source: &str, context: &CaptionContext) -> String {
+        source
+            .parse::<Template>()
+            .expect("The template is valid")
+            .render(context, 1024)
+    }
+
+    #[test]
+    fn renders_escaped_placeholders() {
+        assert_eq!(
+            render("<b>{title}</b> by {author} in r/{subreddit} ({score}) {url} {channel_username}", &context()),
+            "<b>Cats &amp; dogs</b> by someone in r/pics (42) https://example.com/?a=1&amp;b=2 @channel"
+        );
+        assert_eq!(render("{ Title }", &context()), "Cats &amp; dogs");
+    }
+
+    #[test]
+    fn renders_literal_braces() {
+        assert_eq!(render("{{title}} {{ }}", &context()), "{title} { }");
+        assert_eq!(render("{{{title}}}", &context()), "{Cats &amp; dogs}");
+    }
+
+    #[test]
+    fn renders_conditionals() {
+        let template = "{?nsfw}NSFW {/nsfw}{!nsfw}SFW {/nsfw}{?flair}[{flair}]{/flair}";
+        assert_eq!(render(template, &context()), "SFW");
+        let context = CaptionContext {
+            nsfw: true,
+            flair: Some("Cute".into()),
+            ..context()
+        };
+        assert_eq!(render(template, &context), "NSFW [Cute]");
+    }
+
+    #[test]
+    fn renders_nested_conditionals() {
+        let template = "{?link}link{?nsfw} nsfw{!spoiler} visible{/spoiler}{/nsfw}{/link}";
+        assert_eq!(render(template, &context()), "link");
+        let context = CaptionContext {
+            nsfw: true,
+            ..context()
+        };
+        assert_eq!(render(template, &context), "link nsfw visible");
+        let context = CaptionContext {
+            spoiler: true,
+            ..context
+        };
+        assert_eq!(render(template, &context), "link nsfw");
+    }
+
+    #[test]
+    fn rejects_malformed_templates() {
+        for source in [
+            "{?nsfw}never closed",
+            "{?nsfw}{?flair}inner closed only{/flair}",
+            "{?nsfw}wrong close{/flair}",
+            "{/nsfw}",
+            "{title",
+            "lone } brace",
+            "{unknown}",
+            "{?unknown}{/unknown}",
+            "<b>not closed",
+            "<b>{?nsfw}</b>{/nsfw}",
+            "<i>crossed <b>tags</i></b>",
+            "<script>alert(1)</script>",
+            "a < b",
+        ] {
+            assert!(
+                source.parse::<Template>().is_err(),
+                "{} was accepted",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn checks_link_targets() {
+        for source in [
+            "<a href=\"https://example.com\">web</a>",
+            "<a href='http://example.com'>web</a>",
+            "<a href=\"tg://user?id=1\">user</a>",
+        ] {
+            assert!(
+                source.parse::<Template>().is_ok(),
+                "{} was rejected",
+                source
+            );
+        }
+        for source in [
+            "<a href=\"javascript:alert(1)\">script</a>",
+            "<a href=https://example.com>unquoted</a>",
+            "<a href=\"https://example.com'>mixed quotes</a>",
+            "<a href=\"https://\">empty</a>",
+            "<a href=\"https://example.com\" onclick=\"x\">extra</a>",
+            "<a>no target</a>",
+        ] {
+            assert!(
+                source.parse::<Template>().is_err(),
+                "{} was accepted",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn escapes_stray_html_characters() {
+        assert_eq!(
+            render("1 > 0 & true &amp; done", &context()),
+            "1 &gt; 0 &amp; true &amp; done"
+        );
+    }
+
+    #[test]
+    fn shortens_the_selftext_first() {
+        let context = CaptionContext {
+            selftext: "word ".repeat(300),
+            ..context()
+        };
+        let rendered = "{title}\n\n{selftext}"
+            .parse::<Template>()
+            .unwrap()
+            .render(&context, 200);
+        assert!(visible_len(&rendered) <= 200);
+        assert!(rendered.starts_with("Cats &amp; dogs\n\nword"));
+    }
+
+    #[test]
+    fn shortens_the_title_when_the_selftext_is_gone() {
+        let context = CaptionContext {
+            title: "a".repeat(300),
+            ..context()
+        };
+        let rendered = "{title} {permalink}"
+            .parse::<Template>()
+            .unwrap()
+            .render(&context, 100);
+        assert_eq!(visible_len(&rendered), 100);
+        assert!(rendered.ends_with(&context.permalink));
+        assert!(rendered.contains('…'));
+    }
+
+    #[test]
+    fn falls_back_without_cutting_entities() {
+        let context = CaptionContext {
+            title: "&".repeat(30),
+            ..context()
+        };
+        let template = "{permalink} {permalink} {title}"
+            .parse::<Template>()
+            .unwrap();
+        for limit in [10, 25, 33, 40] {
+            let rendered = template.render(&context, limit);
+            assert!(
+                visible_len(&rendered) <= limit,
+                "{} is over {}",
+                rendered,
+                limit
+            );
+            for (index, _) in rendered.match_indices('&') {
+                assert!(
+                    starts_with_entity(&rendered[index..]),
+                    "{} cuts an entity",
+                    rendered
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn counts_visible_characters() {
+        assert_eq!(visible_len("<b>a &amp; b</b>"), 5);
+        assert_eq!(visible_len("<a href=\"https://example.com\">x</a>"), 1);
+    }
+}
@@ -0,0 +1,137 @@
+use std::sync::{Arc, Mutex};
+
+use diesel::{OptionalExtension, SqliteConnection};
+use log::info;
+use teloxide::{
+    prelude::*,
+    types::{MessageId, ParseMode},
+    utils::html::escape,
+};
+
+use super::{caption::truncate, delivery::MESSAGE_LIMIT, markdown::to_telegram_html, MirrorResult};
+use crate::{
+    db::models::{BotStatus, Channel, ChannelSubreddit, PostedSubmission, Subreddit},
+    reddit_bot::{
+        comment::{Comment, Replies},
+        fetch_comments,
+    },
+};
+
+/// Comments fetched to pick the best ones from.
+const FETCH_LIMIT: u32 = 100;
+/// Room left in each comment's share of the message for the author line.
+const HEADER_ALLOWANCE: usize = 80;
+
+/// Replies to the copy of a mirrored post that Telegram forwarded into the channel's
+/// discussion group with the best Reddit comments of the submission.
+///
+/// Forwards of anything but the first message of a post, or of posts whose link doesn't
+/// mirror comments, are ignored.
+pub async fn mirror_comments(
+    bot: &Bot,
+    reddit_bot: &roux::Me,
+    conn: &Arc<Mutex<SqliteConnection>>,
+    msg: &Message,
+) -> MirrorResult<()> {
+    let (channel_chat, message_id) = match (msg.forward_from_chat(), msg.forward_from_message_id())
+    {
+        (Some(chat), Some(message_id)) => (chat, MessageId(message_id)),
+        _ => return Ok(()),
+    };
+    let (posted, link) = {
+        let conn = &mut conn.lock().unwrap();
+        if BotStatus::is_paused(conn)? {
+            return Ok(());
+        }
+        let channel = match Channel::get_by_chat_id(channel_chat.id, conn).optional()? {
+            Some(channel) => channel,
+            None => return Ok(()),
+        };
+        let posted = match PostedSubmission::get_by_first_message(&channel, message_id, conn)? {
+            Some(posted) => posted,
+            None => return Ok(()),
+        };
+        // Posts mirrored before the subreddit was recorded can't be matched to their link.
+        let subreddit = match posted.subreddit_id {
+            Some(subreddit_id) => Subreddit::get_by_id(subreddit_id, conn)?,
+            None => return Ok(()),
+        };
+        match ChannelSubreddit::get(&channel, &subreddit, conn).optional()? {
+            Some(link) if link.comments_enabled => (posted, link),
+            _ => return Ok(()),
+        }
+    };
+    let replies = fetch_comments(
+        reddit_bot,
+        &posted.fullname,
+        link.comment_depth as u32,
+        FETCH_LIMIT,
+    )
+    .await?;
+    let comments = pick_comments(&replies, &link);
+    if comments.is_empty() {
+        return Ok(());
+    }
+    bot.send_message(msg.chat.id, render_comments(&comments))
+        .parse_mode(ParseMode::Html)
+        .disable_web_page_preview(true)
+        .reply_to_message_id(msg.id)
+        .await?;
+    info!(
+        "Mirrored {} comments of {} to discussion group {}",
+        comments.len(),
+        posted.fullname,
+        msg.chat.id
+    );
+    Ok(())
+}
+
+/// The highest scored comments allowed by the link's settings, with their depth.
+fn pick_comments<'a>(replies: &'a Replies, link: &ChannelSubreddit) -> Vec<(i32, &'a Comment)> {
+    let mut comments = Vec::new();
+    collect_comments(replies, 0, link, &mut comments);
+    comments.sort_by_key(|(_, comment)| std::cmp::Reverse(comment.score));
+    comments.truncate(link.comment_limit.max(0) as usize);
+    comments
+}
+
+fn collect_comments<'a>(
+    replies: &'a Replies,
+    depth: i32,
+    link: &ChannelSubreddit,
+    comments: &mut Vec<(i32, &'a Comment)>,
+) {
+    if depth > link.comment_depth {
+        return;
+    }
+    for comment in replies.comments() {
+        // Stickied comments are usually moderator notices rather than discussion.
+        if comment.is_removed() || comment.stickied {
+            continue;
+        }
+        if link
+            .comment_min_score
+            .is_none_or(|min_score| comment.score >= min_score as i64)
+        {
+            comments.push((depth, comment));
+        }
+        collect_comments(&comment.replies, depth + 1, link, comments);
+    }
+}
+
+fn render_comments(comments: &[(i32, &Comment)]) -> String {
+    let body_limit = (MESSAGE_LIMIT / comments.len()).saturating_sub(HEADER_ALLOWANCE);
+    comments
+        .iter()
+        .map(|(depth, comment)| {
+            format!(
+                "{}<b>u/{}</b> · {} points\n{}",
+                if *depth > 0 { "↳ " } else { "" },
+                escape(&comment.author),
+                comment.score,
+                to_telegram_html(&truncate(comment.body.trim(), body_limit))
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
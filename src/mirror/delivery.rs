@@ -0,0 +1,227 @@
+use log::warn;
+use teloxide::{
+    prelude::*,
+    types::{
+        InlineKeyboardMarkup, InputFile, InputMedia, InputMediaPhoto, InputMediaVideo, MessageId,
+        ParseMode,
+    },
+    RequestError,
+};
+
+use super::{
+    buttons::keyboard,
+    caption::{CaptionContext, Template},
+    media::{classify, GalleryMedia, PostMedia},
+    outbox::Outbox,
+    MirrorResult,
+};
+use crate::{db::models::Channel, reddit_bot::submission::Submission};
+
+/// Telegram refuses captions longer than this.
+const CAPTION_LIMIT: usize = 1024;
+/// Telegram refuses text messages longer than this.
+pub(super) const MESSAGE_LIMIT: usize = 4096;
+/// Telegram refuses media groups with more items than this.
+const MEDIA_GROUP_LIMIT: usize = 10;
+/// Text of the message carrying the buttons of a media group, which can't have any itself.
+const BUTTONS_TEXT: &str = "Links to the post above:";
+
+/// Sends a single submission to a channel and returns the ids of the messages it produced.
+///
+/// Media Telegram can't handle natively, or refuses to fetch, goes out as a text message
+/// with the link instead.
+pub(super) async fn deliver(
+    bot: &Bot,
+    outbox: &Outbox,
+    channel: &Channel,
+    submission: &Submission,
+    template: &Template,
+) -> MirrorResult<Vec<MessageId>> {
+    let chat_id = ChatId(channel.chat_id);
+    let context = CaptionContext::new(submission, channel);
+    let keyboard = keyboard(&channel.post_buttons(), submission);
+    let sent = match classify(submission) {
+        PostMedia::Text => {
+            return send_text(bot, outbox, chat_id, template, &context, keyboard).await
+        }
+        media => {
+            send_media(
+                bot,
+                outbox,
+                chat_id,
+                media,
+                template.render(&context, CAPTION_LIMIT),
+                keyboard.clone(),
+            )
+            .await
+        }
+    };
+    match sent {
+        Ok(message_ids) => Ok(message_ids),
+        Err(RequestError::Api(error)) => {
+            warn!(
+                "Telegram refused the media of {}: {}. Sending a link instead.",
+                submission.name, error
+            );
+            send_text(bot, outbox, chat_id, template, &context, keyboard).await
+        }
+        Err(error) => Err(error.into()),
+    }
+}
+
+async fn send_text(
+    bot: &Bot,
+    outbox: &Outbox,
+    chat_id: ChatId,
+    template: &Template,
+    context: &CaptionContext,
+    keyboard: Option<InlineKeyboardMarkup>,
+) -> MirrorResult<Vec<MessageId>> {
+    let mut request = bot
+        .send_message(chat_id, template.render(context, MESSAGE_LIMIT))
+        .parse_mode(ParseMode::Html);
+    request.reply_markup = keyboard.map(Into::into);
+    let message = outbox.send(chat_id, request).await?;
+    Ok(vec![message.id])
+}
+
+async fn send_media(
+    bot: &Bot,
+    outbox: &Outbox,
+    chat_id: ChatId,
+    media: PostMedia,
+    caption: String,
+    keyboard: Option<InlineKeyboardMarkup>,
+) -> Result<Vec<MessageId>, RequestError> {
+    match media {
+        PostMedia::Gallery(items) => {
+            send_gallery(bot, outbox, chat_id, items, caption, keyboard).await
+        }
+        single => Ok(vec![
+            send_single(bot, outbox, chat_id, single, caption, keyboard).await?,
+        ]),
+    }
+}
+
+async fn send_single(
+    bot: &Bot,
+    outbox: &Outbox,
+    chat_id: ChatId,
+    media: PostMedia,
+    caption: String,
+    keyboard: Option<InlineKeyboardMarkup>,
+) -> Result<MessageId, RequestError> {
+    let reply_markup = keyboard.map(Into::into);
+    let message = match media {
+        PostMedia::Photo(url) => {
+            let mut request = bot
+                .send_photo(chat_id, InputFile::url(url))
+                .caption(caption)
+                .parse_mode(ParseMode::Html);
+            request.reply_markup = reply_markup;
+            outbox.send(chat_id, request).await?
+        }
+        PostMedia::Video(url) => {
+            let mut request = bot
+                .send_video(chat_id, InputFile::url(url))
+                .caption(caption)
+                .parse_mode(ParseMode::Html);
+            request.reply_markup = reply_markup;
+            outbox.send(chat_id, request).await?
+        }
+        PostMedia::Animation(url) => {
+            let mut request = bot
+                .send_animation(chat_id, InputFile::url(url))
+                .caption(caption)
+                .parse_mode(ParseMode::Html);
+            request.reply_markup = reply_markup;
+            outbox.send(chat_id, request).await?
+        }
+        PostMedia::Gallery(_) | PostMedia::Text => {
+            unreachable!("only single media are sent on their own")
+        }
+    };
+    Ok(message.id)
+}
+
+/// Sends the gallery as media groups of up to ten items, captioning only the first one.
+///
+/// Media groups can't carry buttons, so unless the caption ended up on a lone item they
+/// follow the gallery in a message of their own.
+async fn send_gallery(
+    bot: &Bot,
+    outbox: &Outbox,
+    chat_id: ChatId,
+    items: Vec<GalleryMedia>,
+    caption: String,
+    mut keyboard: Option<InlineKeyboardMarkup>,
+) -> Result<Vec<MessageId>, RequestError> {
+    let mut caption = Some(caption);
+    let mut message_ids = Vec::with_capacity(items.len());
+    for chunk in items.chunks(MEDIA_GROUP_LIMIT) {
+        if let [single] = chunk {
+            let single = match single.clone() {
+                GalleryMedia::Photo(url) => PostMedia::Photo(url),
+                GalleryMedia::Video(url) => PostMedia::Animation(url),
+            };
+            let item_keyboard = if caption.is_some() {
+                keyboard.take()
+            } else {
+                None
+            };
+            message_ids.push(
+                send_single(
+                    bot,
+                    outbox,
+                    chat_id,
+                    single,
+                    caption.take().unwrap_or_default(),
+                    item_keyboard,
+                )
+                .await?,
+            );
+            continue;
+        }
+        let group: Vec<InputMedia> = chunk
+            .iter()
+            .map(|item| {
+                let item_caption = caption.take();
+                match item.clone() {
+                    GalleryMedia::Photo(url) => {
+                        let photo = InputMediaPhoto::new(InputFile::url(url));
+                        InputMedia::Photo(match item_caption {
+                            Some(item_caption) => {
+                                photo.caption(item_caption).parse_mode(ParseMode::Html)
+                            }
+                            None => photo,
+                        })
+                    }
+                    GalleryMedia::Video(url) => {
+                        let video = InputMediaVideo::new(InputFile::url(url));
+                        InputMedia::Video(match item_caption {
+                            Some(item_caption) => {
+                                video.caption(item_caption).parse_mode(ParseMode::Html)
+                            }
+                            None => video,
+                        })
+                    }
+                }
+            })
+            .collect();
+        let messages = outbox
+            .send_many(chat_id, group.len(), bot.send_media_group(chat_id, group))
+            .await?;
+        message_ids.extend(messages.iter().map(|message| message.id));
+    }
+    if let Some(keyboard) = keyboard {
+        let message = outbox
+            .send(
+                chat_id,
+                bot.send_message(chat_id, BUTTONS_TEXT)
+                    .reply_markup(keyboard),
+            )
+            .await?;
+        message_ids.push(message.id);
+    }
+    Ok(message_ids)
+}
@@ -0,0 +1,108 @@
+use chrono::{Duration, Utc};
+use diesel::{QueryResult, SqliteConnection};
+use url::{form_urlencoded, Url};
+
+use crate::{
+    db::models::{Channel, ContentKeys, PostedSubmission},
+    reddit_bot::submission::Submission,
+    settings::SETTINGS_INSTANCE,
+};
+
+/// Query parameters that only tell where a link was shared from.
+const TRACKING_PARAMS: [&str; 8] = [
+    "fbclid", "gclid", "igshid", "mc_cid", "mc_eid", "ref", "ref_src", "si",
+];
+/// Hosts serving the media Reddit hosts itself, named after the media id.
+const REDDIT_MEDIA_HOSTS: [&str; 3] = ["i.redd.it", "v.redd.it", "preview.redd.it"];
+
+fn is_tracking_param(name: &str) -> bool {
+    name.starts_with("utm_") || TRACKING_PARAMS.contains(&name)
+}
+
+/// The linked URL reduced to what tells two links apart.
+///
+/// Self posts and cross-posts of them link to Reddit itself and have none.
+fn canonical_url(submission: &Submission) -> Option<String> {
+    if submission.is_self {
+        return None;
+    }
+    let url = Url::parse(submission.url.as_deref()?).ok()?;
+    let host = url.host_str()?.to_ascii_lowercase();
+    let host = host
+        .strip_prefix("www.")
+        .or_else(|| host.strip_prefix("m."))
+        .unwrap_or(&host);
+    let mut params: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(name, _)| !is_tracking_param(name))
+        .map(|(name, value)| (name.into_owned(), value.into_owned()))
+        .collect();
+    params.sort();
+    let mut canonical = format!("{}{}", host, url.path().trim_end_matches('/'));
+    if !params.is_empty() {
+        canonical.push('?');
+        canonical += &form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(params)
+            .finish();
+    }
+    Some(canonical)
+}
+
+/// The media id in the URL of a file hosted by Reddit, e.g. `abc123` for
+/// `https://i.redd.it/abc123.jpg` or `https://v.redd.it/abc123/DASH_720.mp4`.
+fn reddit_media_id(url: &str) -> Option<String> {
+    let url = Url::parse(url).ok()?;
+    if !REDDIT_MEDIA_HOSTS.contains(&url.host_str()?) {
+        return None;
+    }
+    let segment = url.path_segments()?.next()?;
+    let id = segment.split('.').next()?;
+    (!id.is_empty()).then(|| id.to_owned())
+}
+
+fn media_id(submission: &Submission) -> Option<String> {
+    if let Some(item) = submission
+        .gallery_data
+        .as_ref()
+        .and_then(|gallery| gallery.items.first())
+    {
+        return Some(item.media_id.clone());
+    }
+    submission
+        .url
+        .as_deref()
+        .and_then(reddit_media_id)
+        .or_else(|| {
+            let video = submission.media.as_ref()?.reddit_video.as_ref()?;
+            reddit_media_id(&video.fallback_url)
+        })
+}
+
+pub(super) fn content_keys(submission: &Submission) -> ContentKeys {
+    ContentKeys {
+        canonical_url: canonical_url(submission),
+        crosspost_parent: submission.crosspost_parent.clone(),
+        media_id: media_id(submission),
+    }
+}
+
+/// The post the channel already received within the duplicate window with the same content
+/// as `submission`, if any.
+pub(super) fn find_duplicate(
+    conn: &mut SqliteConnection,
+    channel: &Channel,
+    submission: &Submission,
+) -> QueryResult<Option<PostedSubmission>> {
+    let window = SETTINGS_INSTANCE.mirror.duplicate_window;
+    if window == 0 {
+        return Ok(None);
+    }
+    let since = Utc::now().naive_utc() - Duration::hours(window as i64);
+    PostedSubmission::find_duplicate(
+        channel,
+        &submission.name,
+        &content_keys(submission),
+        since,
+        conn,
+    )
+}
@@ -0,0 +1,524 @@
+use std::fmt;
+
+use log::warn;
+use regex::{Regex, RegexBuilder};
+
+use super::media::{classify, PostMedia};
+use crate::{
+    db::models::{ChannelSubreddit, FlairList, FlairRule, KeywordRule, RuleKind},
+    reddit_bot::submission::Submission,
+};
+
+/// Why a fetched submission wasn't delivered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SkipReason {
+    BelowMinScore { score: i64, min_score: i32 },
+    Nsfw,
+    Spoiler,
+    NotMedia,
+    CrosspostParentNsfw,
+    CrosspostParentSpoiler,
+    PostLimit { limit: i32 },
+    DailyLimit { limit: i32 },
+    ExcludedKeyword { rule: String },
+    MissingKeyword,
+    FlairBlocked { flair: String },
+    FlairNotAllowed { flair: Option<String> },
+    Duplicate { original: String },
+}
+
+impl SkipReason {
+    /// Codes of the reasons a post is held back by a posting limit rather than its content.
+    pub const QUOTA_CODES: [&'static str; 2] = ["post_limit", "daily_limit"];
+
+    /// Stable identifier stored with skipped submissions.
+    pub fn code(&self) -> &'static str {
+        match self {
+            SkipReason::BelowMinScore { .. } => "min_score",
+            SkipReason::Nsfw => "nsfw",
+            SkipReason::Spoiler => "spoiler",
+            SkipReason::NotMedia => "not_media",
+            SkipReason::CrosspostParentNsfw => "crosspost_nsfw",
+            SkipReason::CrosspostParentSpoiler => "crosspost_spoiler",
+            SkipReason::PostLimit { .. } => "post_limit",
+            SkipReason::DailyLimit { .. } => "daily_limit",
+            SkipReason::ExcludedKeyword { .. } => "keyword_excluded",
+            SkipReason::MissingKeyword => "keyword_missing",
+            SkipReason::FlairBlocked { .. } => "flair_blocked",
+            SkipReason::FlairNotAllowed { .. } => "flair_not_allowed",
+            SkipReason::Duplicate { .. } => "duplicate",
+        }
+    }
+    /// Additional context worth keeping next to the code, if any.
+    pub fn detail(&self) -> Option<String> {
+        match self {
+            SkipReason::BelowMinScore { score, min_score } => {
+                Some(format!("score {} < {}", score, min_score))
+            }
+            SkipReason::PostLimit { limit } => Some(format!("{} per poll", limit)),
+            SkipReason::DailyLimit { limit } => Some(format!("{} per day", limit)),
+            SkipReason::ExcludedKeyword { rule } => Some(rule.clone()),
+            SkipReason::FlairBlocked { flair } => Some(flair.clone()),
+            SkipReason::FlairNotAllowed { flair } => {
+                Some(flair.clone().unwrap_or_else(|| "no flair".to_owned()))
+            }
+            SkipReason::Duplicate { original } => Some(format!("of {}", original)),
+            _ => None,
+        }
+    }
+    /// Human readable explanation of a stored [`SkipReason::code`].
+    pub fn describe(code: &str) -> &'static str {
+        match code {
+            "min_score" => "score below the minimum",
+            "nsfw" => "NSFW posts not allowed",
+            "spoiler" => "spoilers hidden",
+            "not_media" => "not a media post",
+            "crosspost_nsfw" => "cross-post of an NSFW post",
+            "crosspost_spoiler" => "cross-post of a spoiler",
+            "post_limit" => "post limit reached",
+            "daily_limit" => "daily limit reached",
+            "keyword_excluded" => "matched an exclude rule",
+            "keyword_missing" => "matched no include rule",
+            "flair_blocked" => "flair on the blocklist",
+            "flair_not_allowed" => "flair not on the allowlist",
+            "duplicate" => "already posted as another submission",
+            _ => "unknown reason",
+        }
+    }
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.detail() {
+            Some(detail) => write!(f, "{} ({})", SkipReason::describe(self.code()), detail),
+            None => write!(f, "{}", SkipReason::describe(self.code())),
+        }
+    }
+}
+
+/// Checks a submission against the content filters of the channel–subreddit link it's
+/// about to be queued for.
+///
+/// When `respect_external_content_flag` is set, cross-posts also inherit the NSFW and
+/// spoiler flags of their original submission, which Reddit doesn't always copy over.
+pub fn check(link: &ChannelSubreddit, submission: &Submission) -> Result<(), SkipReason> {
+    if let Some(min_score) = link.min_score {
+        if submission.score < min_score as i64 {
+            return Err(SkipReason::BelowMinScore {
+                score: submission.score,
+                min_score,
+            });
+        }
+    }
+    if !link.allow_nsfw && submission.over_18 {
+        return Err(SkipReason::Nsfw);
+    }
+    if !link.show_spoilers && submission.spoiler {
+        return Err(SkipReason::Spoiler);
+    }
+    if link.respect_external_content_flag {
+        for parent in &submission.crosspost_parent_list {
+            if !link.allow_nsfw && parent.over_18 {
+                return Err(SkipReason::CrosspostParentNsfw);
+            }
+            if !link.show_spoilers && parent.spoiler {
+                return Err(SkipReason::CrosspostParentSpoiler);
+            }
+        }
+    }
+    if link.medias_only && classify(submission) == PostMedia::Text {
+        return Err(SkipReason::NotMedia);
+    }
+    Ok(())
+}
+
+/// Checks whether a channel–subreddit link may take one more post.
+///
+/// `queued` counts the posts already accepted in this poll, `posted_today` the ones
+/// delivered earlier today.
+pub fn check_quota(
+    post_limit: Option<i32>,
+    daily_limit: Option<i32>,
+    queued: i32,
+    posted_today: i32,
+) -> Result<(), SkipReason> {
+    if let Some(limit) = post_limit {
+        if queued >= limit {
+            return Err(SkipReason::PostLimit { limit });
+        }
+    }
+    if let Some(limit) = daily_limit {
+        if posted_today + queued >= limit {
+            return Err(SkipReason::DailyLimit { limit });
+        }
+    }
+    Ok(())
+}
+
+/// Checks the flair of a submission against the allowlist and blocklist of a link.
+///
+/// Rules with a template id match on it when the submission has one too, so renamed
+/// flairs keep matching. Otherwise the flair text is compared, ignoring case.
+pub fn check_flair(rules: &[FlairRule], submission: &Submission) -> Result<(), SkipReason> {
+    let flair = submission
+        .link_flair_text
+        .as_deref()
+        .map(str::trim)
+        .filter(|flair| !flair.is_empty());
+    let matches = |rule: &&FlairRule| match (&rule.template_id, &submission.link_flair_template_id)
+    {
+        (Some(rule_template), Some(template)) => rule_template == template,
+        _ => flair.is_some_and(|flair| flair.eq_ignore_ascii_case(rule.flair_text.trim())),
+    };
+    if let Some(rule) = rules
+        .iter()
+        .filter(|rule| rule.list == FlairList::Block)
+        .find(matches)
+    {
+        return Err(SkipReason::FlairBlocked {
+            flair: flair.unwrap_or(&rule.flair_text).to_owned(),
+        });
+    }
+    let mut allowed = rules
+        .iter()
+        .filter(|rule| rule.list == FlairList::Allow)
+        .peekable();
+    if allowed.peek().is_some() && !allowed.any(|rule| matches(&rule)) {
+        return Err(SkipReason::FlairNotAllowed {
+            flair: flair.map(str::to_owned),
+        });
+    }
+    Ok(())
+}
+
+/// Compiles the pattern of a keyword rule.
+///
+/// Plain words only match whole words, so "cat" doesn't match "category".
+pub fn compile_rule(pattern: &str, is_regex: bool, case_sensitive: bool) -> Result<Regex, String> {
+    let pattern = pattern.trim();
+    if pattern.is_empty() {
+        return Err("The rule is empty".to_owned());
+    }
+    let expression = if is_regex {
+        pattern.to_owned()
+    } else {
+        let is_word_char = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric() || c == '_');
+        format!(
+            "{}{}{}",
+            if is_word_char(pattern.chars().next()) {
+                r"\b"
+            } else {
+                ""
+            },
+            regex::escape(pattern),
+            if is_word_char(pattern.chars().last()) {
+                r"\b"
+            } else {
+                ""
+            }
+        )
+    };
+    RegexBuilder::new(&expression)
+        .case_insensitive(!case_sensitive)
+        .build()
+        .map_err(|error| format!("Invalid regular expression: {}", error))
+}
+
+/// The keyword rules of a channel–subreddit link, compiled once per poll.
+pub struct KeywordFilter {
+    include: Vec<Regex>,
+    exclude: Vec<(Regex, String)>,
+}
+
+impl KeywordFilter {
+    /// Compiles the rules, leaving out the ones that don't compile anymore.
+    pub fn new(rules: &[KeywordRule]) -> Self {
+        let mut filter = KeywordFilter {
+            include: Vec::new(),
+            exclude: Vec::new(),
+        };
+        for rule in rules {
+            let regex = match compile_rule(&rule.pattern, rule.is_regex, rule.case_sensitive) {
+                Ok(regex) => regex,
+                Err(error) => {
+                    warn!("Ignoring keyword rule {}: {}", rule.id, error);
+                    continue;
+                }
+            };
+            match rule.kind {
+                RuleKind::Include => filter.include.push(regex),
+                RuleKind::Exclude => filter.exclude.push((regex, rule.to_string())),
+            }
+        }
+        filter
+    }
+
+    /// Checks the title and self text of a submission against the rules.
+    ///
+    /// Exclude rules win over include rules.
+    pub fn check(&self, submission: &Submission) -> Result<(), SkipReason> {
+        let texts = [submission.title.as_str(), submission.selftext.as_str()];
+        let matches = |regex: &Regex| texts.iter().any(|text| regex.is_match(text));
+        if let Some((_, rule)) = self.exclude.iter().find(|(regex, _)| matches(regex)) {
+            return Err(SkipReason::ExcludedKeyword { rule: rule.clone() });
+        }
+        if !self.include.is_empty() && !self.include.iter().any(matches) {
+            return Err(SkipReason::MissingKeyword);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDateTime;
+
+    fn submission(title: &str, selftext: &str) -> Submission {
+        serde_json::from_value(serde_json::json!({
+            "name": "t3_abc123",
+            "title": title,
+            "author": "someone",
+            "subreddit": "pics",
+            "permalink": "/r/pics/comments/abc123/post/",
+            "score": 10,
+            "over_18": false,
+            "is_self": true,
+            "selftext": selftext,
+        }))
+        .expect("The submission is valid")
+    }
+
+    fn flaired(flair: Option<&str>, template_id: Option<&str>) -> Submission {
+        Submission {
+            link_flair_text: flair.map(str::to_owned),
+            link_flair_template_id: template_id.map(str::to_owned),
+            ..submission("Title", "")
+        }
+    }
+
+    fn keyword_rule(kind: RuleKind, pattern: &str, is_regex: bool) -> KeywordRule {
+        KeywordRule {
+            id: 1,
+            channel_id: 1,
+            subreddit_id: 1,
+            kind,
+            pattern: pattern.to_owned(),
+            is_regex,
+            case_sensitive: false,
+            created_at: NaiveDateTime::default(),
+        }
+    }
+
+    fn flair_rule(list: FlairList, flair_text: &str, template_id: Option<&str>) -> FlairRule {
+        FlairRule {
+            id: 1,
+            channel_id: 1,
+            subreddit_id: 1,
+            list,
+            flair_text: flair_text.to_owned(),
+            template_id: template_id.map(str::to_owned),
+            created_at: NaiveDateTime::default(),
+        }
+    }
+
+    #[test]
+    fn plain_words_match_whole_words() {
+        let regex = compile_rule("cat", false, false).unwrap();
+        assert!(regex.is_match("a cat sat"));
+        assert!(regex.is_match("cat."));
+        assert!(!regex.is_match("category"));
+        assert!(!regex.is_match("concatenate"));
+    }
+
+    #[test]
+    fn boundaries_only_wrap_word_characters() {
+        let regex = compile_rule("c++", false, false).unwrap();
+        assert!(regex.is_match("learning c++ today"));
+        assert!(regex.is_match("c++17"));
+        let regex = compile_rule("#news", false, false).unwrap();
+        assert!(regex.is_match("today's#news"));
+        assert!(!regex.is_match("#newsletter"));
+    }
+
+    #[test]
+    fn plain_words_are_escaped() {
+        let regex = compile_rule("a.b", false, false).unwrap();
+        assert!(regex.is_match("a.b"));
+        assert!(!regex.is_match("axb"));
+    }
+
+    #[test]
+    fn respects_case_sensitivity() {
+        assert!(compile_rule("NSFL", false, false).unwrap().is_match("nsfl"));
+        let regex = compile_rule("NSFL", false, true).unwrap();
+        assert!(regex.is_match("NSFL"));
+        assert!(!regex.is_match("nsfl"));
+        assert!(!compile_rule("a+", true, true).unwrap().is_match("A"));
+    }
+
+    #[test]
+    fn compiles_regular_expressions_as_written() {
+        let regex = compile_rule(r"\b(cats?|kittens?)\b", true, false).unwrap();
+        assert!(regex.is_match("Two Kittens"));
+        assert!(!regex.is_match("catalog"));
+        assert!(compile_rule("cat", true, false)
+            .unwrap()
+            .is_match("category"));
+    }
+
+    #[test]
+    fn rejects_empty_and_invalid_rules() {
+        assert!(compile_rule("  ", false, false).is_err());
+        assert!(compile_rule("(unclosed", true, false).is_err());
+        assert!(compile_rule("(unclosed", false, false).is_ok());
+    }
+
+    #[test]
+    fn exclude_rules_win_over_include_rules() {
+        let filter = KeywordFilter::new(&[
+            keyword_rule(RuleKind::Include, "cat", false),
+            keyword_rule(RuleKind::Exclude, "giveaway", false),
+        ]);
+        assert_eq!(filter.check(&submission("My cat", "")), Ok(()));
+        assert_eq!(
+            filter.check(&submission("Cat giveaway", "")),
+            Err(SkipReason::ExcludedKeyword {
+                rule: "exclude \"giveaway\"".to_owned()
+            })
+        );
+        assert_eq!(
+            filter.check(&submission("My dog", "")),
+            Err(SkipReason::MissingKeyword)
+        );
+    }
+
+    #[test]
+    fn keywords_match_the_self_text_too() {
+        let filter = KeywordFilter::new(&[keyword_rule(RuleKind::Include, "cat", false)]);
+        assert_eq!(filter.check(&submission("Look", "at my cat")), Ok(()));
+        let filter = KeywordFilter::new(&[keyword_rule(RuleKind::Exclude, "spam", false)]);
+        assert!(filter.check(&submission("Look", "spam inside")).is_err());
+    }
+
+    #[test]
+    fn without_rules_everything_passes() {
+        assert_eq!(
+            KeywordFilter::new(&[]).check(&submission("Any", "")),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn broken_rules_are_left_out() {
+        let filter = KeywordFilter::new(&[
+            keyword_rule(RuleKind::Include, "(unclosed", true),
+            keyword_rule(RuleKind::Exclude, "[", true),
+        ]);
+        assert_eq!(filter.check(&submission("Anything", "")), Ok(()));
+    }
+
+    #[test]
+    fn blocks_flairs_by_text_ignoring_case() {
+        let rules = [flair_rule(FlairList::Block, "Meta", None)];
+        assert_eq!(
+            check_flair(&rules, &flaired(Some(" meta "), None)),
+            Err(SkipReason::FlairBlocked {
+                flair: "meta".to_owned()
+            })
+        );
+        assert_eq!(
+            check_flair(&rules, &flaired(Some("Metadata"), None)),
+            Ok(())
+        );
+        assert_eq!(check_flair(&rules, &flaired(None, None)), Ok(()));
+    }
+
+    #[test]
+    fn matches_flairs_by_template_id_when_both_have_one() {
+        let rules = [flair_rule(FlairList::Block, "Old name", Some("template"))];
+        assert!(check_flair(&rules, &flaired(Some("New name"), Some("template"))).is_err());
+        assert_eq!(
+            check_flair(&rules, &flaired(Some("Old name"), Some("other"))),
+            Ok(())
+        );
+        assert!(check_flair(&rules, &flaired(Some("old name"), None)).is_err());
+    }
+
+    #[test]
+    fn allowlists_require_a_matching_flair() {
+        let rules = [
+            flair_rule(FlairList::Allow, "Cats", None),
+            flair_rule(FlairList::Allow, "Dogs", None),
+        ];
+        assert_eq!(check_flair(&rules, &flaired(Some("dogs"), None)), Ok(()));
+        assert_eq!(
+            check_flair(&rules, &flaired(Some("Birds"), None)),
+            Err(SkipReason::FlairNotAllowed {
+                flair: Some("Birds".to_owned())
+            })
+        );
+        assert_eq!(
+            check_flair(&rules, &flaired(None, None)),
+            Err(SkipReason::FlairNotAllowed { flair: None })
+        );
+    }
+
+    #[test]
+    fn blocklists_win_over_allowlists() {
+        let rules = [
+            flair_rule(FlairList::Allow, "Cats", None),
+            flair_rule(FlairList::Block, "cats", None),
+        ];
+        assert!(matches!(
+            check_flair(&rules, &flaired(Some("Cats"), None)),
+            Err(SkipReason::FlairBlocked { .. })
+        ));
+    }
+
+    #[test]
+    fn quota_without_limits_accepts_everything() {
+        assert_eq!(check_quota(None, None, 1000, 1000), Ok(()));
+    }
+
+    #[test]
+    fn post_limit_counts_the_posts_of_this_poll() {
+        assert_eq!(check_quota(Some(3), None, 2, 100), Ok(()));
+        assert_eq!(
+            check_quota(Some(3), None, 3, 0),
+            Err(SkipReason::PostLimit { limit: 3 })
+        );
+    }
+
+    #[test]
+    fn daily_limit_counts_delivered_and_queued_posts() {
+        assert_eq!(check_quota(None, Some(5), 2, 2), Ok(()));
+        assert_eq!(
+            check_quota(None, Some(5), 2, 3),
+            Err(SkipReason::DailyLimit { limit: 5 })
+        );
+        assert_eq!(
+            check_quota(None, Some(5), 0, 5),
+            Err(SkipReason::DailyLimit { limit: 5 })
+        );
+    }
+
+    #[test]
+    fn post_limit_is_checked_before_the_daily_limit() {
+        assert_eq!(
+            check_quota(Some(2), Some(2), 2, 0),
+            Err(SkipReason::PostLimit { limit: 2 })
+        );
+        assert_eq!(
+            check_quota(Some(10), Some(4), 1, 3),
+            Err(SkipReason::DailyLimit { limit: 4 })
+        );
+        assert_eq!(check_quota(Some(10), Some(4), 1, 2), Ok(()));
+    }
+
+    #[test]
+    fn zero_limits_block_every_post() {
+        assert!(check_quota(Some(0), None, 0, 0).is_err());
+        assert!(check_quota(None, Some(0), 0, 0).is_err());
+    }
+}
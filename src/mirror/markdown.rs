@@ -0,0 +1,249 @@
+use teloxide::utils::html::escape;
+
+/// Converts Reddit flavored markdown into the HTML subset Telegram understands.
+///
+/// Anything without a Telegram counterpart (tables, superscript, ...) is kept as plain text.
+pub fn to_telegram_html(markdown: &str) -> String {
+    markdown
+        .lines()
+        .map(|line| {
+            let trimmed = line.trim_start();
+            if let Some(heading) = heading(trimmed) {
+                format!("<b>{}</b>", inline(heading))
+            } else if let Some(quote) = trimmed.strip_prefix('>').filter(|_| !is_spoiler(trimmed)) {
+                format!("<i>{}</i>", inline(quote.trim_start()))
+            } else if let Some(item) = trimmed
+                .strip_prefix("* ")
+                .or_else(|| trimmed.strip_prefix("- "))
+            {
+                format!("• {}", inline(item))
+            } else {
+                inline(line)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Whether the line starts with a `>!spoiler!<` rather than a quote.
+fn is_spoiler(line: &str) -> bool {
+    line.strip_prefix(">!")
+        .is_some_and(|spoiler| spoiler.contains("!<"))
+}
+
+fn heading(line: &str) -> Option<&str> {
+    let text = line.trim_start_matches('#');
+    if text.len() == line.len() || !text.starts_with(' ') {
+        return None;
+    }
+    Some(text.trim())
+}
+
+/// Inline delimiters and the tags they turn into, longest first so `**` wins over `*`.
+const DELIMITERS: [(&str, &str, &str); 6] = [
+    ("**", "**", "b"),
+    ("__", "__", "b"),
+    ("~~", "~~", "s"),
+    (">!", "!<", "tg-spoiler"),
+    ("*", "*", "i"),
+    ("`", "`", "code"),
+];
+
+fn inline(text: &str) -> String {
+    let mut html = String::with_capacity(text.len());
+    let mut rest = text;
+    'outer: while let Some(current) = rest.chars().next() {
+        if current == '\\' {
+            if let Some(escaped) = rest[1..].chars().next() {
+                if escaped.is_ascii_punctuation() {
+                    html += &escape(&escaped.to_string());
+                    rest = &rest[1 + escaped.len_utf8()..];
+                    continue;
+                }
+            }
+        }
+        if current == '[' {
+            if let Some((link, consumed)) = link(rest) {
+                html += &link;
+                rest = &rest[consumed..];
+                continue;
+            }
+        }
+        for (opening, closing, tag) in DELIMITERS {
+            let Some(after_opening) = rest.strip_prefix(opening) else {
+                continue;
+            };
+            let Some(end) = find_closing(after_opening, closing) else {
+                continue;
+            };
+            let content = &after_opening[..end];
+            if content.is_empty() || content.starts_with(' ') {
+                continue;
+            }
+            let content = if tag == "code" {
+                escape(content)
+            } else {
+                inline(content)
+            };
+            html += &format!("<{tag}>{content}</{tag}>");
+            rest = &after_opening[end + closing.len()..];
+            continue 'outer;
+        }
+        html += &escape(&current.to_string());
+        rest = &rest[current.len_utf8()..];
+    }
+    html
+}
+
+/// Finds the delimiter closing a span. A run of stars may close nested spans at once, as in
+/// `**bold *italic***`, the outermost span taking the last stars of the run.
+fn find_closing(text: &str, closing: &str) -> Option<usize> {
+    if !closing.starts_with('*') {
+        return text.find(closing);
+    }
+    let mut search = 0;
+    while let Some(start) = text[search..].find('*').map(|index| index + search) {
+        let run = text[start..].len() - text[start..].trim_start_matches('*').len();
+        // A pair of stars inside italics is a bold span of its own.
+        let nested_bold = closing.len() == 1 && run == 2;
+        if !nested_bold && run >= closing.len() {
+            return Some(start + run - closing.len());
+        }
+        search = start + run;
+    }
+    None
+}
+
+/// Parses a `[text](url)` link at the start of `text`, returning its HTML and the consumed length.
+fn link(text: &str) -> Option<(String, usize)> {
+    let label_end = text.find("](")?;
+    let label = &text[1..label_end];
+    let url_start = label_end + 2;
+    // Urls may contain balanced parentheses, like Wikipedia's `Rust_(programming_language)`.
+    let mut depth = 0;
+    let url_end = url_start
+        + text[url_start..].find(|current| {
+            match current {
+                '(' => depth += 1,
+                ')' if depth == 0 => return true,
+                ')' => depth -= 1,
+                _ => (),
+            }
+            false
+        })?;
+    let url = text[url_start..url_end].trim();
+    if label.contains('[') || url.is_empty() || url.contains(char::is_whitespace) {
+        return None;
+    }
+    let url = if url.starts_with('/') {
+        format!("https://www.reddit.com{}", url)
+    } else {
+        url.to_owned()
+    };
+    Some((
+        format!(
+            "<a href=\"{}\">{}</a>",
+            escape(&url).replace('"', "&quot;"),
+            inline(label)
+        ),
+        url_end + 1,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::to_telegram_html;
+
+    #[test]
+    fn converts_emphasis() {
+        assert_eq!(
+            to_telegram_html("**bold**, *italic*, ~~struck~~ and `code`"),
+            "<b>bold</b>, <i>italic</i>, <s>struck</s> and <code>code</code>"
+        );
+        assert_eq!(
+            to_telegram_html(">!spoiler!<"),
+            "<tg-spoiler>spoiler</tg-spoiler>"
+        );
+    }
+
+    #[test]
+    fn converts_nested_emphasis() {
+        assert_eq!(
+            to_telegram_html("**bold *italic* text**"),
+            "<b>bold <i>italic</i> text</b>"
+        );
+        assert_eq!(
+            to_telegram_html("*italic **bold** text*"),
+            "<i>italic <b>bold</b> text</i>"
+        );
+        assert_eq!(
+            to_telegram_html("**bold *italic***"),
+            "<b>bold <i>italic</i></b>"
+        );
+        assert_eq!(
+            to_telegram_html("*italic **bold***"),
+            "<i>italic <b>bold</b></i>"
+        );
+        assert_eq!(to_telegram_html("***both***"), "<b><i>both</i></b>");
+    }
+
+    #[test]
+    fn keeps_unmatched_delimiters() {
+        assert_eq!(to_telegram_html("2 * 3 * 4"), "2 * 3 * 4");
+        assert_eq!(to_telegram_html("**not closed"), "**not closed");
+        assert_eq!(to_telegram_html("\\*escaped\\*"), "*escaped*");
+    }
+
+    #[test]
+    fn escapes_html_in_text() {
+        assert_eq!(
+            to_telegram_html("a <b> & c > d"),
+            "a &lt;b&gt; &amp; c &gt; d"
+        );
+        assert_eq!(
+            to_telegram_html("`<tag> & co`"),
+            "<code>&lt;tag&gt; &amp; co</code>"
+        );
+        assert_eq!(to_telegram_html("**<&>**"), "<b>&lt;&amp;&gt;</b>");
+    }
+
+    #[test]
+    fn converts_links() {
+        assert_eq!(
+            to_telegram_html("[a *site*](https://example.com/?a=1&b=2)"),
+            "<a href=\"https://example.com/?a=1&amp;b=2\">a <i>site</i></a>"
+        );
+        assert_eq!(
+            to_telegram_html("[sub](/r/rust)"),
+            "<a href=\"https://www.reddit.com/r/rust\">sub</a>"
+        );
+    }
+
+    #[test]
+    fn keeps_parentheses_in_link_urls() {
+        assert_eq!(
+            to_telegram_html("see [Rust](https://en.wikipedia.org/wiki/Rust_(programming_language)) now"),
+            "see <a href=\"https://en.wikipedia.org/wiki/Rust_(programming_language)\">Rust</a> now"
+        );
+        assert_eq!(
+            to_telegram_html("([link](https://example.com))"),
+            "(<a href=\"https://example.com\">link</a>)"
+        );
+    }
+
+    #[test]
+    fn keeps_malformed_links_as_text() {
+        assert_eq!(to_telegram_html("[text](not closed"), "[text](not closed");
+        assert_eq!(to_telegram_html("[text](has space)"), "[text](has space)");
+    }
+
+    #[test]
+    fn converts_blocks() {
+        assert_eq!(
+            to_telegram_html("# Title\n> quoted\n* item\n- other"),
+            "<b>Title</b>\n<i>quoted</i>\n• item\n• other"
+        );
+        assert_eq!(to_telegram_html("#hashtag"), "#hashtag");
+        assert_eq!(to_telegram_html(">!not closed"), "<i>!not closed</i>");
+    }
+}
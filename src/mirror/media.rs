@@ -0,0 +1,123 @@
+use url::Url;
+
+use crate::reddit_bot::submission::{RedditVideo, Submission};
+
+/// How a submission is going to be presented in Telegram.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PostMedia {
+    Photo(Url),
+    /// Items of a Reddit gallery, in the order the author arranged them.
+    Gallery(Vec<GalleryMedia>),
+    Video(Url),
+    Animation(Url),
+    /// Nothing Telegram can show natively, the post goes out as a text message.
+    Text,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GalleryMedia {
+    Photo(Url),
+    Video(Url),
+}
+
+pub fn classify(submission: &Submission) -> PostMedia {
+    if submission.is_gallery {
+        if let Some(gallery) = gallery(submission) {
+            return gallery;
+        }
+    }
+    if let Some(video) = submission
+        .media
+        .as_ref()
+        .and_then(|media| media.reddit_video.as_ref())
+    {
+        if let Some(media) = reddit_video(video) {
+            return media;
+        }
+    }
+    let url = submission.url.as_deref().unwrap_or_default();
+    let path = url
+        .split(['?', '#'])
+        .next()
+        .unwrap_or_default()
+        .to_lowercase();
+    if path.ends_with(".gif") || path.ends_with(".gifv") {
+        let preview_mp4 = submission
+            .preview
+            .as_ref()
+            .and_then(|preview| preview.images.first())
+            .and_then(|image| image.variants.mp4.as_ref())
+            .map(|mp4| mp4.source.url.clone());
+        let animation_url = match preview_mp4 {
+            Some(mp4_url) => mp4_url,
+            None if path.ends_with(".gifv") => url.replacen(".gifv", ".mp4", 1),
+            None => url.to_owned(),
+        };
+        if let Ok(animation_url) = Url::parse(&animation_url) {
+            return PostMedia::Animation(animation_url);
+        }
+    }
+    if path.ends_with(".mp4") {
+        if let Ok(video_url) = Url::parse(url) {
+            return PostMedia::Video(video_url);
+        }
+    }
+    if let Some(video) = submission
+        .preview
+        .as_ref()
+        .and_then(|preview| preview.reddit_video_preview.as_ref())
+    {
+        if let Some(media) = reddit_video(video) {
+            return media;
+        }
+    }
+    let is_image = submission.post_hint.as_deref() == Some("image")
+        || [".jpg", ".jpeg", ".png", ".webp"]
+            .iter()
+            .any(|extension| path.ends_with(extension));
+    if is_image {
+        if let Ok(image_url) = Url::parse(url) {
+            return PostMedia::Photo(image_url);
+        }
+    }
+    PostMedia::Text
+}
+
+fn reddit_video(video: &RedditVideo) -> Option<PostMedia> {
+    let video_url = Url::parse(&video.fallback_url).ok()?;
+    Some(if video.is_gif {
+        PostMedia::Animation(video_url)
+    } else {
+        PostMedia::Video(video_url)
+    })
+}
+
+fn gallery(submission: &Submission) -> Option<PostMedia> {
+    let metadata = submission.media_metadata.as_ref()?;
+    let mut items: Vec<GalleryMedia> = submission
+        .gallery_data
+        .as_ref()?
+        .items
+        .iter()
+        .filter_map(|item| {
+            let item_metadata = metadata.get(&item.media_id)?;
+            let source = item_metadata.s.as_ref()?;
+            if item_metadata.e.as_deref() == Some("AnimatedImage") {
+                let video_url = source.mp4.as_deref().or(source.gif.as_deref())?;
+                Url::parse(video_url).ok().map(GalleryMedia::Video)
+            } else {
+                Url::parse(source.u.as_deref()?)
+                    .ok()
+                    .map(GalleryMedia::Photo)
+            }
+        })
+        .collect();
+    match items.len() {
+        0 => None,
+        1 => Some(match items.remove(0) {
+            GalleryMedia::Photo(photo_url) => PostMedia::Photo(photo_url),
+            GalleryMedia::Video(video_url) => PostMedia::Animation(video_url),
+        }),
+        _ => Some(PostMedia::Gallery(items)),
+    }
+}
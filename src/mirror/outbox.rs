@@ -0,0 +1,268 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use log::warn;
+use teloxide::{
+    requests::{Output, Request},
+    types::ChatId,
+    RequestError,
+};
+use tokio::time::{sleep, Instant};
+
+const SECOND: Duration = Duration::from_secs(1);
+const MINUTE: Duration = Duration::from_secs(60);
+
+/// Paces every message the mirror sends so it stays under Telegram's flood limits.
+///
+/// Requests first wait for room in their own chat, then queue up for the global budget in
+/// arrival order, so a channel that hit its per-chat limit never holds up the others.
+/// Requests refused with `RetryAfter` pause their chat for the given time and are retried.
+#[derive(Clone)]
+pub struct Outbox {
+    per_second: usize,
+    per_chat_minute: usize,
+    windows: Arc<Mutex<Windows>>,
+    turn: Arc<tokio::sync::Mutex<()>>,
+}
+
+#[derive(Default)]
+struct Windows {
+    overall: VecDeque<Instant>,
+    chats: HashMap<ChatId, ChatWindow>,
+}
+
+#[derive(Default)]
+struct ChatWindow {
+    sent: VecDeque<Instant>,
+    frozen_until: Option<Instant>,
+}
+
+/// How long to wait until `count` more sends fit in a window of `span` allowing `limit` sends.
+fn window_wait(
+    sent: &mut VecDeque<Instant>,
+    span: Duration,
+    limit: usize,
+    count: usize,
+) -> Duration {
+    let now = Instant::now();
+    while sent
+        .front()
+        .is_some_and(|&at| now.duration_since(at) >= span)
+    {
+        sent.pop_front();
+    }
+    // A request larger than the whole window only has to wait for an empty one.
+    let count = count.min(limit);
+    if sent.len() + count <= limit {
+        return Duration::ZERO;
+    }
+    let blocking = sent[sent.len() + count - limit - 1];
+    span.saturating_sub(now.duration_since(blocking))
+}
+
+impl Windows {
+    fn chat_wait(&mut self, chat_id: ChatId, per_chat_minute: usize, count: usize) -> Duration {
+        let chat = self.chats.entry(chat_id).or_default();
+        let frozen = chat
+            .frozen_until
+            .map(|until| until.saturating_duration_since(Instant::now()))
+            .unwrap_or_default();
+        frozen.max(window_wait(&mut chat.sent, MINUTE, per_chat_minute, count))
+    }
+
+    fn record(&mut self, chat_id: ChatId, count: usize) {
+        let now = Instant::now();
+        let chat = self.chats.entry(chat_id).or_default();
+        for _ in 0..count {
+            self.overall.push_back(now);
+            chat.sent.push_back(now);
+        }
+    }
+}
+
+impl Outbox {
+    pub fn new(per_second: u32, per_chat_minute: u32) -> Self {
+        Outbox {
+            per_second: per_second.max(1) as usize,
+            per_chat_minute: per_chat_minute.max(1) as usize,
+            windows: Arc::default(),
+            turn: Arc::default(),
+        }
+    }
+
+    /// Waits until `count` messages may be sent to the chat and books them.
+    async fn acquire(&self, chat_id: ChatId, count: usize) {
+        loop {
+            let chat_wait =
+                self.windows
+                    .lock()
+                    .unwrap()
+                    .chat_wait(chat_id, self.per_chat_minute, count);
+            if !chat_wait.is_zero() {
+                sleep(chat_wait).await;
+                continue;
+            }
+            let _turn = self.turn.lock().await;
+            // Another request to the same chat may have been booked while waiting for the turn.
+            let overall_wait = {
+                let mut windows = self.windows.lock().unwrap();
+                if !windows
+                    .chat_wait(chat_id, self.per_chat_minute, count)
+                    .is_zero()
+                {
+                    continue;
+                }
+                window_wait(&mut windows.overall, SECOND, self.per_second, count)
+            };
+            if overall_wait.is_zero() {
+                self.windows.lock().unwrap().record(chat_id, count);
+                return;
+            }
+            sleep(overall_wait).await;
+        }
+    }
+
+    fn freeze(&self, chat_id: ChatId, duration: Duration) {
+        let until = Instant::now() + duration;
+        let mut windows = self.windows.lock().unwrap();
+        let chat = windows.chats.entry(chat_id).or_default();
+        chat.frozen_until = Some(chat.frozen_until.map_or(until, |frozen| frozen.max(until)));
+    }
+
+    /// Sends a request producing a single message.
+    pub async fn send<R>(&self, chat_id: ChatId, request: R) -> Result<Output<R>, RequestError>
+    where
+        R: Request<Err = RequestError>,
+    {
+        self.send_many(chat_id, 1, request).await
+    }
+
+    /// Sends a request producing `count` messages, such as a media group.
+    pub async fn send_many<R>(
+        &self,
+        chat_id: ChatId,
+        count: usize,
+        request: R,
+    ) -> Result<Output<R>, RequestError>
+    where
+        R: Request<Err = RequestError>,
+    {
+        loop {
+            self.acquire(chat_id, count).await;
+            match request.send_ref().await {
+                Err(RequestError::RetryAfter(retry_after)) => {
+                    warn!(
+                        "Telegram asked to wait {:?} before sending to chat {}",
+                        retry_after, chat_id
+                    );
+                    self.freeze(chat_id, retry_after);
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Instants `ago` before the current (paused) time, oldest first.
+    fn sent_ago(ago: &[u64]) -> VecDeque<Instant> {
+        let now = Instant::now();
+        ago.iter()
+            .map(|&seconds| now - Duration::from_secs(seconds))
+            .collect()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn empty_windows_have_room() {
+        let mut sent = VecDeque::new();
+        assert_eq!(window_wait(&mut sent, MINUTE, 3, 1), Duration::ZERO);
+        assert_eq!(window_wait(&mut sent, MINUTE, 3, 3), Duration::ZERO);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn full_windows_wait_for_the_oldest_send() {
+        let mut sent = sent_ago(&[50, 30, 10]);
+        assert_eq!(
+            window_wait(&mut sent, MINUTE, 3, 1),
+            Duration::from_secs(10)
+        );
+        // Two more sends have to wait until the two oldest ones left the window.
+        let mut sent = sent_ago(&[50, 30]);
+        assert_eq!(window_wait(&mut sent, MINUTE, 3, 1), Duration::ZERO);
+        assert_eq!(
+            window_wait(&mut sent, MINUTE, 3, 2),
+            Duration::from_secs(10)
+        );
+        assert_eq!(
+            window_wait(&mut sent, MINUTE, 3, 3),
+            Duration::from_secs(30)
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn expired_sends_leave_the_window() {
+        let mut sent = sent_ago(&[120, 60, 59]);
+        assert_eq!(window_wait(&mut sent, MINUTE, 2, 1), Duration::ZERO);
+        assert_eq!(sent.len(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn requests_larger_than_the_window_wait_for_an_empty_one() {
+        let mut sent = VecDeque::new();
+        assert_eq!(window_wait(&mut sent, SECOND, 3, 10), Duration::ZERO);
+        let mut sent = sent_ago(&[0]);
+        assert_eq!(window_wait(&mut sent, SECOND, 3, 10), SECOND);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn chats_have_windows_of_their_own() {
+        let mut windows = Windows::default();
+        windows.record(ChatId(1), 2);
+        assert_eq!(windows.chat_wait(ChatId(1), 2, 1), MINUTE);
+        assert_eq!(windows.chat_wait(ChatId(2), 2, 1), Duration::ZERO);
+        assert_eq!(windows.overall.len(), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn frozen_chats_wait_for_the_freeze() {
+        let outbox = Outbox::new(30, 20);
+        outbox.freeze(ChatId(1), Duration::from_secs(5));
+        outbox.freeze(ChatId(1), Duration::from_secs(2));
+        let mut windows = outbox.windows.lock().unwrap();
+        assert_eq!(windows.chat_wait(ChatId(1), 20, 1), Duration::from_secs(5));
+        assert_eq!(windows.chat_wait(ChatId(2), 20, 1), Duration::ZERO);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn acquire_paces_each_chat_per_minute() {
+        let outbox = Outbox::new(30, 2);
+        let start = Instant::now();
+        outbox.acquire(ChatId(1), 1).await;
+        outbox.acquire(ChatId(1), 1).await;
+        assert_eq!(start.elapsed(), Duration::ZERO);
+        outbox.acquire(ChatId(2), 1).await;
+        assert_eq!(start.elapsed(), Duration::ZERO);
+        outbox.acquire(ChatId(1), 1).await;
+        assert_eq!(start.elapsed(), MINUTE);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn acquire_paces_all_chats_per_second() {
+        let outbox = Outbox::new(2, 20);
+        let start = Instant::now();
+        outbox.acquire(ChatId(1), 1).await;
+        outbox.acquire(ChatId(2), 1).await;
+        assert_eq!(start.elapsed(), Duration::ZERO);
+        outbox.acquire(ChatId(3), 1).await;
+        assert_eq!(start.elapsed(), SECOND);
+        // A media group counts as one send per message.
+        outbox.acquire(ChatId(4), 2).await;
+        assert_eq!(start.elapsed(), SECOND * 2);
+    }
+}
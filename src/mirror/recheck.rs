@@ -0,0 +1,138 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use chrono::{Duration, Utc};
+use diesel::SqliteConnection;
+use log::{info, warn};
+use teloxide::{prelude::*, RequestError};
+
+use super::{outbox::Outbox, MirrorResult};
+use crate::{
+    db::models::{BotStatus, Channel, NewModerationAction, PostedSubmission},
+    reddit_bot::fetch_info,
+    settings::SETTINGS_INSTANCE,
+};
+
+/// Human readable explanation of a removal reported by [`Submission::removal`].
+///
+/// [`Submission::removal`]: crate::reddit_bot::submission::Submission::removal
+fn describe_removal(reason: &str) -> &'static str {
+    match reason {
+        "moderator" | "automod_filtered" => "removed by the subreddit moderators",
+        "deleted" | "author" => "deleted by its author",
+        "reddit" | "anti_evil_ops" | "community_ops" => "removed by Reddit",
+        "copyright_takedown" => "removed after a copyright notice",
+        _ => "removed",
+    }
+}
+
+/// Looks up the recently mirrored submissions on Reddit again and takes down the
+/// messages of those that were removed or deleted there since.
+pub(super) async fn recheck_cycle(
+    bot: &Bot,
+    outbox: &Outbox,
+    reddit_bot: &roux::Me,
+    conn: &Arc<Mutex<SqliteConnection>>,
+) -> MirrorResult<()> {
+    if BotStatus::is_paused(&mut conn.lock().unwrap())? {
+        return Ok(());
+    }
+    let cutoff =
+        Utc::now().naive_utc() - Duration::hours(SETTINGS_INSTANCE.mirror.recheck_window as i64);
+    let posted = PostedSubmission::get_posted_since(cutoff, &mut conn.lock().unwrap())?;
+    if posted.is_empty() {
+        return Ok(());
+    }
+    let mut fullnames: Vec<String> = posted.iter().map(|post| post.fullname.clone()).collect();
+    fullnames.sort();
+    fullnames.dedup();
+    let removals: HashMap<String, String> = fetch_info(reddit_bot, &fullnames)
+        .await?
+        .into_iter()
+        .filter_map(|submission| {
+            let reason = submission.removal()?.to_owned();
+            Some((submission.name, reason))
+        })
+        .collect();
+    for post in &posted {
+        let reason = match removals.get(&post.fullname) {
+            Some(reason) => reason,
+            None => continue,
+        };
+        let channel = Channel::get_by_id(post.channel_id, &mut conn.lock().unwrap())?;
+        let (action, detail) = take_down(bot, outbox, &channel, post, reason).await;
+        info!(
+            "{} was {} on Reddit, {} its messages in channel {}",
+            post.fullname,
+            describe_removal(reason),
+            action,
+            channel.chat_id
+        );
+        NewModerationAction::new(post, reason, action, detail).insert(&mut conn.lock().unwrap())?;
+    }
+    Ok(())
+}
+
+/// Deletes the messages of a post, or marks the post as removed when Telegram doesn't allow
+/// deleting them anymore.
+///
+/// Returns the action taken and, if something went wrong, why.
+async fn take_down(
+    bot: &Bot,
+    outbox: &Outbox,
+    channel: &Channel,
+    post: &PostedSubmission,
+    reason: &str,
+) -> (&'static str, Option<String>) {
+    let chat_id = ChatId(channel.chat_id);
+    let message_ids = post.message_ids();
+    let mut delete_error = None;
+    for message_id in &message_ids {
+        if let Err(error) = outbox
+            .send(chat_id, bot.delete_message(chat_id, *message_id))
+            .await
+        {
+            delete_error = Some(error);
+            break;
+        }
+    }
+    let delete_error = match delete_error {
+        Some(error) => error,
+        None => return ("deleted", None),
+    };
+    let first_message_id = match message_ids.first() {
+        Some(message_id) => *message_id,
+        None => return ("failed", Some(delete_error.to_string())),
+    };
+    let notice = format!("This post was {}.", describe_removal(reason));
+    // Whether the message carries a text or a caption isn't stored, so try both.
+    let edited = match outbox
+        .send(
+            chat_id,
+            bot.edit_message_text(chat_id, first_message_id, notice.clone()),
+        )
+        .await
+    {
+        Err(RequestError::Api(_)) => outbox
+            .send(
+                chat_id,
+                bot.edit_message_caption(chat_id, first_message_id)
+                    .caption(notice),
+            )
+            .await
+            .map(|_| ()),
+        result => result.map(|_| ()),
+    };
+    match edited {
+        Ok(()) => ("edited", Some(delete_error.to_string())),
+        Err(error) => {
+            warn!(
+                "Couldn't take down {} in channel {}: {}",
+                post.fullname, channel.chat_id, error
+            );
+            ("failed", Some(format!("{}; {}", delete_error, error)))
+        }
+    }
+}
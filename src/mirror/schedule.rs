@@ -0,0 +1,341 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, Duration, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+
+use crate::{db::models::Channel, settings::SETTINGS_INSTANCE};
+
+/// A daily span of local time during which a channel accepts posts.
+///
+/// Spans ending before they start run past midnight, e.g. `22:00-02:00`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Window {
+    start: NaiveTime,
+    end: NaiveTime,
+}
+
+impl Window {
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+impl FromStr for Window {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (start, end) = value
+            .split_once('-')
+            .ok_or_else(|| format!("Posting window \"{}\" isn't written as HH:MM-HH:MM", value))?;
+        let parse = |time: &str| {
+            NaiveTime::parse_from_str(time.trim(), "%H:%M")
+                .map_err(|_| format!("\"{}\" isn't a time of day (HH:MM)", time.trim()))
+        };
+        let window = Window {
+            start: parse(start)?,
+            end: parse(end)?,
+        };
+        if window.start == window.end {
+            return Err(format!("Posting window \"{}\" is empty", value.trim()));
+        }
+        Ok(window)
+    }
+}
+
+pub fn parse_timezone(value: &str) -> Result<Tz, String> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| format!("Unknown time zone \"{}\"", value.trim()))
+}
+
+/// Parses a comma separated list of [`Window`]s.
+pub fn parse_windows(value: &str) -> Result<Vec<Window>, String> {
+    value.split(',').map(str::parse).collect()
+}
+
+/// Parses a `;` separated list of cron expressions.
+///
+/// The usual five fields (minute to day of week) are accepted as well as the six or seven
+/// fields of the `cron` crate, which start with the second and may end with the year.
+pub fn parse_times(value: &str) -> Result<Vec<cron::Schedule>, String> {
+    value
+        .split(';')
+        .map(|expression| {
+            let expression = expression.trim();
+            let full_expression = if expression.split_whitespace().count() == 5 {
+                format!("0 {}", expression)
+            } else {
+                expression.to_owned()
+            };
+            cron::Schedule::from_str(&full_expression)
+                .map_err(|error| format!("Invalid cron expression \"{}\": {}", expression, error))
+        })
+        .collect()
+}
+
+/// When a channel accepts posts, evaluated in the channel's own time zone.
+pub struct Schedule {
+    timezone: Tz,
+    windows: Vec<Window>,
+    times: Vec<cron::Schedule>,
+    min_interval: Option<Duration>,
+    /// Spread the queued posts evenly over this time span.
+    spread_over: Option<Duration>,
+}
+
+impl Schedule {
+    pub fn for_channel(channel: &Channel) -> Result<Schedule, String> {
+        Ok(Schedule {
+            timezone: parse_timezone(&channel.timezone)?,
+            windows: match &channel.posting_windows {
+                Some(windows) => parse_windows(windows)?,
+                None => Vec::new(),
+            },
+            times: match &channel.posting_times {
+                Some(times) => parse_times(times)?,
+                None => Vec::new(),
+            },
+            min_interval: channel
+                .min_post_interval
+                .map(|min_interval| Duration::seconds(min_interval as i64)),
+            spread_over: channel
+                .spread_posts
+                .then(|| Duration::seconds(SETTINGS_INSTANCE.mirror.poll_interval as i64)),
+        })
+    }
+
+    /// Whether `now` falls into one of the posting windows, if there are any.
+    pub fn is_open(&self, now: DateTime<Utc>) -> bool {
+        let local_time = now.with_timezone(&self.timezone).time();
+        self.windows.is_empty()
+            || self
+                .windows
+                .iter()
+                .any(|window| window.contains(local_time))
+    }
+
+    /// The time to leave between two posts while `pending` posts are waiting.
+    pub fn spacing(&self, pending: usize) -> Duration {
+        let spread = self
+            .spread_over
+            .map(|spread_over| spread_over / pending.max(1) as i32)
+            .unwrap_or_else(Duration::zero);
+        self.min_interval.unwrap_or_else(Duration::zero).max(spread)
+    }
+
+    /// Whether the next of `pending` posts may go out at `now`, given when the channel last
+    /// received one.
+    ///
+    /// With fixed posting times, a single post is released per time that passed since the
+    /// last post. Times missed while the bot was down collapse into one post.
+    pub fn is_due(
+        &self,
+        now: DateTime<Utc>,
+        last_posted_at: Option<NaiveDateTime>,
+        pending: usize,
+    ) -> bool {
+        if !self.is_open(now) {
+            return false;
+        }
+        let last_posted_at =
+            last_posted_at.map(|last_posted_at| Utc.from_utc_datetime(&last_posted_at));
+        if last_posted_at.is_some_and(|last_posted_at| now - last_posted_at < self.spacing(pending))
+        {
+            return false;
+        }
+        if self.times.is_empty() {
+            return true;
+        }
+        let since = last_posted_at
+            .unwrap_or_else(|| now - Duration::days(1))
+            .with_timezone(&self.timezone);
+        self.times.iter().any(|times| {
+            times
+                .after(&since)
+                .next()
+                .is_some_and(|next| next.with_timezone(&Utc) <= now)
+        })
+    }
+
+    /// The next posting time after `now`, if the schedule has fixed times.
+    pub fn next_time(&self, now: DateTime<Utc>) -> Option<DateTime<Tz>> {
+        let now = now.with_timezone(&self.timezone);
+        self.times
+            .iter()
+            .filter_map(|times| times.after(&now).next())
+            .min()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(value: &str) -> NaiveTime {
+        NaiveTime::parse_from_str(value, "%H:%M").unwrap()
+    }
+
+    fn at(value: &str) -> DateTime<Utc> {
+        Utc.from_utc_datetime(&NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M").unwrap())
+    }
+
+    fn new_schedule(timezone: Tz, windows: &str, times: &str) -> Schedule {
+        Schedule {
+            timezone,
+            windows: if windows.is_empty() {
+                Vec::new()
+            } else {
+                parse_windows(windows).unwrap()
+            },
+            times: if times.is_empty() {
+                Vec::new()
+            } else {
+                parse_times(times).unwrap()
+            },
+            min_interval: None,
+            spread_over: None,
+        }
+    }
+
+    #[test]
+    fn windows_include_their_start_but_not_their_end() {
+        let window: Window = "09:00-17:00".parse().unwrap();
+        assert!(window.contains(time("09:00")));
+        assert!(window.contains(time("16:59")));
+        assert!(!window.contains(time("17:00")));
+        assert!(!window.contains(time("08:59")));
+    }
+
+    #[test]
+    fn windows_may_cross_midnight() {
+        let window: Window = "22:00-02:00".parse().unwrap();
+        assert!(window.contains(time("22:00")));
+        assert!(window.contains(time("23:59")));
+        assert!(window.contains(time("00:00")));
+        assert!(window.contains(time("01:59")));
+        assert!(!window.contains(time("02:00")));
+        assert!(!window.contains(time("12:00")));
+        assert!(!window.contains(time("21:59")));
+    }
+
+    #[test]
+    fn rejects_malformed_windows() {
+        for value in ["09:00", "9-17", "09:00-25:00", "10:00-10:00", "a-b"] {
+            assert!(value.parse::<Window>().is_err(), "{} was accepted", value);
+        }
+        assert_eq!(parse_windows("08:00-10:00, 18:00-20:00").unwrap().len(), 2);
+        assert!(parse_windows("08:00-10:00,").is_err());
+    }
+
+    #[test]
+    fn accepts_five_six_and_seven_field_cron_expressions() {
+        let five = &parse_times("30 9 * * *").unwrap()[0];
+        let six = &parse_times("0 30 9 * * *").unwrap()[0];
+        let after = at("2023-06-01 00:00");
+        assert_eq!(five.after(&after).next(), Some(at("2023-06-01 09:30")));
+        assert_eq!(five.after(&after).next(), six.after(&after).next());
+        let seven = &parse_times("0 30 9 * * * 2030").unwrap()[0];
+        let next = seven.after(&after).next().unwrap();
+        assert!(next >= at("2030-01-01 00:00") && next.time() == time("09:30"));
+    }
+
+    #[test]
+    fn parses_several_cron_expressions() {
+        assert_eq!(parse_times("0 9 * * *; 0 18 * * Sat").unwrap().len(), 2);
+        for value in ["61 * * * *", "* * *", "0 9 * * *;", "not cron"] {
+            assert!(parse_times(value).is_err(), "{} was accepted", value);
+        }
+    }
+
+    #[test]
+    fn posting_windows_use_the_channel_time_zone() {
+        let schedule = new_schedule(chrono_tz::Europe::Berlin, "09:00-17:00", "");
+        // Berlin is two hours ahead of UTC in summer.
+        assert!(!schedule.is_open(at("2023-06-01 06:30")));
+        assert!(schedule.is_open(at("2023-06-01 07:00")));
+        assert!(schedule.is_open(at("2023-06-01 14:59")));
+        assert!(!schedule.is_open(at("2023-06-01 15:00")));
+        assert!(!schedule.is_due(at("2023-06-01 16:00"), None, 1));
+    }
+
+    #[test]
+    fn overnight_windows_stay_open_across_the_date_change() {
+        let schedule = new_schedule(chrono_tz::UTC, "22:00-02:00", "");
+        assert!(schedule.is_due(at("2023-06-01 23:30"), None, 1));
+        assert!(schedule.is_due(at("2023-06-02 01:30"), None, 1));
+        assert!(!schedule.is_due(at("2023-06-02 02:30"), None, 1));
+    }
+
+    #[test]
+    fn without_restrictions_posts_are_always_due() {
+        let schedule = new_schedule(chrono_tz::UTC, "", "");
+        let now = at("2023-06-01 12:00");
+        assert!(schedule.is_due(now, None, 1));
+        assert!(schedule.is_due(now, Some(now.naive_utc()), 1));
+    }
+
+    #[test]
+    fn spacing_is_the_larger_of_the_interval_and_the_spread() {
+        let mut schedule = new_schedule(chrono_tz::UTC, "", "");
+        assert_eq!(schedule.spacing(3), Duration::zero());
+        schedule.spread_over = Some(Duration::minutes(60));
+        assert_eq!(schedule.spacing(4), Duration::minutes(15));
+        assert_eq!(schedule.spacing(0), Duration::minutes(60));
+        schedule.min_interval = Some(Duration::minutes(20));
+        assert_eq!(schedule.spacing(4), Duration::minutes(20));
+        assert_eq!(schedule.spacing(2), Duration::minutes(30));
+    }
+
+    #[test]
+    fn posts_wait_for_the_spacing() {
+        let mut schedule = new_schedule(chrono_tz::UTC, "", "");
+        schedule.min_interval = Some(Duration::minutes(10));
+        let last_posted_at = at("2023-06-01 12:00").naive_utc();
+        assert!(!schedule.is_due(at("2023-06-01 12:09"), Some(last_posted_at), 1));
+        assert!(schedule.is_due(at("2023-06-01 12:10"), Some(last_posted_at), 1));
+    }
+
+    #[test]
+    fn posting_times_release_a_post_once_they_pass() {
+        let schedule = new_schedule(chrono_tz::UTC, "", "0 9 * * *");
+        let last_posted_at = at("2023-05-31 09:00").naive_utc();
+        assert!(!schedule.is_due(at("2023-06-01 08:59"), Some(last_posted_at), 1));
+        assert!(schedule.is_due(at("2023-06-01 09:00"), Some(last_posted_at), 1));
+        let last_posted_at = at("2023-06-01 09:01").naive_utc();
+        assert!(!schedule.is_due(at("2023-06-01 20:00"), Some(last_posted_at), 5));
+    }
+
+    #[test]
+    fn missed_posting_times_collapse_into_one_post() {
+        let schedule = new_schedule(chrono_tz::UTC, "", "0 * * * *");
+        let last_posted_at = at("2023-06-01 06:00").naive_utc();
+        assert!(schedule.is_due(at("2023-06-01 09:30"), Some(last_posted_at), 3));
+        // Once that post went out, the next one waits for the next full hour.
+        let last_posted_at = at("2023-06-01 09:30").naive_utc();
+        assert!(!schedule.is_due(at("2023-06-01 09:45"), Some(last_posted_at), 2));
+        assert!(schedule.is_due(at("2023-06-01 10:00"), Some(last_posted_at), 2));
+    }
+
+    #[test]
+    fn posting_times_look_back_a_day_for_new_channels() {
+        let schedule = new_schedule(chrono_tz::UTC, "", "0 9 * * Mon");
+        // 2023-06-01 is a Thursday.
+        assert!(!schedule.is_due(at("2023-06-01 12:00"), None, 1));
+        assert!(schedule.is_due(at("2023-06-05 12:00"), None, 1));
+    }
+
+    #[test]
+    fn next_time_is_given_in_the_channel_time_zone() {
+        let schedule = new_schedule(chrono_tz::Europe::Berlin, "", "0 9 * * *; 0 18 * * *");
+        let next_time = schedule.next_time(at("2023-06-01 08:00")).unwrap();
+        assert_eq!(next_time.with_timezone(&Utc), at("2023-06-01 16:00"));
+        assert!(new_schedule(chrono_tz::UTC, "", "")
+            .next_time(at("2023-06-01 08:00"))
+            .is_none());
+    }
+}
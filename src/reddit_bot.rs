@@ -1,5 +1,14 @@
-use crate::settings;
-use roux::{util::RouxError, Me, Reddit};
+pub mod comment;
+pub mod submission;
+
+use crate::{db::models::Subreddit, settings};
+use comment::{CommentListing, Replies};
+use roux::{
+    response::BasicListing,
+    util::{url, RouxError},
+    Me, Reddit,
+};
+use submission::Submission;
 
 pub(crate) async fn setup_roux(reddit_conf: &settings::RedditConf) -> Result<Me, RouxError> {
     Reddit::new(
@@ -12,3 +21,87 @@ pub(crate) async fn setup_roux(reddit_conf: &settings::RedditConf) -> Result<Me,
     .login()
     .await
 }
+
+/// Fetches submissions of a subreddit using its configured sorting through the authenticated client.
+///
+/// `roux`'s own `SubmissionData` doesn't expose most of the fields the mirror needs,
+/// so the listing is deserialized into our own [`Submission`].
+pub(crate) async fn fetch_submissions(
+    reddit_bot: &Me,
+    subreddit: &Subreddit,
+    limit: u32,
+) -> Result<Vec<Submission>, RouxError> {
+    let mut request_url = format!(
+        "{}?limit={}&raw_json=1",
+        url::build_oauth(&format!(
+            "r/{}/{}",
+            subreddit.name,
+            subreddit.sorting.listing()
+        )),
+        limit
+    );
+    if subreddit.sorting.uses_period() {
+        request_url += format!("&t={}", subreddit.sort_period.as_str()).as_str();
+    }
+    let response = reddit_bot.client.get(&request_url).send().await?;
+    if !response.status().is_success() {
+        return Err(RouxError::Status(response));
+    }
+    let listing = response.json::<BasicListing<Submission>>().await?;
+    Ok(listing
+        .data
+        .children
+        .into_iter()
+        .map(|thing| thing.data)
+        .collect())
+}
+
+/// Largest number of fullnames Reddit resolves in a single `api/info` request.
+const INFO_LIMIT: usize = 100;
+
+/// Fetches the current state of submissions by their fullnames.
+pub(crate) async fn fetch_info(
+    reddit_bot: &Me,
+    fullnames: &[String],
+) -> Result<Vec<Submission>, RouxError> {
+    let mut submissions = Vec::with_capacity(fullnames.len());
+    for chunk in fullnames.chunks(INFO_LIMIT) {
+        let request_url = format!(
+            "{}?id={}&raw_json=1",
+            url::build_oauth("api/info"),
+            chunk.join(",")
+        );
+        let response = reddit_bot.client.get(&request_url).send().await?;
+        if !response.status().is_success() {
+            return Err(RouxError::Status(response));
+        }
+        let listing = response.json::<BasicListing<Submission>>().await?;
+        submissions.extend(listing.data.children.into_iter().map(|thing| thing.data));
+    }
+    Ok(submissions)
+}
+
+/// Fetches the best comments of a submission, with replies down to `depth` levels below the
+/// top-level comments.
+pub(crate) async fn fetch_comments(
+    reddit_bot: &Me,
+    fullname: &str,
+    depth: u32,
+    limit: u32,
+) -> Result<Replies, RouxError> {
+    let request_url = format!(
+        "{}?sort=top&depth={}&limit={}&raw_json=1",
+        url::build_oauth(&format!("comments/{}", fullname.trim_start_matches("t3_"))),
+        depth + 1,
+        limit
+    );
+    let response = reddit_bot.client.get(&request_url).send().await?;
+    if !response.status().is_success() {
+        return Err(RouxError::Status(response));
+    }
+    // The first listing holds the submission itself.
+    let (_, comments) = response
+        .json::<(serde::de::IgnoredAny, CommentListing)>()
+        .await?;
+    Ok(Replies::Listing(comments))
+}
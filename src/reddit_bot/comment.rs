@@ -0,0 +1,74 @@
+use serde_derive::Deserialize;
+
+/// A Reddit comment (`t1`) with the replies loaded alongside it.
+#[derive(Deserialize, Debug, Clone)]
+pub struct Comment {
+    pub author: String,
+    #[serde(default)]
+    pub body: String,
+    pub score: i64,
+    #[serde(default)]
+    pub stickied: bool,
+    #[serde(default)]
+    pub replies: Replies,
+}
+
+impl Comment {
+    /// Whether the comment is gone, leaving only a placeholder.
+    pub fn is_removed(&self) -> bool {
+        self.author == "[deleted]" || self.body == "[removed]" || self.body == "[deleted]"
+    }
+}
+
+/// Reddit sends an empty string instead of a listing when a comment has no replies.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum Replies {
+    Listing(CommentListing),
+    None(serde::de::IgnoredAny),
+}
+
+impl Default for Replies {
+    fn default() -> Self {
+        Replies::None(serde::de::IgnoredAny)
+    }
+}
+
+impl Replies {
+    pub fn comments(&self) -> impl Iterator<Item = &Comment> {
+        match self {
+            Replies::Listing(listing) => listing.comments(),
+            Replies::None(_) => [].iter(),
+        }
+        .filter_map(|thing| match thing {
+            CommentThing::Comment(comment) => Some(comment),
+            CommentThing::More(_) => None,
+        })
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct CommentListing {
+    data: CommentListingData,
+}
+
+impl CommentListing {
+    fn comments(&self) -> std::slice::Iter<'_, CommentThing> {
+        self.data.children.iter()
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct CommentListingData {
+    children: Vec<CommentThing>,
+}
+
+/// An entry of a comment tree: a comment or a stub standing for the ones not loaded.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "kind", content = "data")]
+pub enum CommentThing {
+    #[serde(rename = "t1")]
+    Comment(Comment),
+    #[serde(rename = "more")]
+    More(serde::de::IgnoredAny),
+}
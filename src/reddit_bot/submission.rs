@@ -0,0 +1,129 @@
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// A Reddit link (`t3`) as returned by the listing endpoints.
+///
+/// Queued posts keep it serialized until they are delivered.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Submission {
+    /// Fullname of the submission, e.g. `t3_13k4l2x`.
+    pub name: String,
+    pub title: String,
+    pub author: String,
+    pub subreddit: String,
+    pub permalink: String,
+    pub url: Option<String>,
+    pub score: i64,
+    #[serde(default)]
+    pub num_comments: i64,
+    pub over_18: bool,
+    pub is_self: bool,
+    #[serde(default)]
+    pub selftext: String,
+    pub link_flair_text: Option<String>,
+    pub link_flair_template_id: Option<String>,
+    #[serde(default)]
+    pub spoiler: bool,
+    #[serde(default)]
+    pub is_gallery: bool,
+    pub post_hint: Option<String>,
+    /// Fullname of the original submission when this one is a cross-post.
+    pub crosspost_parent: Option<String>,
+    #[serde(default)]
+    pub crosspost_parent_list: Vec<CrosspostParent>,
+    pub media: Option<Media>,
+    pub preview: Option<Preview>,
+    pub gallery_data: Option<GalleryData>,
+    pub media_metadata: Option<HashMap<String, MediaMetadata>>,
+    /// Set once the submission is removed, e.g. `moderator`, `deleted` or `reddit`.
+    pub removed_by_category: Option<String>,
+}
+
+/// The subset of the original submission Reddit embeds into a cross-post.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CrosspostParent {
+    pub over_18: bool,
+    #[serde(default)]
+    pub spoiler: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Media {
+    pub reddit_video: Option<RedditVideo>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RedditVideo {
+    pub fallback_url: String,
+    #[serde(default)]
+    pub is_gif: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Preview {
+    #[serde(default)]
+    pub images: Vec<PreviewImage>,
+    pub reddit_video_preview: Option<RedditVideo>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PreviewImage {
+    pub source: PreviewSource,
+    #[serde(default)]
+    pub variants: PreviewVariants,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PreviewSource {
+    pub url: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct PreviewVariants {
+    pub mp4: Option<Box<PreviewImage>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GalleryData {
+    pub items: Vec<GalleryItem>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GalleryItem {
+    pub media_id: String,
+}
+
+/// An entry of `media_metadata`, describing one gallery item.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MediaMetadata {
+    /// Kind of the item, `Image` or `AnimatedImage`.
+    pub e: Option<String>,
+    /// The full size source of the item.
+    pub s: Option<MediaSource>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MediaSource {
+    pub u: Option<String>,
+    pub mp4: Option<String>,
+    pub gif: Option<String>,
+}
+
+impl Submission {
+    pub fn reddit_link(&self) -> String {
+        format!("https://www.reddit.com{}", self.permalink)
+    }
+    /// Why the submission is no longer visible on Reddit, if it isn't.
+    pub fn removal(&self) -> Option<&str> {
+        if let Some(category) = &self.removed_by_category {
+            return Some(category);
+        }
+        if self.selftext == "[removed]" {
+            return Some("moderator");
+        }
+        if self.author == "[deleted]" {
+            return Some("deleted");
+        }
+        None
+    }
+}
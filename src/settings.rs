@@ -5,13 +5,14 @@ use std::env;
 
 impl Settings {
     pub fn from_config_file() -> Result<Self, ConfigError> {
-        let args = env::args();
-        let args: Vec<String> = args.collect();
-        let default_filename = "tg-subreddit-mirror.toml".to_owned();
-        let filename = args.get(1).unwrap_or(&default_filename);
+        // Flags like `--migration-status` may come before or after the file name.
+        let filename = env::args()
+            .skip(1)
+            .find(|arg| !arg.starts_with("--"))
+            .unwrap_or_else(|| "tg-subreddit-mirror.toml".to_owned());
         // Build the configuration
         let app_config = Config::builder()
-            .add_source(File::with_name(filename))
+            .add_source(File::with_name(&filename))
             .add_source(Environment::with_prefix("tgsmrs"))
             .build()?;
         app_config.try_deserialize()
@@ -21,6 +22,16 @@ impl Settings {
 #[derive(Deserialize, Debug)]
 pub struct TeloxideConf {
     pub token: String,
+    /// Seconds after which an unfinished conversation with the bot is forgotten.
+    #[serde(default = "default_dialogue_ttl")]
+    pub dialogue_ttl: u64,
+    /// Telegram ids of the users allowed to manage the whole instance.
+    #[serde(default)]
+    pub superadmins: Vec<u64>,
+}
+
+fn default_dialogue_ttl() -> u64 {
+    3600
 }
 
 #[derive(Deserialize, Debug)]
@@ -47,6 +58,43 @@ pub struct DatabaseConf {
     pub url: String,
 }
 
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct MirrorConf {
+    /// Seconds between two polls of every enabled subreddit.
+    pub poll_interval: u64,
+    /// How many submissions are requested from Reddit per subreddit and poll.
+    pub fetch_limit: u32,
+    /// Seconds between two checks of the queued posts against the channels' schedules.
+    pub dispatch_interval: u64,
+    /// Seconds between two checks of the recently mirrored submissions for removals.
+    pub recheck_interval: u64,
+    /// Hours after posting during which a mirrored submission is checked for removals.
+    pub recheck_window: u64,
+    /// Hours during which a delivered post keeps its cross-posts and reposts of the same URL
+    /// or media out of the channel. `0` delivers them anyway.
+    pub duplicate_window: u64,
+    /// Messages the mirror may send per second across all channels.
+    pub messages_per_second: u32,
+    /// Messages the mirror may send to a single channel per minute.
+    pub messages_per_minute_per_chat: u32,
+}
+
+impl Default for MirrorConf {
+    fn default() -> Self {
+        MirrorConf {
+            poll_interval: 300,
+            fetch_limit: 25,
+            dispatch_interval: 30,
+            recheck_interval: 1800,
+            recheck_window: 48,
+            duplicate_window: 72,
+            messages_per_second: 30,
+            messages_per_minute_per_chat: 20,
+        }
+    }
+}
+
 pub static SETTINGS_INSTANCE: Lazy<Settings> =
     Lazy::new(|| Settings::from_config_file().expect("Couldn't load app configuration"));
 
@@ -55,4 +103,6 @@ pub struct Settings {
     pub teloxide: TeloxideConf,
     pub reddit: RedditConf,
     pub database: DatabaseConf,
+    #[serde(default)]
+    pub mirror: MirrorConf,
 }
@@ -1,23 +1,43 @@
+mod admin;
 mod channel;
+mod configure;
+mod flair;
+mod listing;
+mod storage;
 mod subreddit;
 
 use std::sync::{Arc, Mutex};
 
+use crate::{
+    mirror::{comments::mirror_comments, outbox::Outbox},
+    settings::SETTINGS_INSTANCE,
+};
 use diesel::SqliteConnection;
+use serde_derive::{Deserialize, Serialize};
+use storage::SqliteStorage;
 use teloxide::{
     dispatching::{dialogue, UpdateHandler},
     macros::BotCommands,
     prelude::*,
+    types::{InlineKeyboardButton, InlineKeyboardMarkup},
+    utils::command::BotCommands as _,
 };
 
-use crate::settings::SETTINGS_INSTANCE;
+/// Buttons shown on each page of a channel or subreddit picker.
+const PICKER_PAGE_SIZE: usize = 8;
+
+/// Telegram refuses to send longer messages.
+const MESSAGE_LENGTH_LIMIT: usize = 4096;
 
-#[derive(Clone, Default)]
-pub(self) enum State {
+#[derive(Clone, Default, Serialize, Deserialize)]
+enum State {
     #[default]
     MainMenu,
     Channel(channel::State),
     Sub(subreddit::State),
+    Configure(configure::State),
+    Flair(flair::State),
+    Listing(listing::State),
 }
 
 #[derive(BotCommands, Clone)]
@@ -31,33 +51,102 @@ enum Command {
     LinkChannel,
     UnlinkChannel,
     ListChannels,
+    ListSubreddits,
+    SkipStats,
+    SetTemplate,
+    Schedule,
+    SetButtons,
+    History,
     LinkSubreddit,
     UnlinkSubreddit,
+    SetSorting,
+    SetQuota,
+    SetComments,
+    Keywords,
+    Flairs,
+    Configure,
 }
 
-pub(self) type DispatcherSchema = UpdateHandler<Box<dyn std::error::Error + Send + Sync + 'static>>;
-pub(self) type TeloxideResult = Result<(), Box<dyn std::error::Error + Send + Sync>>;
-pub(self) type AppDialogue = teloxide::dispatching::dialogue::InMemStorage<State>;
-
-pub async fn setup_teloxide(reddit_bot: roux::Me, conn: SqliteConnection) {
-    pretty_env_logger::init();
-    let bot = Bot::new(&SETTINGS_INSTANCE.teloxide.token);
-    let dispatcher = Dispatcher::builder(bot, dispatcher_schema()).dependencies(dptree::deps![
-        dialogue::InMemStorage::<State>::new(),
-        Arc::new(Mutex::new(conn)),
-        Arc::new(Mutex::new(reddit_bot))
-    ]);
+type DispatcherSchema = UpdateHandler<Box<dyn std::error::Error + Send + Sync + 'static>>;
+type TeloxideResult = Result<(), Box<dyn std::error::Error + Send + Sync>>;
+type AppDialogue = SqliteStorage<State>;
+
+pub async fn setup_teloxide(
+    bot: Bot,
+    outbox: Outbox,
+    reddit_bot: roux::Me,
+    conn: Arc<Mutex<SqliteConnection>>,
+) {
+    let storage =
+        SqliteStorage::<State>::new(conn.clone(), SETTINGS_INSTANCE.teloxide.dialogue_ttl)
+            .expect("Couldn't set up the dialogue storage");
+    Dispatcher::builder(bot, dispatcher_schema())
+        .dependencies(dptree::deps![
+            storage,
+            conn,
+            outbox,
+            Arc::new(Mutex::new(reddit_bot))
+        ])
+        .enable_ctrlc_handler()
+        .build()
+        .dispatch()
+        .await;
 }
 
 fn dispatcher_schema() -> DispatcherSchema {
-    dialogue::enter::<Update, dialogue::InMemStorage<State>, State, _>().branch(
-        Update::filter_message()
-            .branch(channel::schema())
-            .branch(subreddit::schema()),
-    )
+    dptree::entry()
+        .branch(
+            Update::filter_message()
+                .filter(|msg: Message| msg.is_automatic_forward())
+                .endpoint(on_automatic_forward),
+        )
+        .branch(
+            dialogue::enter::<Update, AppDialogue, State, _>()
+                .branch(
+                    Update::filter_message()
+                        .filter_command::<Command>()
+                        .branch(dptree::case![Command::Help].endpoint(on_help))
+                        .branch(dptree::case![Command::Cancel].endpoint(on_cancel)),
+                )
+                .branch(channel::schema())
+                .branch(subreddit::schema())
+                .branch(configure::schema())
+                .branch(flair::schema())
+                .branch(listing::schema())
+                .branch(admin::schema()),
+        )
+}
+
+/// Handles the copies of channel posts Telegram forwards into the linked discussion group.
+async fn on_automatic_forward(
+    bot: Bot,
+    msg: Message,
+    conn: Arc<Mutex<SqliteConnection>>,
+    reddit_bot: Arc<Mutex<roux::Me>>,
+) -> TeloxideResult {
+    let reddit_bot = reddit_bot.lock().unwrap().clone();
+    mirror_comments(&bot, &reddit_bot, &conn, &msg).await
 }
 
-pub(self) async fn msg_reply<T>(text: T, bot: &Bot, msg: &Message) -> TeloxideResult
+async fn on_help(bot: Bot, msg: Message) -> TeloxideResult {
+    msg_reply(Command::descriptions().to_string(), &bot, &msg).await
+}
+
+/// Leaves whatever conversation the chat is in, from any state.
+async fn on_cancel(
+    bot: Bot,
+    dialogue: Dialogue<State, AppDialogue>,
+    state: State,
+    msg: Message,
+) -> TeloxideResult {
+    if let State::MainMenu = state {
+        return msg_reply("There's nothing to cancel.", &bot, &msg).await;
+    }
+    dialogue.exit().await?;
+    msg_reply("Cancelled.", &bot, &msg).await
+}
+
+async fn msg_reply<T>(text: T, bot: &Bot, msg: &Message) -> TeloxideResult
 where
     T: Into<String>,
 {
@@ -68,9 +157,68 @@ where
         .map_err(|x| x.into())
 }
 
-pub(self) async fn update_dialogue(
-    dialogue: &Dialogue<State, AppDialogue>,
-    state: State,
-) -> TeloxideResult {
+/// Replaces the message the pressed button belongs to with `text`, removing its keyboard.
+async fn callback_reply<T>(text: T, bot: &Bot, q: &CallbackQuery) -> TeloxideResult
+where
+    T: Into<String>,
+{
+    if let Some(message) = &q.message {
+        bot.edit_message_text(message.chat.id, message.id, text)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Replaces the message the pressed button belongs to with `text` and a new keyboard.
+async fn callback_menu<T>(
+    text: T,
+    keyboard: InlineKeyboardMarkup,
+    bot: &Bot,
+    q: &CallbackQuery,
+) -> TeloxideResult
+where
+    T: Into<String>,
+{
+    if let Some(message) = &q.message {
+        bot.edit_message_text(message.chat.id, message.id, text)
+            .reply_markup(keyboard)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Lays out one button a row, a page at a time, followed by buttons sending
+/// `<page_prefix>:<page>` to turn to the neighbouring pages.
+fn paged_keyboard(
+    buttons: Vec<InlineKeyboardButton>,
+    page: usize,
+    page_prefix: &str,
+) -> InlineKeyboardMarkup {
+    let pages = buttons.len().div_ceil(PICKER_PAGE_SIZE).max(1);
+    let page = page.min(pages - 1);
+    let mut navigation = Vec::new();
+    if page > 0 {
+        navigation.push(InlineKeyboardButton::callback(
+            "Previous",
+            format!("{}:{}", page_prefix, page - 1),
+        ));
+    }
+    if page + 1 < pages {
+        navigation.push(InlineKeyboardButton::callback(
+            "Next",
+            format!("{}:{}", page_prefix, page + 1),
+        ));
+    }
+    InlineKeyboardMarkup::new(
+        buttons
+            .into_iter()
+            .skip(page * PICKER_PAGE_SIZE)
+            .take(PICKER_PAGE_SIZE)
+            .map(|button| vec![button])
+            .chain((!navigation.is_empty()).then_some(navigation)),
+    )
+}
+
+async fn update_dialogue(dialogue: &Dialogue<State, AppDialogue>, state: State) -> TeloxideResult {
     dialogue.update(state).await.map_err(|x| x.into())
 }
@@ -0,0 +1,254 @@
+use super::DispatcherSchema;
+use crate::settings::SETTINGS_INSTANCE;
+use teloxide::{macros::BotCommands, prelude::*};
+
+/// Commands reserved to the superadmins of the instance. They are left out of `/help` and
+/// ignored when anyone else sends them.
+#[derive(BotCommands, Clone)]
+#[command(rename_rule = "snake_case")]
+pub enum AdminCommand {
+    Stats,
+    ChannelsAll,
+    PauseAll,
+    ResumeAll,
+    ForceUnlink(String),
+    Broadcast(String),
+}
+
+pub mod helpers {
+    use super::*;
+
+    pub(crate) fn is_superadmin(msg: &Message) -> bool {
+        msg.from()
+            .is_some_and(|user| SETTINGS_INSTANCE.teloxide.superadmins.contains(&user.id.0))
+    }
+}
+
+mod listeners {
+    use super::*;
+    use crate::{
+        db::models::{BotStatus, Channel, InstanceStats, Subreddit},
+        mirror::outbox::Outbox,
+        teloxide::{msg_reply, TeloxideResult, MESSAGE_LENGTH_LIMIT},
+    };
+    use chrono::{Duration, Utc};
+    use diesel::SqliteConnection;
+    use log::{info, warn};
+    use std::sync::{Arc, Mutex};
+
+    pub(super) async fn on_stats(
+        bot: Bot,
+        msg: Message,
+        conn: Arc<Mutex<SqliteConnection>>,
+    ) -> TeloxideResult {
+        let (stats, status) = {
+            let conn = &mut conn.lock().unwrap();
+            let cutoff = Utc::now().naive_utc() - Duration::days(1);
+            (InstanceStats::collect(cutoff, conn)?, BotStatus::get(conn)?)
+        };
+        let mirroring = match status.paused_at {
+            Some(paused_at) if status.paused => {
+                format!("paused since {}", paused_at.format("%Y-%m-%d %H:%M UTC"))
+            }
+            _ => "active".to_owned(),
+        };
+        msg_reply(
+            format!(
+                "Mirroring: {}\n\
+                Channels: {} ({} paused)\n\
+                Subreddits: {} ({} paused)\n\
+                Channel–subreddit links: {}\n\
+                Queued posts: {}\n\
+                Posts delivered: {} ({} in the last 24 hours)",
+                mirroring,
+                stats.channels,
+                stats.paused_channels,
+                stats.subreddits,
+                stats.paused_subreddits,
+                stats.links,
+                stats.queued_posts,
+                stats.posted_total,
+                stats.posted_since
+            ),
+            &bot,
+            &msg,
+        )
+        .await
+    }
+
+    pub(super) async fn on_channels_all(
+        bot: Bot,
+        msg: Message,
+        conn: Arc<Mutex<SqliteConnection>>,
+    ) -> TeloxideResult {
+        let entries = {
+            let conn = &mut conn.lock().unwrap();
+            let mut entries = Vec::new();
+            for channel in Channel::get_all(conn)? {
+                let subreddits = Subreddit::get_by_channel(channel.clone(), conn)?;
+                entries.push(format!(
+                    "{} (id: {})\nOwner: {}\nSubreddits: {}{}\n\n",
+                    channel.title,
+                    channel.chat_id,
+                    channel
+                        .owner_id
+                        .map(|owner_id| owner_id.to_string())
+                        .unwrap_or_else(|| "unknown".to_owned()),
+                    subreddits.len(),
+                    if channel.disabled { "\nPaused" } else { "" }
+                ));
+            }
+            entries
+        };
+        if entries.is_empty() {
+            return msg_reply("No channels are linked.", &bot, &msg).await;
+        }
+        // Spread the listing over as many messages as it takes to stay under the limit.
+        let mut messages = vec![String::new()];
+        for entry in entries {
+            let message = messages.last_mut().unwrap();
+            if !message.is_empty()
+                && message.chars().count() + entry.chars().count() > MESSAGE_LENGTH_LIMIT
+            {
+                messages.push(entry);
+            } else {
+                message.push_str(&entry);
+            }
+        }
+        for message in messages {
+            msg_reply(message, &bot, &msg).await?;
+        }
+        Ok(())
+    }
+
+    pub(super) async fn on_pause_all(
+        bot: Bot,
+        msg: Message,
+        conn: Arc<Mutex<SqliteConnection>>,
+    ) -> TeloxideResult {
+        BotStatus::set_paused(
+            true,
+            msg.from().map(|user| user.id),
+            &mut conn.lock().unwrap(),
+        )?;
+        info!(
+            "Mirroring paused by superadmin {:?}",
+            msg.from().map(|user| user.id)
+        );
+        msg_reply(
+            "Paused all mirroring. Queued posts are kept until /resume_all.",
+            &bot,
+            &msg,
+        )
+        .await
+    }
+
+    pub(super) async fn on_resume_all(
+        bot: Bot,
+        msg: Message,
+        conn: Arc<Mutex<SqliteConnection>>,
+    ) -> TeloxideResult {
+        BotStatus::set_paused(
+            false,
+            msg.from().map(|user| user.id),
+            &mut conn.lock().unwrap(),
+        )?;
+        info!(
+            "Mirroring resumed by superadmin {:?}",
+            msg.from().map(|user| user.id)
+        );
+        msg_reply("Resumed mirroring.", &bot, &msg).await
+    }
+
+    pub(super) async fn on_force_unlink(
+        bot: Bot,
+        msg: Message,
+        conn: Arc<Mutex<SqliteConnection>>,
+        chat_id: String,
+    ) -> TeloxideResult {
+        let chat_id = match chat_id.trim().parse() {
+            Ok(chat_id) => ChatId(chat_id),
+            Err(_) => {
+                return msg_reply("Usage: /force_unlink <channel id>", &bot, &msg).await;
+            }
+        };
+        let actor = msg.from().map(|user| user.id);
+        let unlinked = {
+            let conn = &mut conn.lock().unwrap();
+            match Channel::get_by_chat_id(chat_id, conn) {
+                Ok(channel) => {
+                    Channel::delete(channel.chat_id, actor, conn)?;
+                    Some(channel)
+                }
+                Err(_) => None,
+            }
+        };
+        let channel = match unlinked {
+            Some(channel) => channel,
+            None => return msg_reply("Couldn't find the channel.", &bot, &msg).await,
+        };
+        info!(
+            "Channel {} force-unlinked by superadmin {:?}",
+            channel.chat_id,
+            msg.from().map(|user| user.id)
+        );
+        msg_reply(
+            format!(
+                "Unlinked the channel {} (id: {}) and its subreddits.",
+                channel.title, channel.chat_id
+            ),
+            &bot,
+            &msg,
+        )
+        .await
+    }
+
+    pub(super) async fn on_broadcast(
+        bot: Bot,
+        msg: Message,
+        conn: Arc<Mutex<SqliteConnection>>,
+        outbox: Outbox,
+        text: String,
+    ) -> TeloxideResult {
+        let text = text.trim();
+        if text.is_empty() {
+            return msg_reply("Usage: /broadcast <message>", &bot, &msg).await;
+        }
+        let owner_ids = Channel::get_owner_ids(&mut conn.lock().unwrap())?;
+        let mut failed = 0;
+        for owner_id in &owner_ids {
+            // Owners who never started a private chat with the bot can't be messaged.
+            let chat_id = ChatId(*owner_id);
+            if let Err(error) = outbox.send(chat_id, bot.send_message(chat_id, text)).await {
+                warn!("Couldn't send the broadcast to {}: {}", owner_id, error);
+                failed += 1;
+            }
+        }
+        msg_reply(
+            format!(
+                "Sent the message to {} of {} channel owners.",
+                owner_ids.len() - failed,
+                owner_ids.len()
+            ),
+            &bot,
+            &msg,
+        )
+        .await
+    }
+}
+
+pub fn schema() -> DispatcherSchema {
+    use super::State as SupState;
+    use teloxide::dptree::case;
+    Update::filter_message().branch(
+        case![SupState::MainMenu]
+            .filter(|msg: Message| helpers::is_superadmin(&msg))
+            .filter_command::<AdminCommand>()
+            .branch(case![AdminCommand::Stats].endpoint(listeners::on_stats))
+            .branch(case![AdminCommand::ChannelsAll].endpoint(listeners::on_channels_all))
+            .branch(case![AdminCommand::PauseAll].endpoint(listeners::on_pause_all))
+            .branch(case![AdminCommand::ResumeAll].endpoint(listeners::on_resume_all))
+            .branch(case![AdminCommand::ForceUnlink(chat_id)].endpoint(listeners::on_force_unlink))
+            .branch(case![AdminCommand::Broadcast(text)].endpoint(listeners::on_broadcast)),
+    )
+}
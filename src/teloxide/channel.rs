@@ -1,13 +1,20 @@
-use crate::db::models::Channel;
+use crate::db::models::{Channel, Subreddit};
 
-use super::DispatcherSchema;
+use super::{DispatcherSchema, MESSAGE_LENGTH_LIMIT};
 use diesel::SqliteConnection;
+use serde_derive::{Deserialize, Serialize};
 use std::sync::{Arc, Mutex};
 use teloxide::prelude::*;
 
 pub mod helpers {
     use super::*;
+    use crate::teloxide::paged_keyboard;
+    use log::warn;
     use std::error::Error;
+    use teloxide::{
+        types::{InlineKeyboardButton, InlineKeyboardMarkup, Me, User},
+        RequestError,
+    };
 
     pub(crate) fn channel_list_message(
         channels: Vec<Channel>,
@@ -23,6 +30,236 @@ pub mod helpers {
         Ok(message_content)
     }
 
+    pub(crate) fn skip_stats_message(
+        channels: Vec<Channel>,
+        conn: &mut SqliteConnection,
+    ) -> Result<String, Box<dyn Error + Send + Sync + 'static>> {
+        use crate::{db::models::SkippedSubmission, mirror::filter::SkipReason};
+
+        let channel_count = channels.len();
+        let mut message_content = String::new();
+        for (shown, available_channel) in channels.into_iter().enumerate() {
+            let mut section = format!("Channel name: {}\n", available_channel.title);
+            let counts = SkippedSubmission::count_by_reason(&available_channel, conn)?;
+            if counts.is_empty() {
+                section += "No skipped posts.\n\n";
+            } else {
+                for (reason, count) in counts {
+                    section += format!("{}: {}\n", SkipReason::describe(&reason), count).as_str();
+                }
+                section += "Latest skipped posts:\n";
+                for skipped in SkippedSubmission::get_latest(&available_channel, 5, conn)? {
+                    section += format!(
+                        "https://redd.it/{} - {}{}\n",
+                        skipped.fullname.trim_start_matches("t3_"),
+                        SkipReason::describe(&skipped.reason),
+                        skipped
+                            .detail
+                            .map(|detail| format!(" ({})", detail))
+                            .unwrap_or_default()
+                    )
+                    .as_str();
+                }
+                section += "\n";
+            }
+            // Leave room for the line telling how many channels didn't fit.
+            let length = message_content.chars().count() + section.chars().count();
+            if length + 40 > MESSAGE_LENGTH_LIMIT {
+                message_content += format!("…and {} more channels", channel_count - shown).as_str();
+                break;
+            }
+            message_content += section.as_str();
+        }
+        Ok(message_content)
+    }
+
+    pub(crate) fn schedule_message(
+        channel: &Channel,
+        conn: &mut SqliteConnection,
+    ) -> Result<String, Box<dyn Error + Send + Sync + 'static>> {
+        use crate::{db::models::QueuedPost, mirror::schedule::Schedule};
+        use chrono::Utc;
+
+        let next_time = match Schedule::for_channel(channel) {
+            Ok(schedule) => schedule.next_time(Utc::now()).map(|next_time| {
+                format!(
+                    "\nNext posting time: {}",
+                    next_time.format("%Y-%m-%d %H:%M")
+                )
+            }),
+            Err(error) => Some(format!("\nThe schedule is invalid: {}", error)),
+        };
+        Ok(format!(
+            "Schedule of {}:\nTime zone: {}\nPosting windows: {}\nPosting times: {}{}\n\
+            Minimum interval: {}\nSpread over the poll interval: {}\nQueued posts: {}",
+            channel.title,
+            channel.timezone,
+            channel.posting_windows.as_deref().unwrap_or("any time"),
+            channel
+                .posting_times
+                .as_deref()
+                .unwrap_or("as soon as posts arrive"),
+            next_time.unwrap_or_default(),
+            channel
+                .min_post_interval
+                .map(|min_interval| format!("{} seconds", min_interval))
+                .unwrap_or_else(|| "none".to_owned()),
+            if channel.spread_posts { "on" } else { "off" },
+            QueuedPost::get_by_channel(channel, conn)?.len()
+        ))
+    }
+
+    pub(crate) fn buttons_message(channel: &Channel) -> String {
+        let buttons = channel.post_buttons();
+        if buttons.is_empty() {
+            return format!("Posts in {} have no buttons.", channel.title);
+        }
+        format!(
+            "Buttons under the posts in {}: {}",
+            channel.title,
+            buttons
+                .iter()
+                .map(|button| button.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    }
+
+    /// Whether the user is currently an administrator of the channel who may post in it.
+    pub(crate) async fn can_manage_channel(
+        bot: &Bot,
+        channel: &Channel,
+        user: Option<&User>,
+    ) -> Result<bool, RequestError> {
+        let user = match user {
+            Some(user) => user,
+            None => return Ok(false),
+        };
+        match bot.get_chat_member(ChatId(channel.chat_id), user.id).await {
+            Ok(member) => Ok(member.kind.can_post_messages()),
+            // Telegram refuses to tell about chats the bot was removed from.
+            Err(RequestError::Api(_)) => Ok(false),
+            Err(error) => Err(error),
+        }
+    }
+
+    pub(crate) fn not_admin_message(channel: &Channel) -> String {
+        format!(
+            "Only administrators of {} who can post messages there may manage it.",
+            channel.title
+        )
+    }
+
+    /// The sorting and the pause switch are kept on the subreddit itself and so apply to every
+    /// channel it's linked to. Only a user managing all of those channels may change them.
+    pub(crate) async fn can_manage_subreddit(
+        bot: &Bot,
+        subreddit: &Subreddit,
+        user: Option<&User>,
+        conn: &Arc<Mutex<SqliteConnection>>,
+    ) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        let channels = Channel::get_by_subreddit(subreddit.clone(), &mut conn.lock().unwrap())?;
+        for channel in &channels {
+            if !can_manage_channel(bot, channel, user).await? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    pub(crate) fn shared_subreddit_message(subreddit: &Subreddit) -> String {
+        format!(
+            "r/{} is also linked to channels you don't manage. Its sorting and pause switch \
+            apply to all of them, so only their administrators may change those.",
+            subreddit.name
+        )
+    }
+
+    /// The keyboard of the subreddits linked to the channel, with a button to pick the whole
+    /// channel instead.
+    pub(crate) fn template_target_keyboard(
+        subreddits: &[Subreddit],
+        page: usize,
+    ) -> InlineKeyboardMarkup {
+        use crate::teloxide::subreddit::helpers::subreddit_keyboard;
+
+        let mut keyboard = subreddit_keyboard(subreddits, page);
+        keyboard.inline_keyboard.insert(
+            0,
+            vec![InlineKeyboardButton::callback("Whole channel", "all")],
+        );
+        keyboard
+    }
+
+    pub(crate) fn channel_keyboard(channels: &[Channel], page: usize) -> InlineKeyboardMarkup {
+        paged_keyboard(
+            channels
+                .iter()
+                .map(|channel| {
+                    InlineKeyboardButton::callback(
+                        channel.title.clone(),
+                        format!("channel:{}", channel.chat_id),
+                    )
+                })
+                .collect(),
+            page,
+            "channels",
+        )
+    }
+
+    /// Handles a press on the channel keyboard. The page buttons turn the page, a channel is
+    /// only returned once the user is known to manage it.
+    pub(crate) async fn picked_channel(
+        bot: &Bot,
+        q: &CallbackQuery,
+        me: &Me,
+        conn: &Arc<Mutex<SqliteConnection>>,
+    ) -> Result<Option<Channel>, Box<dyn Error + Send + Sync>> {
+        let data = q.data.as_deref().unwrap_or_default();
+        if let Some(page) = data
+            .strip_prefix("channels:")
+            .and_then(|page| page.parse::<usize>().ok())
+        {
+            bot.answer_callback_query(q.id.clone()).await?;
+            let channels =
+                get_channels_where_admins(bot, conn.clone(), &q.from.id, &me.user.id).await?;
+            if let Some(message) = &q.message {
+                bot.edit_message_reply_markup(message.chat.id, message.id)
+                    .reply_markup(channel_keyboard(&channels, page))
+                    .await?;
+            }
+            return Ok(None);
+        }
+        let chat_id = match data
+            .strip_prefix("channel:")
+            .and_then(|chat_id| chat_id.parse::<i64>().ok())
+        {
+            Some(chat_id) => ChatId(chat_id),
+            None => {
+                bot.answer_callback_query(q.id.clone()).await?;
+                return Ok(None);
+            }
+        };
+        let channel = Channel::get_by_chat_id(chat_id, &mut conn.lock().unwrap());
+        let channel = match channel {
+            Ok(channel) => channel,
+            Err(_) => {
+                bot.answer_callback_query(q.id.clone())
+                    .text("This channel isn't linked anymore.")
+                    .await?;
+                return Ok(None);
+            }
+        };
+        if !can_manage_channel(bot, &channel, Some(&q.from)).await? {
+            bot.answer_callback_query(q.id.clone())
+                .text(not_admin_message(&channel))
+                .await?;
+            return Ok(None);
+        }
+        bot.answer_callback_query(q.id.clone()).await?;
+        Ok(Some(channel))
+    }
+
     pub(crate) async fn get_channels_where_admins(
         bot: &Bot,
         conn: Arc<Mutex<SqliteConnection>>,
@@ -37,13 +274,21 @@ pub mod helpers {
             .load::<i64>(&mut *conn.lock().unwrap())?;
         let mut available_channels: Vec<i64> = Vec::with_capacity(linked_channel_ids.capacity());
         for channel_id in linked_channel_ids {
-            let admins: Vec<_> = bot
-                .get_chat_administrators(ChatId(channel_id))
-                .await?
+            // One unreachable channel, e.g. one the bot was removed from, shouldn't hide the rest.
+            let admins = match bot.get_chat_administrators(ChatId(channel_id)).await {
+                Ok(admins) => admins,
+                Err(error) => {
+                    warn!(
+                        "Couldn't get the administrators of channel {}: {}",
+                        channel_id, error
+                    );
+                    continue;
+                }
+            };
+            let user_may_post = admins
                 .iter()
-                .map(|admin| admin.user.id)
-                .collect();
-            if admins.contains(user_id) && admins.contains(bot_id) {
+                .any(|admin| admin.user.id == *user_id && admin.kind.can_post_messages());
+            if user_may_post && admins.iter().any(|admin| admin.user.id == *bot_id) {
                 available_channels.push(channel_id);
             }
         }
@@ -55,16 +300,23 @@ pub mod helpers {
 }
 
 mod listeners {
+    use super::helpers::{can_manage_channel, not_admin_message, picked_channel};
     use super::*;
     use crate::{
         db::models::NewChannel,
-        teloxide::{msg_reply, update_dialogue, AppDialogue, State as SupState, TeloxideResult},
+        teloxide::{
+            callback_menu, callback_reply, msg_reply, update_dialogue, AppDialogue,
+            State as SupState, TeloxideResult,
+        },
     };
     use teloxide::{
         types::{Me, Message},
         Bot,
     };
 
+    /// Audit entries shown by `/history`.
+    const HISTORY_LENGTH: i64 = 10;
+
     pub(super) async fn on_channel_link(
         bot: Bot,
         dialogue: Dialogue<SupState, AppDialogue>,
@@ -106,6 +358,10 @@ mod listeners {
                 )
                 .await;
         }
+        let from_user = match msg.from() {
+            Some(user) => user,
+            None => return msg_reply("Couldn't recognize the user. Try again.", &bot, &msg).await,
+        };
         let chat_admins = bot.get_chat_administrators(forward_chat.id).await?;
         if !chat_admins.iter().any(|admin| admin.user.id == me.id) {
             return msg_reply(
@@ -115,11 +371,30 @@ mod listeners {
                 )
                 .await;
         }
+        if !chat_admins
+            .iter()
+            .any(|admin| admin.user.id == from_user.id && admin.kind.can_post_messages())
+        {
+            return msg_reply(
+                format!(
+                    "Only administrators of this channel who can post messages there may link it. Try again or use command /cancel@{}",
+                    me.username()
+                ),
+                &bot,
+                &msg,
+            )
+            .await;
+        }
+        if Channel::get_by_chat_id(forward_chat.id, &mut conn.lock().unwrap()).is_ok() {
+            msg_reply("This channel is already linked.", &bot, &msg).await?;
+            return update_dialogue(&dialogue, SupState::MainMenu).await;
+        }
         let new_channel: NewChannel = NewChannel::new(
             forward_chat.id.0,
             forward_chat.title().unwrap_or_default(),
             forward_chat.username(),
             forward_chat.invite_link(),
+            from_user.id,
         );
         let channel = new_channel.insert(&mut conn.lock().unwrap())?;
         msg_reply(
@@ -141,61 +416,46 @@ mod listeners {
         me: Me,
         conn: Arc<Mutex<SqliteConnection>>,
     ) -> TeloxideResult {
-        use super::helpers::{channel_list_message, get_channels_where_admins};
+        use super::helpers::{channel_keyboard, get_channels_where_admins};
 
         let from_user = match msg.from() {
             Some(user) => user,
             None => return msg_reply("Couldn't recognize the user. Try again.", &bot, &msg).await,
         };
         let channels = get_channels_where_admins(&bot, conn, &from_user.id, &me.user.id).await?;
-        msg_reply(
-            format!(
-                "Okay. Type the ID of the channel you want to unlink:\n\n{}",
-                channel_list_message(channels)?
-            ),
-            &bot,
-            &msg,
-        )
-        .await?;
+        if channels.is_empty() {
+            return msg_reply(
+                "No channels found. Try adding a new channel first",
+                &bot,
+                &msg,
+            )
+            .await;
+        }
+        bot.send_message(msg.chat.id, "Okay. Choose the channel you want to unlink:")
+            .reply_to_message_id(msg.id)
+            .reply_markup(channel_keyboard(&channels, 0))
+            .await?;
         update_dialogue(&dialogue, SupState::Channel(State::UnlinkReceiveChannel)).await
     }
 
-    pub(super) async fn on_channel_unlink_msg(
+    pub(super) async fn on_channel_unlink_channel(
         bot: Bot,
         dialogue: Dialogue<SupState, AppDialogue>,
-        msg: Message,
+        q: CallbackQuery,
+        me: Me,
         conn: Arc<Mutex<SqliteConnection>>,
     ) -> TeloxideResult {
-        let channel_id: ChatId = match msg.text() {
-            Some(text) => ChatId(text.parse()?),
-            None => {
-                return msg_reply(
-                    "Please send a message with the id of the channel you wish to unlink",
-                    &bot,
-                    &msg,
-                )
-                .await;
-            }
-        };
-        let channel = Channel::get_by_chat_id(channel_id, &mut conn.lock().unwrap());
-        let channel = match channel {
-            Ok(channel) => channel,
-            Err(_) => {
-                return msg_reply(
-                    "Couldn't find the channel. Please send the of an already linked channel.",
-                    &bot,
-                    &msg,
-                )
-                .await;
-            }
+        let channel = match picked_channel(&bot, &q, &me, &conn).await? {
+            Some(channel) => channel,
+            None => return Ok(()),
         };
-        msg_reply(
+        callback_reply(
             format!(
                 "Are you sure you want to remove channel \"{}\" (Id: {})? Type the channel title to remove it",
                 channel.title, channel.chat_id
             ),
             &bot,
-            &msg,
+            &q,
         )
         .await?;
         update_dialogue(&dialogue, SupState::Channel(State::UnlinkConfirm(channel))).await
@@ -211,7 +471,15 @@ mod listeners {
         if msg.text().unwrap_or("") != channel.title {
             return msg_reply("Cancelled unlinking channel.", &bot, &msg).await;
         }
-        let deleted_rows = Channel::delete(channel.chat_id, &mut conn.lock().unwrap())?;
+        if !can_manage_channel(&bot, &channel, msg.from()).await? {
+            msg_reply(not_admin_message(&channel), &bot, &msg).await?;
+            return update_dialogue(&dialogue, SupState::MainMenu).await;
+        }
+        let deleted_rows = Channel::delete(
+            channel.chat_id,
+            msg.from().map(|user| user.id),
+            &mut conn.lock().unwrap(),
+        )?;
         msg_reply(
             if deleted_rows != 0 {
                 "Successfully unlinked channel."
@@ -240,36 +508,625 @@ mod listeners {
         let channels = get_channels_where_admins(&bot, conn, &user_id, &me.user.id).await?;
         msg_reply(channel_list_message(channels)?, &bot, &msg).await
     }
+
+    pub(super) async fn on_channel_skip_stats(
+        bot: Bot,
+        msg: Message,
+        conn: Arc<Mutex<SqliteConnection>>,
+        me: Me,
+    ) -> TeloxideResult {
+        use super::helpers::{get_channels_where_admins, skip_stats_message};
+
+        let user_id = match msg.from() {
+            Some(user) => user.id,
+            None => return msg_reply("Couldn't recognize the user. Try again.", &bot, &msg).await,
+        };
+        let channels = get_channels_where_admins(&bot, conn.clone(), &user_id, &me.user.id).await?;
+        if channels.is_empty() {
+            return msg_reply("No channels found.", &bot, &msg).await;
+        }
+        let message_content = skip_stats_message(channels, &mut conn.lock().unwrap())?;
+        msg_reply(message_content, &bot, &msg).await
+    }
+
+    pub(super) async fn on_channel_template(
+        bot: Bot,
+        dialogue: Dialogue<SupState, AppDialogue>,
+        msg: Message,
+        me: Me,
+        conn: Arc<Mutex<SqliteConnection>>,
+    ) -> TeloxideResult {
+        use super::helpers::{channel_keyboard, get_channels_where_admins};
+
+        let from_user = match msg.from() {
+            Some(user) => user,
+            None => return msg_reply("Couldn't recognize the user. Try again.", &bot, &msg).await,
+        };
+        let channels = get_channels_where_admins(&bot, conn, &from_user.id, &me.user.id).await?;
+        if channels.is_empty() {
+            return msg_reply(
+                "No channels found. Try adding a new channel first",
+                &bot,
+                &msg,
+            )
+            .await;
+        }
+        bot.send_message(
+            msg.chat.id,
+            "Okay. Choose the channel you want to set the caption template for:",
+        )
+        .reply_to_message_id(msg.id)
+        .reply_markup(channel_keyboard(&channels, 0))
+        .await?;
+        update_dialogue(&dialogue, SupState::Channel(State::TemplateReceiveChannel)).await
+    }
+
+    pub(super) async fn on_channel_template_channel(
+        bot: Bot,
+        dialogue: Dialogue<SupState, AppDialogue>,
+        q: CallbackQuery,
+        me: Me,
+        conn: Arc<Mutex<SqliteConnection>>,
+    ) -> TeloxideResult {
+        use super::helpers::template_target_keyboard;
+
+        let channel = match picked_channel(&bot, &q, &me, &conn).await? {
+            Some(channel) => channel,
+            None => return Ok(()),
+        };
+        let subreddits = Subreddit::get_by_channel(channel.clone(), &mut conn.lock().unwrap())?;
+        callback_menu(
+            "Choose whether to set the template of the whole channel, or to override it only for a linked subreddit:",
+            template_target_keyboard(&subreddits, 0),
+            &bot,
+            &q,
+        )
+        .await?;
+        update_dialogue(
+            &dialogue,
+            SupState::Channel(State::TemplateReceiveTarget(channel)),
+        )
+        .await
+    }
+
+    pub(super) async fn on_channel_template_target(
+        bot: Bot,
+        dialogue: Dialogue<SupState, AppDialogue>,
+        q: CallbackQuery,
+        conn: Arc<Mutex<SqliteConnection>>,
+        channel: Channel,
+    ) -> TeloxideResult {
+        use super::helpers::template_target_keyboard;
+        use crate::{
+            mirror::caption::{DEFAULT_TEMPLATE, TEMPLATE_HELP},
+            teloxide::subreddit::helpers::picked_subreddit,
+        };
+
+        let subreddit = if q.data.as_deref() == Some("all") {
+            bot.answer_callback_query(q.id.clone()).await?;
+            None
+        } else {
+            match picked_subreddit(&bot, &q, &channel, &conn, template_target_keyboard).await? {
+                Some(subreddit) => Some(subreddit),
+                None => return Ok(()),
+            }
+        };
+        callback_reply(
+            format!(
+                "Now send the template. Send \"reset\" to remove it.\n\n{}\n\nThe default template is:\n{}",
+                TEMPLATE_HELP, DEFAULT_TEMPLATE
+            ),
+            &bot,
+            &q,
+        )
+        .await?;
+        update_dialogue(
+            &dialogue,
+            SupState::Channel(State::TemplateReceiveTemplate(channel, subreddit)),
+        )
+        .await
+    }
+
+    pub(super) async fn on_channel_template_template(
+        bot: Bot,
+        dialogue: Dialogue<SupState, AppDialogue>,
+        msg: Message,
+        conn: Arc<Mutex<SqliteConnection>>,
+        (channel, subreddit): (Channel, Option<Subreddit>),
+    ) -> TeloxideResult {
+        use crate::{
+            db::models::ChannelSubreddit,
+            mirror::caption::{CaptionContext, Template},
+        };
+        use teloxide::types::ParseMode;
+
+        if !can_manage_channel(&bot, &channel, msg.from()).await? {
+            msg_reply(not_admin_message(&channel), &bot, &msg).await?;
+            return update_dialogue(&dialogue, SupState::MainMenu).await;
+        }
+        let text = match msg.text() {
+            Some(text) => text,
+            None => return msg_reply("Please send the template as text.", &bot, &msg).await,
+        };
+        let source = if text.trim().eq_ignore_ascii_case("reset") {
+            None
+        } else {
+            Some(text)
+        };
+        if let Some(source) = source {
+            let template: Template = match source.parse() {
+                Ok(template) => template,
+                Err(error) => {
+                    return msg_reply(
+                        format!("Invalid template: {}. Try again.", error),
+                        &bot,
+                        &msg,
+                    )
+                    .await
+                }
+            };
+            let preview = template.render(&CaptionContext::sample(&channel), 1024);
+            if let Err(error) = bot
+                .send_message(msg.chat.id, preview)
+                .parse_mode(ParseMode::Html)
+                .await
+            {
+                return msg_reply(
+                    format!(
+                        "Telegram couldn't display the template: {}. Try again.",
+                        error
+                    ),
+                    &bot,
+                    &msg,
+                )
+                .await;
+            }
+        }
+        {
+            let actor = msg.from().map(|user| user.id);
+            let conn = &mut conn.lock().unwrap();
+            match &subreddit {
+                Some(subreddit) => {
+                    ChannelSubreddit::get(&channel, subreddit, conn)?
+                        .set_caption_template(source, actor, conn)?;
+                }
+                None => {
+                    channel.set_caption_template(source, actor, conn)?;
+                }
+            }
+        }
+        msg_reply(
+            match source {
+                Some(_) => {
+                    "Saved the caption template. The message above shows how a post will look."
+                }
+                None => "Removed the caption template.",
+            },
+            &bot,
+            &msg,
+        )
+        .await?;
+        update_dialogue(&dialogue, SupState::MainMenu).await
+    }
+
+    pub(super) async fn on_channel_schedule(
+        bot: Bot,
+        dialogue: Dialogue<SupState, AppDialogue>,
+        msg: Message,
+        me: Me,
+        conn: Arc<Mutex<SqliteConnection>>,
+    ) -> TeloxideResult {
+        use super::helpers::{channel_keyboard, get_channels_where_admins};
+
+        let from_user = match msg.from() {
+            Some(user) => user,
+            None => return msg_reply("Couldn't recognize the user. Try again.", &bot, &msg).await,
+        };
+        let channels = get_channels_where_admins(&bot, conn, &from_user.id, &me.user.id).await?;
+        if channels.is_empty() {
+            return msg_reply(
+                "No channels found. Try adding a new channel first",
+                &bot,
+                &msg,
+            )
+            .await;
+        }
+        bot.send_message(
+            msg.chat.id,
+            "Okay. Choose the channel whose schedule you want to see or change:",
+        )
+        .reply_to_message_id(msg.id)
+        .reply_markup(channel_keyboard(&channels, 0))
+        .await?;
+        update_dialogue(&dialogue, SupState::Channel(State::ScheduleReceiveChannel)).await
+    }
+
+    pub(super) async fn on_channel_schedule_channel(
+        bot: Bot,
+        dialogue: Dialogue<SupState, AppDialogue>,
+        q: CallbackQuery,
+        me: Me,
+        conn: Arc<Mutex<SqliteConnection>>,
+    ) -> TeloxideResult {
+        use super::helpers::schedule_message;
+
+        let channel = match picked_channel(&bot, &q, &me, &conn).await? {
+            Some(channel) => channel,
+            None => return Ok(()),
+        };
+        let message_content = schedule_message(&channel, &mut conn.lock().unwrap())?;
+        callback_reply(
+            format!(
+                "{}\n\nTo change it, send one of:\n\
+                timezone Europe/Berlin\n\
+                windows 08:00-12:00, 18:00-23:00 (or \"windows none\" to post at any time)\n\
+                times 0 9 * * *; 30 18 * * 1-5 (cron expressions in the channel's time zone, \
+                one post is released at each time; \"times none\" posts as soon as possible)\n\
+                interval 600 (seconds to wait at least between two posts, or \"interval none\")\n\
+                spread on (space the queued posts evenly over the poll interval, or \"spread off\")\n\n\
+                Send \"done\" when you're finished.",
+                message_content
+            ),
+            &bot,
+            &q,
+        )
+        .await?;
+        update_dialogue(
+            &dialogue,
+            SupState::Channel(State::ScheduleReceiveSetting(channel)),
+        )
+        .await
+    }
+
+    pub(super) async fn on_channel_schedule_setting(
+        bot: Bot,
+        dialogue: Dialogue<SupState, AppDialogue>,
+        msg: Message,
+        conn: Arc<Mutex<SqliteConnection>>,
+        channel: Channel,
+    ) -> TeloxideResult {
+        use super::helpers::schedule_message;
+        use crate::db::models::{ScheduleSetting, SettingError};
+
+        if !can_manage_channel(&bot, &channel, msg.from()).await? {
+            msg_reply(not_admin_message(&channel), &bot, &msg).await?;
+            return update_dialogue(&dialogue, SupState::MainMenu).await;
+        }
+        let text = msg.text().unwrap_or_default().trim();
+        let (name, value) = text.split_once(' ').unwrap_or((text, ""));
+        let value = value.trim();
+        let optional_value = if value.is_empty() || value.eq_ignore_ascii_case("none") {
+            None
+        } else {
+            Some(value.to_owned())
+        };
+        let setting = match name.to_lowercase().as_str() {
+            "done" => {
+                msg_reply("Saved the schedule.", &bot, &msg).await?;
+                return update_dialogue(&dialogue, SupState::MainMenu).await;
+            }
+            "timezone" => ScheduleSetting::Timezone(value.to_owned()),
+            "windows" => ScheduleSetting::Windows(optional_value),
+            "times" => ScheduleSetting::Times(optional_value),
+            "interval" => match optional_value.map(|value| value.parse::<i32>()) {
+                None => ScheduleSetting::MinInterval(None),
+                Some(Ok(min_interval)) => ScheduleSetting::MinInterval(Some(min_interval)),
+                Some(Err(_)) => {
+                    return msg_reply(
+                        "The interval must be a whole number of seconds. Try again.",
+                        &bot,
+                        &msg,
+                    )
+                    .await
+                }
+            },
+            "spread" => match value.to_lowercase().as_str() {
+                "on" => ScheduleSetting::Spread(true),
+                "off" => ScheduleSetting::Spread(false),
+                _ => {
+                    return msg_reply("Please send \"spread on\" or \"spread off\".", &bot, &msg)
+                        .await
+                }
+            },
+            _ => {
+                return msg_reply(
+                    "Please start the message with timezone, windows, times, interval or spread, \
+                    or send \"done\".",
+                    &bot,
+                    &msg,
+                )
+                .await
+            }
+        };
+        let updated = channel.update_schedule(
+            setting,
+            msg.from().map(|user| user.id),
+            &mut conn.lock().unwrap(),
+        );
+        let channel = match updated {
+            Ok(channel) => channel,
+            Err(SettingError::Invalid(reason)) => {
+                return msg_reply(format!("{}. Try again.", reason), &bot, &msg).await
+            }
+            Err(error) => return Err(error.into()),
+        };
+        let message_content = schedule_message(&channel, &mut conn.lock().unwrap())?;
+        msg_reply(message_content, &bot, &msg).await?;
+        update_dialogue(
+            &dialogue,
+            SupState::Channel(State::ScheduleReceiveSetting(channel)),
+        )
+        .await
+    }
+
+    pub(super) async fn on_channel_buttons(
+        bot: Bot,
+        dialogue: Dialogue<SupState, AppDialogue>,
+        msg: Message,
+        me: Me,
+        conn: Arc<Mutex<SqliteConnection>>,
+    ) -> TeloxideResult {
+        use super::helpers::{channel_keyboard, get_channels_where_admins};
+
+        let from_user = match msg.from() {
+            Some(user) => user,
+            None => return msg_reply("Couldn't recognize the user. Try again.", &bot, &msg).await,
+        };
+        let channels = get_channels_where_admins(&bot, conn, &from_user.id, &me.user.id).await?;
+        if channels.is_empty() {
+            return msg_reply(
+                "No channels found. Try adding a new channel first",
+                &bot,
+                &msg,
+            )
+            .await;
+        }
+        bot.send_message(
+            msg.chat.id,
+            "Okay. Choose the channel whose post buttons you want to change:",
+        )
+        .reply_to_message_id(msg.id)
+        .reply_markup(channel_keyboard(&channels, 0))
+        .await?;
+        update_dialogue(&dialogue, SupState::Channel(State::ButtonsReceiveChannel)).await
+    }
+
+    pub(super) async fn on_channel_buttons_channel(
+        bot: Bot,
+        dialogue: Dialogue<SupState, AppDialogue>,
+        q: CallbackQuery,
+        me: Me,
+        conn: Arc<Mutex<SqliteConnection>>,
+    ) -> TeloxideResult {
+        use super::helpers::buttons_message;
+
+        let channel = match picked_channel(&bot, &q, &me, &conn).await? {
+            Some(channel) => channel,
+            None => return Ok(()),
+        };
+        callback_reply(
+            format!(
+                "{}\n\nSend the buttons to show under each post, in order and separated by spaces:\n\
+                reddit (Open on Reddit)\n\
+                comments (Comments with their number)\n\
+                source (the linked page, only shown for link posts)\n\n\
+                Send \"none\" to remove the buttons.",
+                buttons_message(&channel)
+            ),
+            &bot,
+            &q,
+        )
+        .await?;
+        update_dialogue(
+            &dialogue,
+            SupState::Channel(State::ButtonsReceiveButtons(channel)),
+        )
+        .await
+    }
+
+    pub(super) async fn on_channel_buttons_buttons(
+        bot: Bot,
+        dialogue: Dialogue<SupState, AppDialogue>,
+        msg: Message,
+        conn: Arc<Mutex<SqliteConnection>>,
+        channel: Channel,
+    ) -> TeloxideResult {
+        use super::helpers::buttons_message;
+        use crate::db::models::PostButton;
+
+        if !can_manage_channel(&bot, &channel, msg.from()).await? {
+            msg_reply(not_admin_message(&channel), &bot, &msg).await?;
+            return update_dialogue(&dialogue, SupState::MainMenu).await;
+        }
+        let text = msg.text().unwrap_or_default().trim();
+        let buttons = if text.eq_ignore_ascii_case("none") {
+            Ok(Vec::new())
+        } else {
+            text.split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|button| !button.is_empty())
+                .map(str::parse::<PostButton>)
+                .collect::<Result<Vec<_>, _>>()
+        };
+        let mut buttons = match buttons {
+            Ok(buttons) => buttons,
+            Err(error) => return msg_reply(format!("{}. Try again.", error), &bot, &msg).await,
+        };
+        let mut seen = Vec::with_capacity(buttons.len());
+        buttons.retain(|button| {
+            let first = !seen.contains(button);
+            seen.push(*button);
+            first
+        });
+        let channel = channel.set_post_buttons(
+            &buttons,
+            msg.from().map(|user| user.id),
+            &mut conn.lock().unwrap(),
+        )?;
+        msg_reply(buttons_message(&channel), &bot, &msg).await?;
+        update_dialogue(&dialogue, SupState::MainMenu).await
+    }
+
+    pub(super) async fn on_channel_history(
+        bot: Bot,
+        dialogue: Dialogue<SupState, AppDialogue>,
+        msg: Message,
+        me: Me,
+        conn: Arc<Mutex<SqliteConnection>>,
+    ) -> TeloxideResult {
+        use super::helpers::{channel_keyboard, get_channels_where_admins};
+
+        let from_user = match msg.from() {
+            Some(user) => user,
+            None => return msg_reply("Couldn't recognize the user. Try again.", &bot, &msg).await,
+        };
+        let channels = get_channels_where_admins(&bot, conn, &from_user.id, &me.user.id).await?;
+        if channels.is_empty() {
+            return msg_reply(
+                "No channels found. Try adding a new channel first",
+                &bot,
+                &msg,
+            )
+            .await;
+        }
+        bot.send_message(
+            msg.chat.id,
+            "Okay. Choose the channel whose recent changes you want to see:",
+        )
+        .reply_to_message_id(msg.id)
+        .reply_markup(channel_keyboard(&channels, 0))
+        .await?;
+        update_dialogue(&dialogue, SupState::Channel(State::HistoryReceiveChannel)).await
+    }
+
+    pub(super) async fn on_channel_history_channel(
+        bot: Bot,
+        dialogue: Dialogue<SupState, AppDialogue>,
+        q: CallbackQuery,
+        me: Me,
+        conn: Arc<Mutex<SqliteConnection>>,
+    ) -> TeloxideResult {
+        use crate::db::models::AuditEntry;
+
+        let channel = match picked_channel(&bot, &q, &me, &conn).await? {
+            Some(channel) => channel,
+            None => return Ok(()),
+        };
+        let entries = AuditEntry::get_latest(&channel, HISTORY_LENGTH, &mut conn.lock().unwrap())?;
+        let history = if entries.is_empty() {
+            format!("No changes recorded for {}.", channel.title)
+        } else {
+            let mut history = format!("Recent changes to {}:", channel.title);
+            for (shown, entry) in entries.iter().enumerate() {
+                let entry = format!("\n\n{}", entry);
+                // Leave room for the line telling how many changes didn't fit.
+                let length = history.chars().count() + entry.chars().count();
+                if length + 40 > MESSAGE_LENGTH_LIMIT {
+                    history += format!("\n\n…and {} older changes", entries.len() - shown).as_str();
+                    break;
+                }
+                history += entry.as_str();
+            }
+            history
+        };
+        callback_reply(history, &bot, &q).await?;
+        update_dialogue(&dialogue, SupState::MainMenu).await
+    }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub enum State {
     LinkReceiveChannel,
     UnlinkReceiveChannel,
     UnlinkConfirm(Channel),
+    TemplateReceiveChannel,
+    TemplateReceiveTarget(Channel),
+    TemplateReceiveTemplate(Channel, Option<Subreddit>),
+    ScheduleReceiveChannel,
+    ScheduleReceiveSetting(Channel),
+    ButtonsReceiveChannel,
+    ButtonsReceiveButtons(Channel),
+    HistoryReceiveChannel,
 }
 
 pub fn schema() -> DispatcherSchema {
     use super::{Command, State as SupState};
     use teloxide::dptree::case;
     use teloxide::prelude::*;
-    Update::filter_message()
-        .branch(
-            case![SupState::MainMenu]
-                .filter_command::<Command>()
-                .branch(case![Command::ListChannels].endpoint(listeners::on_channel_list))
-                .branch(case![Command::LinkChannel].endpoint(listeners::on_channel_link))
-                .branch(case![Command::UnlinkChannel].endpoint(listeners::on_channel_unlink)),
-        )
+    dptree::entry()
         .branch(
-            case![SupState::Channel(x)]
-                .branch(case![State::LinkReceiveChannel].endpoint(listeners::on_channel_link_msg))
+            Update::filter_message()
                 .branch(
-                    case![State::UnlinkReceiveChannel].endpoint(listeners::on_channel_unlink_msg),
+                    case![SupState::MainMenu]
+                        .filter_command::<Command>()
+                        .branch(case![Command::ListChannels].endpoint(listeners::on_channel_list))
+                        .branch(
+                            case![Command::SkipStats].endpoint(listeners::on_channel_skip_stats),
+                        )
+                        .branch(case![Command::LinkChannel].endpoint(listeners::on_channel_link))
+                        .branch(
+                            case![Command::UnlinkChannel].endpoint(listeners::on_channel_unlink),
+                        )
+                        .branch(
+                            case![Command::SetTemplate].endpoint(listeners::on_channel_template),
+                        )
+                        .branch(case![Command::Schedule].endpoint(listeners::on_channel_schedule))
+                        .branch(case![Command::SetButtons].endpoint(listeners::on_channel_buttons))
+                        .branch(case![Command::History].endpoint(listeners::on_channel_history)),
                 )
                 .branch(
-                    case![State::UnlinkConfirm(selected_channel)]
-                        .endpoint(listeners::on_channel_unlink_confirm),
+                    case![SupState::Channel(x)]
+                        .branch(
+                            case![State::LinkReceiveChannel]
+                                .endpoint(listeners::on_channel_link_msg),
+                        )
+                        .branch(
+                            case![State::UnlinkConfirm(selected_channel)]
+                                .endpoint(listeners::on_channel_unlink_confirm),
+                        )
+                        .branch(
+                            case![State::TemplateReceiveTemplate(
+                                selected_channel,
+                                selected_subreddit
+                            )]
+                            .endpoint(listeners::on_channel_template_template),
+                        )
+                        .branch(
+                            case![State::ScheduleReceiveSetting(selected_channel)]
+                                .endpoint(listeners::on_channel_schedule_setting),
+                        )
+                        .branch(
+                            case![State::ButtonsReceiveButtons(selected_channel)]
+                                .endpoint(listeners::on_channel_buttons_buttons),
+                        ),
                 ),
         )
+        .branch(
+            Update::filter_callback_query().branch(
+                case![SupState::Channel(x)]
+                    .branch(
+                        case![State::UnlinkReceiveChannel]
+                            .endpoint(listeners::on_channel_unlink_channel),
+                    )
+                    .branch(
+                        case![State::TemplateReceiveChannel]
+                            .endpoint(listeners::on_channel_template_channel),
+                    )
+                    .branch(
+                        case![State::TemplateReceiveTarget(selected_channel)]
+                            .endpoint(listeners::on_channel_template_target),
+                    )
+                    .branch(
+                        case![State::ScheduleReceiveChannel]
+                            .endpoint(listeners::on_channel_schedule_channel),
+                    )
+                    .branch(
+                        case![State::ButtonsReceiveChannel]
+                            .endpoint(listeners::on_channel_buttons_channel),
+                    )
+                    .branch(
+                        case![State::HistoryReceiveChannel]
+                            .endpoint(listeners::on_channel_history_channel),
+                    ),
+            ),
+        )
 }
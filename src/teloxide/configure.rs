@@ -0,0 +1,697 @@
+use crate::db::models::{Channel, ChannelSubreddit, SortType, Subreddit};
+
+use super::DispatcherSchema;
+use diesel::SqliteConnection;
+use serde_derive::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use teloxide::prelude::*;
+
+/// Settings that are edited by typing a value instead of pressing a button.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub enum NumericField {
+    PostLimit,
+    MinScore,
+}
+
+impl NumericField {
+    fn label(&self) -> &'static str {
+        match self {
+            NumericField::PostLimit => "post limit",
+            NumericField::MinScore => "minimum score",
+        }
+    }
+}
+
+pub mod helpers {
+    use super::*;
+    use crate::db::models::{LinkSetting, SortPeriod};
+    use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup};
+
+    pub(crate) fn on_off(value: bool) -> &'static str {
+        if value {
+            "on"
+        } else {
+            "off"
+        }
+    }
+
+    pub(crate) fn optional_number(value: Option<i32>) -> String {
+        value
+            .map(|value| value.to_string())
+            .unwrap_or_else(|| "none".to_owned())
+    }
+
+    pub(crate) fn sorting_label(subreddit: &Subreddit) -> String {
+        if subreddit.sorting.uses_period() {
+            format!(
+                "{} {}",
+                subreddit.sorting.as_str(),
+                subreddit.sort_period.as_str()
+            )
+        } else {
+            subreddit.sorting.as_str().to_owned()
+        }
+    }
+
+    pub(crate) fn settings_message(link: &ChannelSubreddit, subreddit: &Subreddit) -> String {
+        format!(
+            "Settings of r/{} in this channel. Press a button to change a setting.\n\n\
+            Post limit: {}\n\
+            Minimum score: {}\n\
+            Allow NSFW: {}\n\
+            Show spoilers: {}\n\
+            Media only: {}\n\
+            Respect cross-post flags: {}\n\n\
+            Shared with every channel linked to r/{}:\n\
+            Sorting: {}\n\
+            Mirroring: {}",
+            subreddit.name,
+            optional_number(link.post_limit),
+            optional_number(link.min_score),
+            on_off(link.allow_nsfw),
+            on_off(link.show_spoilers),
+            on_off(link.medias_only),
+            on_off(link.respect_external_content_flag),
+            subreddit.name,
+            sorting_label(subreddit),
+            if subreddit.disabled {
+                "paused"
+            } else {
+                "active"
+            },
+        )
+    }
+
+    pub(crate) fn settings_keyboard(
+        link: &ChannelSubreddit,
+        subreddit: &Subreddit,
+    ) -> InlineKeyboardMarkup {
+        InlineKeyboardMarkup::new([
+            vec![
+                InlineKeyboardButton::callback("Post limit", "edit:post_limit"),
+                InlineKeyboardButton::callback("Minimum score", "edit:min_score"),
+            ],
+            vec![
+                InlineKeyboardButton::callback(
+                    format!("NSFW: {}", on_off(link.allow_nsfw)),
+                    "toggle:allow_nsfw",
+                ),
+                InlineKeyboardButton::callback(
+                    format!("Spoilers: {}", on_off(link.show_spoilers)),
+                    "toggle:show_spoilers",
+                ),
+            ],
+            vec![
+                InlineKeyboardButton::callback(
+                    format!("Media only: {}", on_off(link.medias_only)),
+                    "toggle:medias_only",
+                ),
+                InlineKeyboardButton::callback(
+                    format!(
+                        "Cross-post flags: {}",
+                        on_off(link.respect_external_content_flag)
+                    ),
+                    "toggle:respect_external_content_flag",
+                ),
+            ],
+            vec![InlineKeyboardButton::callback(
+                format!("Sorting: {}", sorting_label(subreddit)),
+                "sorting",
+            )],
+            vec![
+                InlineKeyboardButton::callback(
+                    if subreddit.disabled {
+                        "Resume mirroring"
+                    } else {
+                        "Pause mirroring"
+                    },
+                    "toggle:disabled",
+                ),
+                InlineKeyboardButton::callback("Done", "done"),
+            ],
+        ])
+    }
+
+    pub(crate) fn sorting_keyboard() -> InlineKeyboardMarkup {
+        InlineKeyboardMarkup::new([
+            SortType::ALL
+                .iter()
+                .map(|sorting| {
+                    InlineKeyboardButton::callback(
+                        sorting.as_str(),
+                        format!("sort:{}", sorting.as_str()),
+                    )
+                })
+                .collect::<Vec<_>>(),
+            vec![InlineKeyboardButton::callback("Back", "back")],
+        ])
+    }
+
+    pub(crate) fn period_keyboard() -> InlineKeyboardMarkup {
+        InlineKeyboardMarkup::new([
+            SortPeriod::ALL
+                .iter()
+                .map(|period| {
+                    InlineKeyboardButton::callback(
+                        period.as_str(),
+                        format!("period:{}", period.as_str()),
+                    )
+                })
+                .collect::<Vec<_>>(),
+            vec![InlineKeyboardButton::callback("Back", "back")],
+        ])
+    }
+
+    /// Builds the link setting a `toggle:<field>` button stands for, flipping its current
+    /// value.
+    pub(crate) fn toggled_setting(link: &ChannelSubreddit, field: &str) -> Option<LinkSetting> {
+        Some(match field {
+            "allow_nsfw" => LinkSetting::AllowNsfw(!link.allow_nsfw),
+            "show_spoilers" => LinkSetting::ShowSpoilers(!link.show_spoilers),
+            "medias_only" => LinkSetting::MediasOnly(!link.medias_only),
+            "respect_external_content_flag" => {
+                LinkSetting::RespectExternalContentFlag(!link.respect_external_content_flag)
+            }
+            _ => return None,
+        })
+    }
+
+    /// Shows the settings menu in place of the message the pressed button belonged to.
+    pub(crate) async fn show_settings(
+        bot: &Bot,
+        q: &CallbackQuery,
+        link: &ChannelSubreddit,
+        subreddit: &Subreddit,
+    ) -> Result<(), teloxide::RequestError> {
+        if let Some(message) = &q.message {
+            bot.edit_message_text(
+                message.chat.id,
+                message.id,
+                settings_message(link, subreddit),
+            )
+            .reply_markup(settings_keyboard(link, subreddit))
+            .await?;
+        }
+        Ok(())
+    }
+}
+
+mod listeners {
+    use super::*;
+    use crate::{
+        db::models::{LinkSetting, SortPeriod, SubredditSetting},
+        teloxide::{
+            callback_menu, callback_reply,
+            channel::helpers::{
+                can_manage_channel, can_manage_subreddit, not_admin_message, picked_channel,
+                shared_subreddit_message,
+            },
+            msg_reply,
+            subreddit::helpers::{picked_subreddit, subreddit_keyboard},
+            update_dialogue, AppDialogue, State as SupState, TeloxideResult,
+        },
+    };
+    use teloxide::types::Me;
+
+    pub(super) async fn on_configure(
+        bot: Bot,
+        dialogue: Dialogue<SupState, AppDialogue>,
+        msg: Message,
+        me: Me,
+        conn: Arc<Mutex<SqliteConnection>>,
+    ) -> TeloxideResult {
+        use crate::teloxide::channel::helpers::{channel_keyboard, get_channels_where_admins};
+
+        let from_user = match msg.from() {
+            Some(user) => user,
+            None => return msg_reply("Couldn't recognize the user. Try again.", &bot, &msg).await,
+        };
+        let channels = get_channels_where_admins(&bot, conn, &from_user.id, &me.user.id).await?;
+        if channels.is_empty() {
+            return msg_reply(
+                "No channels found. Try adding a new channel first",
+                &bot,
+                &msg,
+            )
+            .await;
+        }
+        bot.send_message(
+            msg.chat.id,
+            "Okay. Choose the channel whose subreddits you want to configure:",
+        )
+        .reply_to_message_id(msg.id)
+        .reply_markup(channel_keyboard(&channels, 0))
+        .await?;
+        update_dialogue(&dialogue, SupState::Configure(State::ReceiveChannel)).await
+    }
+
+    pub(super) async fn on_configure_channel(
+        bot: Bot,
+        dialogue: Dialogue<SupState, AppDialogue>,
+        q: CallbackQuery,
+        me: Me,
+        conn: Arc<Mutex<SqliteConnection>>,
+    ) -> TeloxideResult {
+        let channel = match picked_channel(&bot, &q, &me, &conn).await? {
+            Some(channel) => channel,
+            None => return Ok(()),
+        };
+        let subreddits = Subreddit::get_by_channel(channel.clone(), &mut conn.lock().unwrap())?;
+        if subreddits.is_empty() {
+            callback_reply("This channel has no linked subreddits.", &bot, &q).await?;
+            return update_dialogue(&dialogue, SupState::MainMenu).await;
+        }
+        callback_menu(
+            "Choose the subreddit to configure:",
+            subreddit_keyboard(&subreddits, 0),
+            &bot,
+            &q,
+        )
+        .await?;
+        update_dialogue(&dialogue, SupState::Configure(State::ReceiveSub(channel))).await
+    }
+
+    pub(super) async fn on_configure_sub(
+        bot: Bot,
+        dialogue: Dialogue<SupState, AppDialogue>,
+        q: CallbackQuery,
+        conn: Arc<Mutex<SqliteConnection>>,
+        channel: Channel,
+    ) -> TeloxideResult {
+        use super::helpers::show_settings;
+
+        let subreddit =
+            match picked_subreddit(&bot, &q, &channel, &conn, subreddit_keyboard).await? {
+                Some(subreddit) => subreddit,
+                None => return Ok(()),
+            };
+        let link = ChannelSubreddit::get(&channel, &subreddit, &mut conn.lock().unwrap())?;
+        show_settings(&bot, &q, &link, &subreddit).await?;
+        update_dialogue(
+            &dialogue,
+            SupState::Configure(State::Menu(channel, subreddit)),
+        )
+        .await
+    }
+
+    pub(super) async fn on_configure_menu(
+        bot: Bot,
+        dialogue: Dialogue<SupState, AppDialogue>,
+        q: CallbackQuery,
+        conn: Arc<Mutex<SqliteConnection>>,
+        (channel, subreddit): (Channel, Subreddit),
+    ) -> TeloxideResult {
+        use super::helpers::{show_settings, sorting_keyboard, toggled_setting};
+
+        if !can_manage_channel(&bot, &channel, Some(&q.from)).await? {
+            bot.answer_callback_query(q.id.clone())
+                .text(not_admin_message(&channel))
+                .await?;
+            return Ok(());
+        }
+        let data = q.data.clone().unwrap_or_default();
+        let (action, field) = data.split_once(':').unwrap_or((data.as_str(), ""));
+        match action {
+            "toggle" => {
+                if field == "disabled"
+                    && !can_manage_subreddit(&bot, &subreddit, Some(&q.from), &conn).await?
+                {
+                    bot.answer_callback_query(q.id.clone())
+                        .text(shared_subreddit_message(&subreddit))
+                        .await?;
+                    return Ok(());
+                }
+                // Toggle the current values, the ones shown may be outdated by now.
+                let updated = {
+                    let conn = &mut conn.lock().unwrap();
+                    let subreddit = Subreddit::get_by_id(subreddit.id, conn)?;
+                    let link = ChannelSubreddit::get(&channel, &subreddit, conn)?;
+                    if field == "disabled" {
+                        Some(
+                            subreddit
+                                .update(
+                                    SubredditSetting::Disabled(!subreddit.disabled),
+                                    Some(q.from.id),
+                                    conn,
+                                )
+                                .map(|subreddit| (link, subreddit)),
+                        )
+                    } else {
+                        toggled_setting(&link, field).map(|setting| {
+                            link.update(setting, Some(q.from.id), conn)
+                                .map(|link| (link, subreddit))
+                        })
+                    }
+                };
+                let (link, subreddit) = match updated {
+                    Some(Ok(updated)) => updated,
+                    Some(Err(error)) => {
+                        bot.answer_callback_query(q.id)
+                            .text(error.to_string())
+                            .await?;
+                        return Ok(());
+                    }
+                    None => {
+                        bot.answer_callback_query(q.id).await?;
+                        return Ok(());
+                    }
+                };
+                bot.answer_callback_query(q.id.clone()).await?;
+                show_settings(&bot, &q, &link, &subreddit).await?;
+                update_dialogue(
+                    &dialogue,
+                    SupState::Configure(State::Menu(channel, subreddit)),
+                )
+                .await
+            }
+            "edit" => {
+                let field = match field {
+                    "post_limit" => NumericField::PostLimit,
+                    "min_score" => NumericField::MinScore,
+                    _ => {
+                        bot.answer_callback_query(q.id).await?;
+                        return Ok(());
+                    }
+                };
+                bot.answer_callback_query(q.id).await?;
+                bot.send_message(
+                    dialogue.chat_id(),
+                    format!(
+                        "Send the new {} for r/{} in this channel, or \"none\" to remove it.",
+                        field.label(),
+                        subreddit.name
+                    ),
+                )
+                .await?;
+                update_dialogue(
+                    &dialogue,
+                    SupState::Configure(State::ReceiveValue(channel, subreddit, field)),
+                )
+                .await
+            }
+            "sorting" => {
+                if !can_manage_subreddit(&bot, &subreddit, Some(&q.from), &conn).await? {
+                    bot.answer_callback_query(q.id.clone())
+                        .text(shared_subreddit_message(&subreddit))
+                        .await?;
+                    return Ok(());
+                }
+                bot.answer_callback_query(q.id.clone()).await?;
+                if let Some(message) = &q.message {
+                    bot.edit_message_text(
+                        message.chat.id,
+                        message.id,
+                        format!(
+                            "Choose how r/{} should be sorted. This applies to every channel \
+                            linked to it:",
+                            subreddit.name
+                        ),
+                    )
+                    .reply_markup(sorting_keyboard())
+                    .await?;
+                }
+                update_dialogue(
+                    &dialogue,
+                    SupState::Configure(State::Sorting(channel, subreddit)),
+                )
+                .await
+            }
+            "done" => {
+                bot.answer_callback_query(q.id.clone()).await?;
+                if let Some(message) = &q.message {
+                    bot.edit_message_reply_markup(message.chat.id, message.id)
+                        .await?;
+                }
+                update_dialogue(&dialogue, SupState::MainMenu).await
+            }
+            _ => {
+                bot.answer_callback_query(q.id).await?;
+                Ok(())
+            }
+        }
+    }
+
+    pub(super) async fn on_configure_sorting(
+        bot: Bot,
+        dialogue: Dialogue<SupState, AppDialogue>,
+        q: CallbackQuery,
+        conn: Arc<Mutex<SqliteConnection>>,
+        (channel, subreddit): (Channel, Subreddit),
+    ) -> TeloxideResult {
+        use super::helpers::{period_keyboard, show_settings};
+
+        if !can_manage_channel(&bot, &channel, Some(&q.from)).await? {
+            bot.answer_callback_query(q.id.clone())
+                .text(not_admin_message(&channel))
+                .await?;
+            return Ok(());
+        }
+        if !can_manage_subreddit(&bot, &subreddit, Some(&q.from), &conn).await? {
+            bot.answer_callback_query(q.id.clone())
+                .text(shared_subreddit_message(&subreddit))
+                .await?;
+            return Ok(());
+        }
+        bot.answer_callback_query(q.id.clone()).await?;
+        let data = q.data.clone().unwrap_or_default();
+        if data == "back" {
+            let link = ChannelSubreddit::get(&channel, &subreddit, &mut conn.lock().unwrap())?;
+            show_settings(&bot, &q, &link, &subreddit).await?;
+            return update_dialogue(
+                &dialogue,
+                SupState::Configure(State::Menu(channel, subreddit)),
+            )
+            .await;
+        }
+        let sorting = match data.strip_prefix("sort:").map(str::parse::<SortType>) {
+            Some(Ok(sorting)) => sorting,
+            _ => return Ok(()),
+        };
+        if sorting.uses_period() {
+            if let Some(message) = &q.message {
+                bot.edit_message_text(
+                    message.chat.id,
+                    message.id,
+                    format!(
+                        "Choose the time range of the {} posts of r/{}:",
+                        sorting.as_str(),
+                        subreddit.name
+                    ),
+                )
+                .reply_markup(period_keyboard())
+                .await?;
+            }
+            return update_dialogue(
+                &dialogue,
+                SupState::Configure(State::SortPeriod(channel, subreddit, sorting)),
+            )
+            .await;
+        }
+        let (link, subreddit) = {
+            let conn = &mut conn.lock().unwrap();
+            let subreddit = subreddit.update(
+                SubredditSetting::Sorting(sorting, subreddit.sort_period),
+                Some(q.from.id),
+                conn,
+            )?;
+            (
+                ChannelSubreddit::get(&channel, &subreddit, conn)?,
+                subreddit,
+            )
+        };
+        show_settings(&bot, &q, &link, &subreddit).await?;
+        update_dialogue(
+            &dialogue,
+            SupState::Configure(State::Menu(channel, subreddit)),
+        )
+        .await
+    }
+
+    pub(super) async fn on_configure_period(
+        bot: Bot,
+        dialogue: Dialogue<SupState, AppDialogue>,
+        q: CallbackQuery,
+        conn: Arc<Mutex<SqliteConnection>>,
+        (channel, subreddit, sorting): (Channel, Subreddit, SortType),
+    ) -> TeloxideResult {
+        use super::helpers::{show_settings, sorting_keyboard};
+
+        if !can_manage_channel(&bot, &channel, Some(&q.from)).await? {
+            bot.answer_callback_query(q.id.clone())
+                .text(not_admin_message(&channel))
+                .await?;
+            return Ok(());
+        }
+        if !can_manage_subreddit(&bot, &subreddit, Some(&q.from), &conn).await? {
+            bot.answer_callback_query(q.id.clone())
+                .text(shared_subreddit_message(&subreddit))
+                .await?;
+            return Ok(());
+        }
+        bot.answer_callback_query(q.id.clone()).await?;
+        let data = q.data.clone().unwrap_or_default();
+        if data == "back" {
+            if let Some(message) = &q.message {
+                bot.edit_message_text(
+                    message.chat.id,
+                    message.id,
+                    format!(
+                        "Choose how r/{} should be sorted. This applies to every channel \
+                        linked to it:",
+                        subreddit.name
+                    ),
+                )
+                .reply_markup(sorting_keyboard())
+                .await?;
+            }
+            return update_dialogue(
+                &dialogue,
+                SupState::Configure(State::Sorting(channel, subreddit)),
+            )
+            .await;
+        }
+        let sort_period = match data.strip_prefix("period:").map(str::parse::<SortPeriod>) {
+            Some(Ok(sort_period)) => sort_period,
+            _ => return Ok(()),
+        };
+        let (link, subreddit) = {
+            let conn = &mut conn.lock().unwrap();
+            let subreddit = subreddit.update(
+                SubredditSetting::Sorting(sorting, sort_period),
+                Some(q.from.id),
+                conn,
+            )?;
+            (
+                ChannelSubreddit::get(&channel, &subreddit, conn)?,
+                subreddit,
+            )
+        };
+        show_settings(&bot, &q, &link, &subreddit).await?;
+        update_dialogue(
+            &dialogue,
+            SupState::Configure(State::Menu(channel, subreddit)),
+        )
+        .await
+    }
+
+    pub(super) async fn on_configure_value(
+        bot: Bot,
+        dialogue: Dialogue<SupState, AppDialogue>,
+        msg: Message,
+        conn: Arc<Mutex<SqliteConnection>>,
+        (channel, subreddit, field): (Channel, Subreddit, NumericField),
+    ) -> TeloxideResult {
+        use super::helpers::{settings_keyboard, settings_message};
+        use crate::db::models::SettingError;
+
+        if !can_manage_channel(&bot, &channel, msg.from()).await? {
+            msg_reply(not_admin_message(&channel), &bot, &msg).await?;
+            return update_dialogue(&dialogue, SupState::MainMenu).await;
+        }
+        let text = msg.text().unwrap_or_default().trim();
+        let value = if text.eq_ignore_ascii_case("none") {
+            None
+        } else {
+            match text.parse::<i32>() {
+                Ok(value) => Some(value),
+                Err(_) => {
+                    return msg_reply(
+                        format!(
+                            "Please send a whole number or \"none\" as the {}.",
+                            field.label()
+                        ),
+                        &bot,
+                        &msg,
+                    )
+                    .await
+                }
+            }
+        };
+        let setting = match field {
+            NumericField::PostLimit => LinkSetting::PostLimit(value),
+            NumericField::MinScore => LinkSetting::MinScore(value),
+        };
+        let updated = {
+            let conn = &mut conn.lock().unwrap();
+            ChannelSubreddit::get(&channel, &subreddit, conn)
+                .map_err(SettingError::from)
+                .and_then(|link| link.update(setting, msg.from().map(|user| user.id), conn))
+        };
+        let link = match updated {
+            Ok(link) => link,
+            Err(SettingError::Invalid(reason)) => {
+                return msg_reply(format!("{}. Try again.", reason), &bot, &msg).await
+            }
+            Err(error) => return Err(error.into()),
+        };
+        bot.send_message(msg.chat.id, settings_message(&link, &subreddit))
+            .reply_to_message_id(msg.id)
+            .reply_markup(settings_keyboard(&link, &subreddit))
+            .await?;
+        update_dialogue(
+            &dialogue,
+            SupState::Configure(State::Menu(channel, subreddit)),
+        )
+        .await
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub enum State {
+    ReceiveChannel,
+    ReceiveSub(Channel),
+    Menu(Channel, Subreddit),
+    ReceiveValue(Channel, Subreddit, NumericField),
+    Sorting(Channel, Subreddit),
+    SortPeriod(Channel, Subreddit, SortType),
+}
+
+pub fn schema() -> DispatcherSchema {
+    use super::{Command, State as SupState};
+    use teloxide::dptree::case;
+    dptree::entry()
+        .branch(
+            Update::filter_message()
+                .branch(
+                    case![SupState::MainMenu]
+                        .filter_command::<Command>()
+                        .branch(case![Command::Configure].endpoint(listeners::on_configure)),
+                )
+                .branch(
+                    case![SupState::Configure(x)].branch(
+                        case![State::ReceiveValue(
+                            selected_channel,
+                            selected_subreddit,
+                            field
+                        )]
+                        .endpoint(listeners::on_configure_value),
+                    ),
+                ),
+        )
+        .branch(
+            Update::filter_callback_query().branch(
+                case![SupState::Configure(x)]
+                    .branch(case![State::ReceiveChannel].endpoint(listeners::on_configure_channel))
+                    .branch(
+                        case![State::ReceiveSub(selected_channel)]
+                            .endpoint(listeners::on_configure_sub),
+                    )
+                    .branch(
+                        case![State::Menu(selected_channel, selected_subreddit)]
+                            .endpoint(listeners::on_configure_menu),
+                    )
+                    .branch(
+                        case![State::Sorting(selected_channel, selected_subreddit)]
+                            .endpoint(listeners::on_configure_sorting),
+                    )
+                    .branch(
+                        case![State::SortPeriod(
+                            selected_channel,
+                            selected_subreddit,
+                            sorting
+                        )]
+                        .endpoint(listeners::on_configure_period),
+                    ),
+            ),
+        )
+}
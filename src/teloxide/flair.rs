@@ -0,0 +1,355 @@
+use crate::db::models::{Channel, Subreddit};
+
+use super::DispatcherSchema;
+use diesel::SqliteConnection;
+use serde_derive::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use teloxide::prelude::*;
+
+/// Most seen flairs offered as buttons.
+const SEEN_FLAIR_LIMIT: i64 = 20;
+
+pub mod helpers {
+    use super::*;
+    use crate::db::models::{ChannelSubreddit, FlairList, FlairRule, SeenFlair};
+    use std::error::Error;
+    use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup};
+
+    fn list_label(list: Option<FlairList>) -> &'static str {
+        match list {
+            Some(FlairList::Allow) => "allowed",
+            Some(FlairList::Block) => "blocked",
+            None => "-",
+        }
+    }
+
+    /// The list a flair is on, if any.
+    pub(crate) fn list_of(rules: &[FlairRule], flair_text: &str) -> Option<FlairList> {
+        rules
+            .iter()
+            .find(|rule| rule.flair_text == flair_text)
+            .map(|rule| rule.list)
+    }
+
+    /// The list a flair moves to when its button is pressed: neither, allowed, blocked.
+    pub(crate) fn next_list(list: Option<FlairList>) -> Option<FlairList> {
+        match list {
+            None => Some(FlairList::Allow),
+            Some(FlairList::Allow) => Some(FlairList::Block),
+            Some(FlairList::Block) => None,
+        }
+    }
+
+    pub(crate) fn flairs_message(subreddit: &Subreddit, rules: &[FlairRule]) -> String {
+        let list = |list: FlairList| {
+            let flairs = rules
+                .iter()
+                .filter(|rule| rule.list == list)
+                .map(|rule| rule.flair_text.as_str())
+                .collect::<Vec<_>>();
+            if flairs.is_empty() {
+                "none".to_owned()
+            } else {
+                flairs.join(", ")
+            }
+        };
+        format!(
+            "Flair filter of r/{}.\n\n\
+            Allowlist: {}\n\
+            Blocklist: {}\n\n\
+            Press a recently seen flair to move it between the allowlist, the blocklist and \
+            neither. For other flairs, send \"allow <flair>\", \"block <flair>\" or \
+            \"remove <flair>\". While the allowlist isn't empty, posts without a flair are \
+            skipped too.",
+            subreddit.name,
+            list(FlairList::Allow),
+            list(FlairList::Block)
+        )
+    }
+
+    pub(crate) fn flairs_keyboard(seen: &[SeenFlair], rules: &[FlairRule]) -> InlineKeyboardMarkup {
+        InlineKeyboardMarkup::new(
+            seen.iter()
+                .map(|flair| {
+                    vec![InlineKeyboardButton::callback(
+                        format!(
+                            "{} ({})",
+                            flair.text,
+                            list_label(list_of(rules, &flair.text))
+                        ),
+                        format!("flair:{}", flair.id),
+                    )]
+                })
+                .chain([vec![InlineKeyboardButton::callback("Done", "done")]]),
+        )
+    }
+
+    /// The message and keyboard of the flair menu of a link.
+    pub(crate) fn flairs_menu(
+        channel: &Channel,
+        subreddit: &Subreddit,
+        conn: &mut SqliteConnection,
+    ) -> Result<(String, InlineKeyboardMarkup), Box<dyn Error + Send + Sync + 'static>> {
+        let link = ChannelSubreddit::get(channel, subreddit, conn)?;
+        let rules = FlairRule::get_by_link(&link, conn)?;
+        let seen = SeenFlair::get_latest(subreddit, SEEN_FLAIR_LIMIT, conn)?;
+        Ok((
+            flairs_message(subreddit, &rules),
+            flairs_keyboard(&seen, &rules),
+        ))
+    }
+}
+
+mod listeners {
+    use super::*;
+    use crate::{
+        db::models::{ChannelSubreddit, FlairList, FlairRule, SeenFlair},
+        teloxide::{
+            callback_menu, callback_reply,
+            channel::helpers::{can_manage_channel, not_admin_message, picked_channel},
+            msg_reply,
+            subreddit::helpers::{picked_subreddit, subreddit_keyboard},
+            update_dialogue, AppDialogue, State as SupState, TeloxideResult,
+        },
+    };
+    use teloxide::types::Me;
+
+    pub(super) async fn on_flairs(
+        bot: Bot,
+        dialogue: Dialogue<SupState, AppDialogue>,
+        msg: Message,
+        me: Me,
+        conn: Arc<Mutex<SqliteConnection>>,
+    ) -> TeloxideResult {
+        use crate::teloxide::channel::helpers::{channel_keyboard, get_channels_where_admins};
+
+        let from_user = match msg.from() {
+            Some(user) => user,
+            None => return msg_reply("Couldn't recognize the user. Try again.", &bot, &msg).await,
+        };
+        let channels = get_channels_where_admins(&bot, conn, &from_user.id, &me.user.id).await?;
+        if channels.is_empty() {
+            return msg_reply(
+                "No channels found. Try adding a new channel first",
+                &bot,
+                &msg,
+            )
+            .await;
+        }
+        bot.send_message(
+            msg.chat.id,
+            "Okay. Choose the channel whose flair filters you want to change:",
+        )
+        .reply_to_message_id(msg.id)
+        .reply_markup(channel_keyboard(&channels, 0))
+        .await?;
+        update_dialogue(&dialogue, SupState::Flair(State::ReceiveChannel)).await
+    }
+
+    pub(super) async fn on_flairs_channel(
+        bot: Bot,
+        dialogue: Dialogue<SupState, AppDialogue>,
+        q: CallbackQuery,
+        me: Me,
+        conn: Arc<Mutex<SqliteConnection>>,
+    ) -> TeloxideResult {
+        let channel = match picked_channel(&bot, &q, &me, &conn).await? {
+            Some(channel) => channel,
+            None => return Ok(()),
+        };
+        let subreddits = Subreddit::get_by_channel(channel.clone(), &mut conn.lock().unwrap())?;
+        if subreddits.is_empty() {
+            callback_reply("This channel has no linked subreddits.", &bot, &q).await?;
+            return update_dialogue(&dialogue, SupState::MainMenu).await;
+        }
+        callback_menu(
+            "Choose the subreddit:",
+            subreddit_keyboard(&subreddits, 0),
+            &bot,
+            &q,
+        )
+        .await?;
+        update_dialogue(&dialogue, SupState::Flair(State::ReceiveSub(channel))).await
+    }
+
+    pub(super) async fn on_flairs_sub(
+        bot: Bot,
+        dialogue: Dialogue<SupState, AppDialogue>,
+        q: CallbackQuery,
+        conn: Arc<Mutex<SqliteConnection>>,
+        channel: Channel,
+    ) -> TeloxideResult {
+        use super::helpers::flairs_menu;
+
+        let subreddit =
+            match picked_subreddit(&bot, &q, &channel, &conn, subreddit_keyboard).await? {
+                Some(subreddit) => subreddit,
+                None => return Ok(()),
+            };
+        let (text, keyboard) = flairs_menu(&channel, &subreddit, &mut conn.lock().unwrap())?;
+        callback_menu(text, keyboard, &bot, &q).await?;
+        update_dialogue(&dialogue, SupState::Flair(State::Menu(channel, subreddit))).await
+    }
+
+    pub(super) async fn on_flairs_menu(
+        bot: Bot,
+        dialogue: Dialogue<SupState, AppDialogue>,
+        q: CallbackQuery,
+        conn: Arc<Mutex<SqliteConnection>>,
+        (channel, subreddit): (Channel, Subreddit),
+    ) -> TeloxideResult {
+        use super::helpers::{flairs_menu, list_of, next_list};
+
+        if !can_manage_channel(&bot, &channel, Some(&q.from)).await? {
+            bot.answer_callback_query(q.id.clone())
+                .text(not_admin_message(&channel))
+                .await?;
+            return Ok(());
+        }
+        let data = q.data.clone().unwrap_or_default();
+        if data == "done" {
+            bot.answer_callback_query(q.id.clone()).await?;
+            if let Some(message) = &q.message {
+                bot.edit_message_reply_markup(message.chat.id, message.id)
+                    .await?;
+            }
+            return update_dialogue(&dialogue, SupState::MainMenu).await;
+        }
+        let seen_id = match data
+            .strip_prefix("flair:")
+            .and_then(|id| id.parse::<i32>().ok())
+        {
+            Some(seen_id) => seen_id,
+            None => return Ok(()),
+        };
+        let (flair, list) = {
+            let conn = &mut conn.lock().unwrap();
+            let flair = SeenFlair::get_by_id(seen_id, conn)?;
+            let link = ChannelSubreddit::get(&channel, &subreddit, conn)?;
+            let list = next_list(list_of(&FlairRule::get_by_link(&link, conn)?, &flair.text));
+            FlairRule::set_list(
+                &link,
+                &flair.text,
+                flair.template_id.as_deref(),
+                list,
+                Some(q.from.id),
+                conn,
+            )?;
+            (flair, list)
+        };
+        bot.answer_callback_query(q.id.clone())
+            .text(match list {
+                Some(FlairList::Allow) => format!("Allowed \"{}\"", flair.text),
+                Some(FlairList::Block) => format!("Blocked \"{}\"", flair.text),
+                None => format!("Removed \"{}\" from the lists", flair.text),
+            })
+            .await?;
+        let (text, keyboard) = flairs_menu(&channel, &subreddit, &mut conn.lock().unwrap())?;
+        if let Some(message) = &q.message {
+            bot.edit_message_text(message.chat.id, message.id, text)
+                .reply_markup(keyboard)
+                .await?;
+        }
+        Ok(())
+    }
+
+    pub(super) async fn on_flairs_text(
+        bot: Bot,
+        dialogue: Dialogue<SupState, AppDialogue>,
+        msg: Message,
+        conn: Arc<Mutex<SqliteConnection>>,
+        (channel, subreddit): (Channel, Subreddit),
+    ) -> TeloxideResult {
+        use super::helpers::flairs_menu;
+
+        if !can_manage_channel(&bot, &channel, msg.from()).await? {
+            msg_reply(not_admin_message(&channel), &bot, &msg).await?;
+            return update_dialogue(&dialogue, SupState::MainMenu).await;
+        }
+        let text = msg.text().unwrap_or_default().trim();
+        let (command, flair) = text.split_once(' ').unwrap_or((text, ""));
+        let flair = flair.trim();
+        let list = match command.to_lowercase().as_str() {
+            "remove" => None,
+            list => match list.parse::<FlairList>() {
+                Ok(list) => Some(list),
+                Err(_) => {
+                    return msg_reply(
+                        "Please send \"allow <flair>\", \"block <flair>\" or \"remove <flair>\", or press a button.",
+                        &bot,
+                        &msg,
+                    )
+                    .await
+                }
+            },
+        };
+        if flair.is_empty() {
+            return msg_reply("Please add the flair. Try again.", &bot, &msg).await;
+        }
+        let menu = {
+            let conn = &mut conn.lock().unwrap();
+            let link = ChannelSubreddit::get(&channel, &subreddit, conn)?;
+            // Flairs typed by hand still get the template id if the flair was seen before.
+            let template_id = SeenFlair::get_by_subreddit(&subreddit, conn)?
+                .into_iter()
+                .find(|seen| seen.text.eq_ignore_ascii_case(flair))
+                .and_then(|seen| seen.template_id);
+            FlairRule::set_list(
+                &link,
+                flair,
+                template_id.as_deref(),
+                list,
+                msg.from().map(|user| user.id),
+                conn,
+            )?;
+            flairs_menu(&channel, &subreddit, conn)?
+        };
+        let (text, keyboard) = menu;
+        bot.send_message(msg.chat.id, text)
+            .reply_to_message_id(msg.id)
+            .reply_markup(keyboard)
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub enum State {
+    ReceiveChannel,
+    ReceiveSub(Channel),
+    Menu(Channel, Subreddit),
+}
+
+pub fn schema() -> DispatcherSchema {
+    use super::{Command, State as SupState};
+    use teloxide::dptree::case;
+    dptree::entry()
+        .branch(
+            Update::filter_message()
+                .branch(
+                    case![SupState::MainMenu]
+                        .filter_command::<Command>()
+                        .branch(case![Command::Flairs].endpoint(listeners::on_flairs)),
+                )
+                .branch(
+                    case![SupState::Flair(x)].branch(
+                        case![State::Menu(selected_channel, selected_subreddit)]
+                            .endpoint(listeners::on_flairs_text),
+                    ),
+                ),
+        )
+        .branch(
+            Update::filter_callback_query().branch(
+                case![SupState::Flair(x)]
+                    .branch(case![State::ReceiveChannel].endpoint(listeners::on_flairs_channel))
+                    .branch(
+                        case![State::ReceiveSub(selected_channel)]
+                            .endpoint(listeners::on_flairs_sub),
+                    )
+                    .branch(
+                        case![State::Menu(selected_channel, selected_subreddit)]
+                            .endpoint(listeners::on_flairs_menu),
+                    ),
+            ),
+        )
+}
@@ -0,0 +1,254 @@
+use crate::db::models::Channel;
+
+use super::DispatcherSchema;
+use diesel::SqliteConnection;
+use serde_derive::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use teloxide::prelude::*;
+
+/// Subreddits shown on each page of `/listsubreddits`.
+const PAGE_SIZE: usize = 5;
+
+pub mod helpers {
+    use super::*;
+    use crate::{
+        db::models::{ChannelSubreddit, FlairList, FlairRule, KeywordRule, Subreddit},
+        teloxide::configure::helpers::{on_off, optional_number, sorting_label},
+    };
+    use std::error::Error;
+    use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup};
+
+    fn link_summary(
+        link: &ChannelSubreddit,
+        subreddit: &Subreddit,
+        keyword_rules: usize,
+        flair_rules: &[FlairRule],
+    ) -> String {
+        let flair_count =
+            |list: FlairList| flair_rules.iter().filter(|rule| rule.list == list).count();
+        format!(
+            "r/{}{}\n\
+            Sorting: {}\n\
+            Post limit: {}, daily limit: {} ({})\n\
+            Comments: {}\n\
+            Minimum score: {}, NSFW: {}, spoilers: {}, media only: {}, cross-post flags: {}\n\
+            Keyword rules: {}, allowed flairs: {}, blocked flairs: {}\n\
+            Last fetched: {}",
+            subreddit.name,
+            if subreddit.disabled { " (paused)" } else { "" },
+            sorting_label(subreddit),
+            optional_number(link.post_limit),
+            optional_number(link.daily_limit),
+            link.quota_policy.as_str(),
+            if link.comments_enabled {
+                format!("up to {}", link.comment_limit)
+            } else {
+                "off".to_owned()
+            },
+            optional_number(link.min_score),
+            on_off(link.allow_nsfw),
+            on_off(link.show_spoilers),
+            on_off(link.medias_only),
+            on_off(link.respect_external_content_flag),
+            keyword_rules,
+            flair_count(FlairList::Allow),
+            flair_count(FlairList::Block),
+            subreddit
+                .last_fetched_at
+                .map(|fetched_at| fetched_at.format("%Y-%m-%d %H:%M UTC").to_string())
+                .unwrap_or_else(|| "never".to_owned()),
+        )
+    }
+
+    fn page_keyboard(page: usize, pages: usize) -> InlineKeyboardMarkup {
+        let mut navigation = Vec::new();
+        if page > 0 {
+            navigation.push(InlineKeyboardButton::callback(
+                "Previous",
+                format!("page:{}", page - 1),
+            ));
+        }
+        if page + 1 < pages {
+            navigation.push(InlineKeyboardButton::callback(
+                "Next",
+                format!("page:{}", page + 1),
+            ));
+        }
+        InlineKeyboardMarkup::new([
+            navigation,
+            vec![InlineKeyboardButton::callback("Done", "done")],
+        ])
+    }
+
+    /// The message and keyboard of a page of the subreddits linked to the channel, or `None`
+    /// when it has none.
+    pub(crate) fn listing_page(
+        channel: &Channel,
+        page: usize,
+        conn: &mut SqliteConnection,
+    ) -> Result<Option<(String, InlineKeyboardMarkup)>, Box<dyn Error + Send + Sync + 'static>>
+    {
+        let mut subreddits = Subreddit::get_by_channel(channel.clone(), conn)?;
+        if subreddits.is_empty() {
+            return Ok(None);
+        }
+        subreddits.sort_by_key(|subreddit| subreddit.name.to_lowercase());
+        let pages = subreddits.len().div_ceil(PAGE_SIZE);
+        let page = page.min(pages - 1);
+        let mut summaries = Vec::with_capacity(PAGE_SIZE);
+        for subreddit in subreddits.iter().skip(page * PAGE_SIZE).take(PAGE_SIZE) {
+            let link = ChannelSubreddit::get(channel, subreddit, conn)?;
+            let keyword_rules = KeywordRule::get_by_link(&link, conn)?.len();
+            let flair_rules = FlairRule::get_by_link(&link, conn)?;
+            summaries.push(link_summary(&link, subreddit, keyword_rules, &flair_rules));
+        }
+        Ok(Some((
+            format!(
+                "Subreddits linked to {} (page {} of {}):\n\n{}",
+                channel.title,
+                page + 1,
+                pages,
+                summaries.join("\n\n")
+            ),
+            page_keyboard(page, pages),
+        )))
+    }
+}
+
+mod listeners {
+    use super::*;
+    use crate::teloxide::{
+        callback_menu, callback_reply,
+        channel::helpers::{can_manage_channel, not_admin_message, picked_channel},
+        msg_reply, update_dialogue, AppDialogue, State as SupState, TeloxideResult,
+    };
+    use teloxide::types::Me;
+
+    pub(super) async fn on_listing(
+        bot: Bot,
+        dialogue: Dialogue<SupState, AppDialogue>,
+        msg: Message,
+        me: Me,
+        conn: Arc<Mutex<SqliteConnection>>,
+    ) -> TeloxideResult {
+        use crate::teloxide::channel::helpers::{channel_keyboard, get_channels_where_admins};
+
+        let from_user = match msg.from() {
+            Some(user) => user,
+            None => return msg_reply("Couldn't recognize the user. Try again.", &bot, &msg).await,
+        };
+        let channels = get_channels_where_admins(&bot, conn, &from_user.id, &me.user.id).await?;
+        if channels.is_empty() {
+            return msg_reply(
+                "No channels found. Try adding a new channel first",
+                &bot,
+                &msg,
+            )
+            .await;
+        }
+        bot.send_message(
+            msg.chat.id,
+            "Okay. Choose the channel whose subreddits you want to see:",
+        )
+        .reply_to_message_id(msg.id)
+        .reply_markup(channel_keyboard(&channels, 0))
+        .await?;
+        update_dialogue(&dialogue, SupState::Listing(State::ReceiveChannel)).await
+    }
+
+    pub(super) async fn on_listing_channel(
+        bot: Bot,
+        dialogue: Dialogue<SupState, AppDialogue>,
+        q: CallbackQuery,
+        me: Me,
+        conn: Arc<Mutex<SqliteConnection>>,
+    ) -> TeloxideResult {
+        use super::helpers::listing_page;
+
+        let channel = match picked_channel(&bot, &q, &me, &conn).await? {
+            Some(channel) => channel,
+            None => return Ok(()),
+        };
+        let page = listing_page(&channel, 0, &mut conn.lock().unwrap())?;
+        let (text, keyboard) = match page {
+            Some(page) => page,
+            None => {
+                callback_reply("This channel has no linked subreddits.", &bot, &q).await?;
+                return update_dialogue(&dialogue, SupState::MainMenu).await;
+            }
+        };
+        callback_menu(text, keyboard, &bot, &q).await?;
+        update_dialogue(&dialogue, SupState::Listing(State::Page(Box::new(channel)))).await
+    }
+
+    pub(super) async fn on_listing_page(
+        bot: Bot,
+        dialogue: Dialogue<SupState, AppDialogue>,
+        q: CallbackQuery,
+        conn: Arc<Mutex<SqliteConnection>>,
+        channel: Box<Channel>,
+    ) -> TeloxideResult {
+        use super::helpers::listing_page;
+
+        if !can_manage_channel(&bot, &channel, Some(&q.from)).await? {
+            bot.answer_callback_query(q.id.clone())
+                .text(not_admin_message(&channel))
+                .await?;
+            return Ok(());
+        }
+        bot.answer_callback_query(q.id.clone()).await?;
+        let data = q.data.clone().unwrap_or_default();
+        if data == "done" {
+            if let Some(message) = &q.message {
+                bot.edit_message_reply_markup(message.chat.id, message.id)
+                    .await?;
+            }
+            return update_dialogue(&dialogue, SupState::MainMenu).await;
+        }
+        let page = match data
+            .strip_prefix("page:")
+            .and_then(|page| page.parse::<usize>().ok())
+        {
+            Some(page) => page,
+            None => return Ok(()),
+        };
+        // The subreddits may have been unlinked since the list was sent.
+        let page = listing_page(&channel, page, &mut conn.lock().unwrap())?;
+        let (text, keyboard) = match page {
+            Some(page) => page,
+            None => {
+                callback_reply("This channel has no linked subreddits.", &bot, &q).await?;
+                return update_dialogue(&dialogue, SupState::MainMenu).await;
+            }
+        };
+        callback_menu(text, keyboard, &bot, &q).await
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub enum State {
+    ReceiveChannel,
+    Page(Box<Channel>),
+}
+
+pub fn schema() -> DispatcherSchema {
+    use super::{Command, State as SupState};
+    use teloxide::dptree::case;
+    dptree::entry()
+        .branch(
+            Update::filter_message().branch(
+                case![SupState::MainMenu]
+                    .filter_command::<Command>()
+                    .branch(case![Command::ListSubreddits].endpoint(listeners::on_listing)),
+            ),
+        )
+        .branch(
+            Update::filter_callback_query().branch(
+                case![SupState::Listing(x)]
+                    .branch(case![State::ReceiveChannel].endpoint(listeners::on_listing_channel))
+                    .branch(
+                        case![State::Page(selected_channel)].endpoint(listeners::on_listing_page),
+                    ),
+            ),
+        )
+}
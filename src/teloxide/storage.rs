@@ -0,0 +1,145 @@
+use std::{
+    error::Error,
+    fmt,
+    marker::PhantomData,
+    sync::{Arc, Mutex},
+};
+
+use chrono::{Duration, Utc};
+use diesel::SqliteConnection;
+use futures::future::BoxFuture;
+use log::{info, warn};
+use serde::{de::DeserializeOwned, Serialize};
+use teloxide::{dispatching::dialogue::Storage, types::ChatId};
+
+use crate::db::models::DialogueState;
+
+#[derive(Debug)]
+pub enum SqliteStorageError {
+    Query(diesel::result::Error),
+    Serialization(serde_json::Error),
+    DialogueNotFound,
+}
+
+impl fmt::Display for SqliteStorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SqliteStorageError::Query(error) => write!(f, "Dialogue query failed: {}", error),
+            SqliteStorageError::Serialization(error) => {
+                write!(f, "Couldn't (de)serialize dialogue state: {}", error)
+            }
+            SqliteStorageError::DialogueNotFound => f.write_str("Dialogue not found"),
+        }
+    }
+}
+
+impl Error for SqliteStorageError {}
+
+impl From<diesel::result::Error> for SqliteStorageError {
+    fn from(error: diesel::result::Error) -> Self {
+        SqliteStorageError::Query(error)
+    }
+}
+
+impl From<serde_json::Error> for SqliteStorageError {
+    fn from(error: serde_json::Error) -> Self {
+        SqliteStorageError::Serialization(error)
+    }
+}
+
+/// Keeps dialogue states as JSON in the bot's database so conversations survive restarts.
+///
+/// States untouched for longer than the configured time to live are treated as absent.
+pub struct SqliteStorage<D> {
+    conn: Arc<Mutex<SqliteConnection>>,
+    ttl: Duration,
+    _state: PhantomData<fn() -> D>,
+}
+
+impl<D> SqliteStorage<D> {
+    pub fn new(
+        conn: Arc<Mutex<SqliteConnection>>,
+        ttl_seconds: u64,
+    ) -> Result<Arc<Self>, SqliteStorageError> {
+        let ttl = Duration::seconds(ttl_seconds as i64);
+        let expired = DialogueState::delete_updated_before(
+            Utc::now().naive_utc() - ttl,
+            &mut conn.lock().unwrap(),
+        )?;
+        if expired > 0 {
+            info!("Dropped {} expired dialogue states", expired);
+        }
+        Ok(Arc::new(SqliteStorage {
+            conn,
+            ttl,
+            _state: PhantomData,
+        }))
+    }
+}
+
+impl<D> Storage<D> for SqliteStorage<D>
+where
+    D: Serialize + DeserializeOwned + Send + 'static,
+{
+    type Error = SqliteStorageError;
+
+    fn remove_dialogue(
+        self: Arc<Self>,
+        chat_id: ChatId,
+    ) -> BoxFuture<'static, Result<(), Self::Error>>
+    where
+        D: Send + 'static,
+    {
+        Box::pin(async move {
+            match DialogueState::delete(chat_id, &mut self.conn.lock().unwrap())? {
+                0 => Err(SqliteStorageError::DialogueNotFound),
+                _ => Ok(()),
+            }
+        })
+    }
+
+    fn update_dialogue(
+        self: Arc<Self>,
+        chat_id: ChatId,
+        dialogue: D,
+    ) -> BoxFuture<'static, Result<(), Self::Error>>
+    where
+        D: Send + 'static,
+    {
+        Box::pin(async move {
+            let state = serde_json::to_string(&dialogue)?;
+            DialogueState::save(chat_id, &state, &mut self.conn.lock().unwrap())?;
+            Ok(())
+        })
+    }
+
+    fn get_dialogue(
+        self: Arc<Self>,
+        chat_id: ChatId,
+    ) -> BoxFuture<'static, Result<Option<D>, Self::Error>> {
+        Box::pin(async move {
+            let conn = &mut self.conn.lock().unwrap();
+            let stored = match DialogueState::get(chat_id, conn)? {
+                Some(stored) => stored,
+                None => return Ok(None),
+            };
+            if stored.updated_at < Utc::now().naive_utc() - self.ttl {
+                DialogueState::delete(chat_id, conn)?;
+                return Ok(None);
+            }
+            match serde_json::from_str(&stored.state) {
+                Ok(dialogue) => Ok(Some(dialogue)),
+                // States saved by an older version of the bot may no longer fit; start the
+                // conversation over instead of failing every update from the chat.
+                Err(error) => {
+                    warn!(
+                        "Dropping the unreadable dialogue state of chat {}: {}",
+                        chat_id, error
+                    );
+                    DialogueState::delete(chat_id, conn)?;
+                    Ok(None)
+                }
+            }
+        })
+    }
+}
@@ -1,19 +1,110 @@
 use super::{AppDialogue, Command, DispatcherSchema, State as SupState, TeloxideResult};
-use crate::db::models::Channel;
+use crate::db::models::{Channel, Subreddit};
+use serde_derive::{Deserialize, Serialize};
 use teloxide::prelude::*;
 
+pub mod helpers {
+    use super::*;
+    use crate::teloxide::paged_keyboard;
+    use diesel::SqliteConnection;
+    use std::{
+        error::Error,
+        sync::{Arc, Mutex},
+    };
+    use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup};
+
+    pub(crate) fn subreddit_keyboard(
+        subreddits: &[Subreddit],
+        page: usize,
+    ) -> InlineKeyboardMarkup {
+        paged_keyboard(
+            subreddits
+                .iter()
+                .map(|subreddit| {
+                    InlineKeyboardButton::callback(
+                        format!("r/{}", subreddit.name),
+                        format!("sub:{}", subreddit.id),
+                    )
+                })
+                .collect(),
+            page,
+            "subs",
+        )
+    }
+
+    /// Handles a press on a keyboard of the subreddits linked to the channel, built by
+    /// `keyboard`. The page buttons turn the page, otherwise the pressed subreddit is returned.
+    pub(crate) async fn picked_subreddit(
+        bot: &Bot,
+        q: &CallbackQuery,
+        channel: &Channel,
+        conn: &Arc<Mutex<SqliteConnection>>,
+        keyboard: fn(&[Subreddit], usize) -> InlineKeyboardMarkup,
+    ) -> Result<Option<Subreddit>, Box<dyn Error + Send + Sync>> {
+        let data = q.data.as_deref().unwrap_or_default();
+        let subreddits = Subreddit::get_by_channel(channel.clone(), &mut conn.lock().unwrap())?;
+        if let Some(page) = data
+            .strip_prefix("subs:")
+            .and_then(|page| page.parse::<usize>().ok())
+        {
+            bot.answer_callback_query(q.id.clone()).await?;
+            if let Some(message) = &q.message {
+                bot.edit_message_reply_markup(message.chat.id, message.id)
+                    .reply_markup(keyboard(&subreddits, page))
+                    .await?;
+            }
+            return Ok(None);
+        }
+        let subreddit_id = match data
+            .strip_prefix("sub:")
+            .and_then(|id| id.parse::<i32>().ok())
+        {
+            Some(subreddit_id) => subreddit_id,
+            None => {
+                bot.answer_callback_query(q.id.clone()).await?;
+                return Ok(None);
+            }
+        };
+        let subreddit = subreddits
+            .into_iter()
+            .find(|subreddit| subreddit.id == subreddit_id);
+        match subreddit {
+            Some(_) => bot.answer_callback_query(q.id.clone()).await?,
+            None => {
+                bot.answer_callback_query(q.id.clone())
+                    .text("This subreddit isn't linked to the channel anymore.")
+                    .await?
+            }
+        };
+        Ok(subreddit)
+    }
+}
+
 mod listeners {
     use diesel::SqliteConnection;
-    use roux::{subreddit::response::SubredditData, Subreddit as SubredditApi};
+    use roux::Subreddit as SubredditApi;
     use std::sync::{Arc, Mutex};
     use teloxide::types::Me;
 
     use crate::{
-        db::models::{ChannelSubreddit, NewChannelSubreddit, NewSubreddit, Subreddit},
-        teloxide::{msg_reply, update_dialogue},
+        db::models::{
+            ChannelSubreddit, NewChannelSubreddit, NewSubreddit, SortPeriod, SortType, Subreddit,
+            SubredditSetting,
+        },
+        teloxide::{
+            callback_menu, callback_reply,
+            channel::helpers::{
+                can_manage_channel, can_manage_subreddit, not_admin_message, picked_channel,
+                shared_subreddit_message,
+            },
+            msg_reply, update_dialogue,
+        },
     };
 
-    use super::*;
+    use super::{
+        helpers::{picked_subreddit, subreddit_keyboard},
+        *,
+    };
     pub(super) async fn on_sub_link(
         bot: Bot,
         dialogue: Dialogue<SupState, AppDialogue>,
@@ -21,7 +112,7 @@ mod listeners {
         me: Me,
         conn: Arc<Mutex<SqliteConnection>>,
     ) -> TeloxideResult {
-        use crate::teloxide::channel::helpers::{channel_list_message, get_channels_where_admins};
+        use crate::teloxide::channel::helpers::{channel_keyboard, get_channels_where_admins};
 
         let from_user = match msg.from() {
             Some(user) => user,
@@ -36,56 +127,28 @@ mod listeners {
             )
             .await;
         }
-        msg_reply(
-            format!(
-                "Got it. Type the ID of the channel you want to link:\n\n{}",
-                channel_list_message(channels)?
-            ),
-            &bot,
-            &msg,
-        )
-        .await?;
+        bot.send_message(msg.chat.id, "Got it. Choose the channel you want to link:")
+            .reply_to_message_id(msg.id)
+            .reply_markup(channel_keyboard(&channels, 0))
+            .await?;
         update_dialogue(&dialogue, SupState::Sub(State::LinkReceiveChannel)).await
     }
 
     pub(super) async fn on_sub_link_channel(
         bot: Bot,
         dialogue: Dialogue<SupState, AppDialogue>,
-        msg: Message,
+        q: CallbackQuery,
+        me: Me,
         conn: Arc<Mutex<SqliteConnection>>,
     ) -> TeloxideResult {
-        use crate::db::schema::channel::dsl::*;
-        use diesel::prelude::*;
-
-        let channel_id: ChatId = match msg.text() {
-            Some(text) => ChatId(text.parse()?),
-            None => {
-                return msg_reply(
-                    "Please send a message with the id of the channel you wish to link",
-                    &bot,
-                    &msg,
-                )
-                .await;
-            }
-        };
-        let found_channel: Result<Channel, _> = channel
-            .filter(chat_id.eq(channel_id.0))
-            .first(&mut *conn.lock().unwrap());
-        let selected_channel = match found_channel {
-            Ok(real_channel) => real_channel,
-            Err(_) => {
-                return msg_reply(
-                    "Couldn't find the channel. Please send the of an already linked channel.",
-                    &bot,
-                    &msg,
-                )
-                .await;
-            }
+        let selected_channel = match picked_channel(&bot, &q, &me, &conn).await? {
+            Some(selected_channel) => selected_channel,
+            None => return Ok(()),
         };
-        msg_reply(
+        callback_reply(
             "Great. Now send the subreddit name (without the preceding /r/ part and without the trailing slashes).",
             &bot,
-            &msg,
+            &q,
         )
         .await?;
         update_dialogue(
@@ -102,6 +165,10 @@ mod listeners {
         conn: Arc<Mutex<SqliteConnection>>,
         selected_channel: Channel,
     ) -> TeloxideResult {
+        if !can_manage_channel(&bot, &selected_channel, msg.from()).await? {
+            msg_reply(not_admin_message(&selected_channel), &bot, &msg).await?;
+            return update_dialogue(&dialogue, SupState::MainMenu).await;
+        }
         let sub_name = match msg.text() {
             Some(text) => text,
             None => return msg_reply("Please send a subreddit name.", &bot, &msg).await,
@@ -112,7 +179,7 @@ mod listeners {
                 return msg_reply(format!("Error: {}. Try again.", error), &bot, &msg).await
             }
         };
-        let (sub_id, sub_name) = match (sub_data.id, sub_data.name) {
+        let (sub_id, sub_name) = match (sub_data.id, sub_data.display_name) {
             (Some(id), Some(name)) => (id, name),
             _ => {
                 return msg_reply(
@@ -123,14 +190,17 @@ mod listeners {
                 .await;
             }
         };
-        let subreddit = match Subreddit::get_by_sub_id(&sub_id, &mut conn.lock().unwrap()) {
-            Ok(db_subreddit) => Ok(db_subreddit),
-            Err(_) => {
-                let new_subreddit = NewSubreddit {
-                    subreddit_id: sub_id.as_str(),
-                    name: sub_name.as_str(),
-                };
-                new_subreddit.insert(&mut conn.lock().unwrap())
+        let subreddit = {
+            let conn = &mut conn.lock().unwrap();
+            match Subreddit::get_by_sub_id(&sub_id, conn) {
+                Ok(db_subreddit) => Ok(db_subreddit),
+                Err(_) => {
+                    let new_subreddit = NewSubreddit {
+                        subreddit_id: sub_id.as_str(),
+                        name: sub_name.as_str(),
+                    };
+                    new_subreddit.insert(conn)
+                }
             }
         };
         let subreddit = match subreddit {
@@ -152,6 +222,7 @@ mod listeners {
         if !related_channels {
             ChannelSubreddit::insert(
                 &NewChannelSubreddit::new(&selected_channel, &subreddit),
+                msg.from().map(|user| user.id),
                 &mut conn.lock().unwrap(),
             )?;
         }
@@ -166,7 +237,7 @@ mod listeners {
         conn: Arc<Mutex<SqliteConnection>>,
         me: Me,
     ) -> TeloxideResult {
-        use crate::teloxide::channel::helpers::{channel_list_message, get_channels_where_admins};
+        use crate::teloxide::channel::helpers::{channel_keyboard, get_channels_where_admins};
 
         let from_user = match msg.from() {
             Some(user) => user,
@@ -181,116 +252,977 @@ mod listeners {
             )
             .await;
         }
-        msg_reply(
+        bot.send_message(
+            msg.chat.id,
+            "Got it. Choose the channel you want to unlink subreddit from:",
+        )
+        .reply_to_message_id(msg.id)
+        .reply_markup(channel_keyboard(&channels, 0))
+        .await?;
+        update_dialogue(&dialogue, SupState::Sub(State::UnlinkReceiveChannel)).await
+    }
+
+    pub(super) async fn on_sub_unlink_channel(
+        bot: Bot,
+        dialogue: Dialogue<SupState, AppDialogue>,
+        q: CallbackQuery,
+        me: Me,
+        conn: Arc<Mutex<SqliteConnection>>,
+    ) -> TeloxideResult {
+        let selected_channel = match picked_channel(&bot, &q, &me, &conn).await? {
+            Some(selected_channel) => selected_channel,
+            None => return Ok(()),
+        };
+        let subreddits =
+            Subreddit::get_by_channel(selected_channel.clone(), &mut conn.lock().unwrap())?;
+        if subreddits.is_empty() {
+            callback_reply("This channel has no linked subreddits.", &bot, &q).await?;
+            return update_dialogue(&dialogue, SupState::MainMenu).await;
+        }
+        callback_menu(
+            "Great. Now choose the subreddit to unlink:",
+            subreddit_keyboard(&subreddits, 0),
+            &bot,
+            &q,
+        )
+        .await?;
+        update_dialogue(
+            &dialogue,
+            SupState::Sub(State::UnlinkReceiveSub(selected_channel)),
+        )
+        .await
+    }
+
+    pub(super) async fn on_sub_unlink_sub(
+        bot: Bot,
+        dialogue: Dialogue<SupState, AppDialogue>,
+        q: CallbackQuery,
+        conn: Arc<Mutex<SqliteConnection>>,
+        channel: Channel,
+    ) -> TeloxideResult {
+        let subreddit =
+            match picked_subreddit(&bot, &q, &channel, &conn, subreddit_keyboard).await? {
+                Some(subreddit) => subreddit,
+                None => return Ok(()),
+            };
+        if !can_manage_channel(&bot, &channel, Some(&q.from)).await? {
+            callback_reply(not_admin_message(&channel), &bot, &q).await?;
+            return update_dialogue(&dialogue, SupState::MainMenu).await;
+        }
+        ChannelSubreddit::delete(
+            &channel,
+            &subreddit,
+            Some(q.from.id),
+            &mut conn.lock().unwrap(),
+        )?;
+        callback_reply(
+            format!("Unlinked r/{} from the channel.", subreddit.name),
+            &bot,
+            &q,
+        )
+        .await?;
+        update_dialogue(&dialogue, SupState::MainMenu).await
+    }
+
+    pub(super) async fn on_sub_sorting(
+        bot: Bot,
+        dialogue: Dialogue<SupState, AppDialogue>,
+        msg: Message,
+        conn: Arc<Mutex<SqliteConnection>>,
+        me: Me,
+    ) -> TeloxideResult {
+        use crate::teloxide::channel::helpers::{channel_keyboard, get_channels_where_admins};
+
+        let from_user = match msg.from() {
+            Some(user) => user,
+            None => return msg_reply("Couldn't recognize the user. Try again.", &bot, &msg).await,
+        };
+        let channels = get_channels_where_admins(&bot, conn, &from_user.id, &me.user.id).await?;
+        if channels.is_empty() {
+            return msg_reply(
+                "No channels found. Try adding a new channel first",
+                &bot,
+                &msg,
+            )
+            .await;
+        }
+        bot.send_message(
+            msg.chat.id,
+            "Got it. Choose the channel whose subreddit you want to re-sort:",
+        )
+        .reply_to_message_id(msg.id)
+        .reply_markup(channel_keyboard(&channels, 0))
+        .await?;
+        update_dialogue(&dialogue, SupState::Sub(State::SortingReceiveChannel)).await
+    }
+
+    pub(super) async fn on_sub_sorting_channel(
+        bot: Bot,
+        dialogue: Dialogue<SupState, AppDialogue>,
+        q: CallbackQuery,
+        me: Me,
+        conn: Arc<Mutex<SqliteConnection>>,
+    ) -> TeloxideResult {
+        let selected_channel = match picked_channel(&bot, &q, &me, &conn).await? {
+            Some(selected_channel) => selected_channel,
+            None => return Ok(()),
+        };
+        let subreddits =
+            Subreddit::get_by_channel(selected_channel.clone(), &mut conn.lock().unwrap())?;
+        if subreddits.is_empty() {
+            callback_reply("This channel has no linked subreddits.", &bot, &q).await?;
+            return update_dialogue(&dialogue, SupState::MainMenu).await;
+        }
+        let subreddit_list = subreddits
+            .iter()
+            .map(|subreddit| {
+                format!(
+                    "r/{} (sorted by {} {})",
+                    subreddit.name,
+                    subreddit.sorting.as_str(),
+                    subreddit.sort_period.as_str()
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        callback_menu(
             format!(
-                "Got it. Type the ID of the channel you want to unlink subreddit from:\n\n{}",
-                channel_list_message(channels)?
+                "Great. Now choose the subreddit to re-sort:\n\n{}",
+                subreddit_list
             ),
+            subreddit_keyboard(&subreddits, 0),
+            &bot,
+            &q,
+        )
+        .await?;
+        update_dialogue(
+            &dialogue,
+            SupState::Sub(State::SortingReceiveSub(selected_channel)),
+        )
+        .await
+    }
+
+    pub(super) async fn on_sub_sorting_sub(
+        bot: Bot,
+        dialogue: Dialogue<SupState, AppDialogue>,
+        q: CallbackQuery,
+        conn: Arc<Mutex<SqliteConnection>>,
+        channel: Channel,
+    ) -> TeloxideResult {
+        let subreddit =
+            match picked_subreddit(&bot, &q, &channel, &conn, subreddit_keyboard).await? {
+                Some(subreddit) => subreddit,
+                None => return Ok(()),
+            };
+        if !can_manage_subreddit(&bot, &subreddit, Some(&q.from), &conn).await? {
+            callback_reply(shared_subreddit_message(&subreddit), &bot, &q).await?;
+            return update_dialogue(&dialogue, SupState::MainMenu).await;
+        }
+        callback_reply(
+            "Send the new sorting: one of hot, rising, latest, top or controversial. \
+            For top and controversial you can add a time range: hour, day, week, month, year or all (e.g. \"top week\").",
+            &bot,
+            &q,
+        )
+        .await?;
+        update_dialogue(
+            &dialogue,
+            SupState::Sub(State::SortingReceiveSorting(channel, subreddit)),
+        )
+        .await
+    }
+
+    pub(super) async fn on_sub_sorting_sorting(
+        bot: Bot,
+        dialogue: Dialogue<SupState, AppDialogue>,
+        msg: Message,
+        conn: Arc<Mutex<SqliteConnection>>,
+        (channel, subreddit): (Channel, Subreddit),
+    ) -> TeloxideResult {
+        if !can_manage_channel(&bot, &channel, msg.from()).await? {
+            msg_reply(not_admin_message(&channel), &bot, &msg).await?;
+            return update_dialogue(&dialogue, SupState::MainMenu).await;
+        }
+        if !can_manage_subreddit(&bot, &subreddit, msg.from(), &conn).await? {
+            msg_reply(shared_subreddit_message(&subreddit), &bot, &msg).await?;
+            return update_dialogue(&dialogue, SupState::MainMenu).await;
+        }
+        let mut words = msg.text().unwrap_or_default().split_whitespace();
+        let sorting = match words.next().map(str::parse::<SortType>) {
+            Some(Ok(sorting)) => sorting,
+            Some(Err(error)) => {
+                return msg_reply(format!("{}. Try again.", error), &bot, &msg).await
+            }
+            None => return msg_reply("Please send the sorting.", &bot, &msg).await,
+        };
+        let sort_period = match words.next().map(str::parse::<SortPeriod>) {
+            Some(Ok(sort_period)) => sort_period,
+            Some(Err(error)) => {
+                return msg_reply(format!("{}. Try again.", error), &bot, &msg).await
+            }
+            None => subreddit.sort_period,
+        };
+        subreddit.update(
+            SubredditSetting::Sorting(sorting, sort_period),
+            msg.from().map(|user| user.id),
+            &mut conn.lock().unwrap(),
+        )?;
+        msg_reply(
+            if sorting.uses_period() {
+                format!(
+                    "r/{} will now be mirrored from its {} posts of the {}.",
+                    subreddit.name,
+                    sorting.as_str(),
+                    sort_period.as_str()
+                )
+            } else {
+                format!(
+                    "r/{} will now be mirrored from its {} posts.",
+                    subreddit.name,
+                    sorting.as_str()
+                )
+            },
             &bot,
             &msg,
         )
         .await?;
-        update_dialogue(&dialogue, SupState::Sub(State::UnlinkReceiveChannel)).await
+        update_dialogue(&dialogue, SupState::MainMenu).await
     }
 
-    pub(super) async fn on_sub_unlink_channel(
+    pub(super) async fn on_sub_quota(
         bot: Bot,
         dialogue: Dialogue<SupState, AppDialogue>,
         msg: Message,
         conn: Arc<Mutex<SqliteConnection>>,
+        me: Me,
     ) -> TeloxideResult {
-        let channel_id: ChatId = match msg.text() {
-            Some(text) => ChatId(text.parse()?),
-            None => {
+        use crate::teloxide::channel::helpers::{channel_keyboard, get_channels_where_admins};
+
+        let from_user = match msg.from() {
+            Some(user) => user,
+            None => return msg_reply("Couldn't recognize the user. Try again.", &bot, &msg).await,
+        };
+        let channels = get_channels_where_admins(&bot, conn, &from_user.id, &me.user.id).await?;
+        if channels.is_empty() {
+            return msg_reply(
+                "No channels found. Try adding a new channel first",
+                &bot,
+                &msg,
+            )
+            .await;
+        }
+        bot.send_message(
+            msg.chat.id,
+            "Got it. Choose the channel whose daily limit you want to change:",
+        )
+        .reply_to_message_id(msg.id)
+        .reply_markup(channel_keyboard(&channels, 0))
+        .await?;
+        update_dialogue(&dialogue, SupState::Sub(State::QuotaReceiveChannel)).await
+    }
+
+    pub(super) async fn on_sub_quota_channel(
+        bot: Bot,
+        dialogue: Dialogue<SupState, AppDialogue>,
+        q: CallbackQuery,
+        me: Me,
+        conn: Arc<Mutex<SqliteConnection>>,
+    ) -> TeloxideResult {
+        let selected_channel = match picked_channel(&bot, &q, &me, &conn).await? {
+            Some(selected_channel) => selected_channel,
+            None => return Ok(()),
+        };
+        let links = {
+            let conn = &mut conn.lock().unwrap();
+            Subreddit::get_by_channel(selected_channel.clone(), conn)?
+                .into_iter()
+                .map(|subreddit| {
+                    ChannelSubreddit::get(&selected_channel, &subreddit, conn)
+                        .map(|link| (subreddit, link))
+                })
+                .collect::<Result<Vec<_>, _>>()?
+        };
+        if links.is_empty() {
+            callback_reply("This channel has no linked subreddits.", &bot, &q).await?;
+            return update_dialogue(&dialogue, SupState::MainMenu).await;
+        }
+        let subreddit_list = links
+            .iter()
+            .map(|(subreddit, link)| match link.daily_limit {
+                Some(daily_limit) => format!(
+                    "r/{} ({} posts a day, {} the rest)",
+                    subreddit.name,
+                    daily_limit,
+                    link.quota_policy.as_str()
+                ),
+                None => format!("r/{} (no daily limit)", subreddit.name),
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let subreddits = links
+            .into_iter()
+            .map(|(subreddit, _)| subreddit)
+            .collect::<Vec<_>>();
+        callback_menu(
+            format!("Great. Now choose the subreddit:\n\n{}", subreddit_list),
+            subreddit_keyboard(&subreddits, 0),
+            &bot,
+            &q,
+        )
+        .await?;
+        update_dialogue(
+            &dialogue,
+            SupState::Sub(State::QuotaReceiveSub(selected_channel)),
+        )
+        .await
+    }
+
+    pub(super) async fn on_sub_quota_sub(
+        bot: Bot,
+        dialogue: Dialogue<SupState, AppDialogue>,
+        q: CallbackQuery,
+        conn: Arc<Mutex<SqliteConnection>>,
+        channel: Channel,
+    ) -> TeloxideResult {
+        let subreddit =
+            match picked_subreddit(&bot, &q, &channel, &conn, subreddit_keyboard).await? {
+                Some(subreddit) => subreddit,
+                None => return Ok(()),
+            };
+        callback_reply(
+            "Send the maximum number of posts a day, followed by what to do with the posts over the limit: \
+            \"drop\" to never post them or \"defer\" to post them on a later day (e.g. \"10 defer\"). \
+            Send \"none\" to remove the limit.",
+            &bot,
+            &q,
+        )
+        .await?;
+        update_dialogue(
+            &dialogue,
+            SupState::Sub(State::QuotaReceiveQuota(channel, subreddit)),
+        )
+        .await
+    }
+
+    pub(super) async fn on_sub_quota_quota(
+        bot: Bot,
+        dialogue: Dialogue<SupState, AppDialogue>,
+        msg: Message,
+        conn: Arc<Mutex<SqliteConnection>>,
+        (channel, subreddit): (Channel, Subreddit),
+    ) -> TeloxideResult {
+        use crate::db::models::{QuotaPolicy, SettingError};
+
+        if !can_manage_channel(&bot, &channel, msg.from()).await? {
+            msg_reply(not_admin_message(&channel), &bot, &msg).await?;
+            return update_dialogue(&dialogue, SupState::MainMenu).await;
+        }
+        let link = ChannelSubreddit::get(&channel, &subreddit, &mut conn.lock().unwrap())?;
+        let mut words = msg.text().unwrap_or_default().split_whitespace();
+        let daily_limit = match words.next() {
+            Some(word) if word.eq_ignore_ascii_case("none") => None,
+            Some(word) => match word.parse::<i32>() {
+                Ok(daily_limit) => Some(daily_limit),
+                Err(_) => {
+                    return msg_reply(
+                        "The daily limit must be a whole number. Try again.",
+                        &bot,
+                        &msg,
+                    )
+                    .await
+                }
+            },
+            None => return msg_reply("Please send the daily limit.", &bot, &msg).await,
+        };
+        let quota_policy = match words.next().map(str::parse::<QuotaPolicy>) {
+            Some(Ok(quota_policy)) => quota_policy,
+            Some(Err(error)) => {
+                return msg_reply(format!("{}. Try again.", error), &bot, &msg).await
+            }
+            None => link.quota_policy,
+        };
+        let updated = link.set_quota(
+            daily_limit,
+            quota_policy,
+            msg.from().map(|user| user.id),
+            &mut conn.lock().unwrap(),
+        );
+        match updated {
+            Ok(_) => (),
+            Err(SettingError::Invalid(reason)) => {
+                return msg_reply(format!("{}. Try again.", reason), &bot, &msg).await
+            }
+            Err(error) => return Err(error.into()),
+        }
+        msg_reply(
+            match daily_limit {
+                Some(daily_limit) => format!(
+                    "At most {} posts a day will be mirrored from r/{}, the rest will be {}.",
+                    daily_limit,
+                    subreddit.name,
+                    if quota_policy == QuotaPolicy::Drop {
+                        "dropped"
+                    } else {
+                        "deferred"
+                    }
+                ),
+                None => format!("Removed the daily limit of r/{}.", subreddit.name),
+            },
+            &bot,
+            &msg,
+        )
+        .await?;
+        update_dialogue(&dialogue, SupState::MainMenu).await
+    }
+
+    pub(super) async fn on_sub_comments(
+        bot: Bot,
+        dialogue: Dialogue<SupState, AppDialogue>,
+        msg: Message,
+        conn: Arc<Mutex<SqliteConnection>>,
+        me: Me,
+    ) -> TeloxideResult {
+        use crate::teloxide::channel::helpers::{channel_keyboard, get_channels_where_admins};
+
+        let from_user = match msg.from() {
+            Some(user) => user,
+            None => return msg_reply("Couldn't recognize the user. Try again.", &bot, &msg).await,
+        };
+        let channels = get_channels_where_admins(&bot, conn, &from_user.id, &me.user.id).await?;
+        if channels.is_empty() {
+            return msg_reply(
+                "No channels found. Try adding a new channel first",
+                &bot,
+                &msg,
+            )
+            .await;
+        }
+        bot.send_message(
+            msg.chat.id,
+            "Got it. Choose the channel whose comment mirroring you want to change:",
+        )
+        .reply_to_message_id(msg.id)
+        .reply_markup(channel_keyboard(&channels, 0))
+        .await?;
+        update_dialogue(&dialogue, SupState::Sub(State::CommentsReceiveChannel)).await
+    }
+
+    pub(super) async fn on_sub_comments_channel(
+        bot: Bot,
+        dialogue: Dialogue<SupState, AppDialogue>,
+        q: CallbackQuery,
+        me: Me,
+        conn: Arc<Mutex<SqliteConnection>>,
+    ) -> TeloxideResult {
+        let selected_channel = match picked_channel(&bot, &q, &me, &conn).await? {
+            Some(selected_channel) => selected_channel,
+            None => return Ok(()),
+        };
+        let links = {
+            let conn = &mut conn.lock().unwrap();
+            Subreddit::get_by_channel(selected_channel.clone(), conn)?
+                .into_iter()
+                .map(|subreddit| {
+                    ChannelSubreddit::get(&selected_channel, &subreddit, conn)
+                        .map(|link| (subreddit, link))
+                })
+                .collect::<Result<Vec<_>, _>>()?
+        };
+        if links.is_empty() {
+            callback_reply("This channel has no linked subreddits.", &bot, &q).await?;
+            return update_dialogue(&dialogue, SupState::MainMenu).await;
+        }
+        let subreddit_list = links
+            .iter()
+            .map(|(subreddit, link)| {
+                if link.comments_enabled {
+                    format!("r/{} ({})", subreddit.name, comments_summary(link))
+                } else {
+                    format!("r/{} (comments off)", subreddit.name)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let subreddits = links
+            .into_iter()
+            .map(|(subreddit, _)| subreddit)
+            .collect::<Vec<_>>();
+        callback_menu(
+            format!("Great. Now choose the subreddit:\n\n{}", subreddit_list),
+            subreddit_keyboard(&subreddits, 0),
+            &bot,
+            &q,
+        )
+        .await?;
+        update_dialogue(
+            &dialogue,
+            SupState::Sub(State::CommentsReceiveSub(selected_channel)),
+        )
+        .await
+    }
+
+    pub(super) async fn on_sub_comments_sub(
+        bot: Bot,
+        dialogue: Dialogue<SupState, AppDialogue>,
+        q: CallbackQuery,
+        conn: Arc<Mutex<SqliteConnection>>,
+        channel: Channel,
+    ) -> TeloxideResult {
+        let subreddit =
+            match picked_subreddit(&bot, &q, &channel, &conn, subreddit_keyboard).await? {
+                Some(subreddit) => subreddit,
+                None => return Ok(()),
+            };
+        callback_reply(
+            "Send \"on\" followed by the number of comments, their minimum score (or \"none\") \
+            and how many reply levels below the top-level comments to include (e.g. \"on 3 10 1\"). \
+            Send \"off\" to stop mirroring comments. \
+            The bot has to be a member of the channel's discussion group for this to work.",
+            &bot,
+            &q,
+        )
+        .await?;
+        update_dialogue(
+            &dialogue,
+            SupState::Sub(State::CommentsReceiveSettings(channel, subreddit)),
+        )
+        .await
+    }
+
+    pub(super) async fn on_sub_comments_settings(
+        bot: Bot,
+        dialogue: Dialogue<SupState, AppDialogue>,
+        msg: Message,
+        conn: Arc<Mutex<SqliteConnection>>,
+        (channel, subreddit): (Channel, Subreddit),
+    ) -> TeloxideResult {
+        use crate::db::models::SettingError;
+
+        if !can_manage_channel(&bot, &channel, msg.from()).await? {
+            msg_reply(not_admin_message(&channel), &bot, &msg).await?;
+            return update_dialogue(&dialogue, SupState::MainMenu).await;
+        }
+        let link = ChannelSubreddit::get(&channel, &subreddit, &mut conn.lock().unwrap())?;
+        let mut words = msg.text().unwrap_or_default().split_whitespace();
+        let enabled = match words.next() {
+            Some(word) if word.eq_ignore_ascii_case("on") => true,
+            Some(word) if word.eq_ignore_ascii_case("off") => false,
+            _ => return msg_reply("Please send \"on\" or \"off\".", &bot, &msg).await,
+        };
+        let limit = match words.next().map(str::parse::<i32>) {
+            Some(Ok(limit)) => limit,
+            Some(Err(_)) => {
                 return msg_reply(
-                    "Please send a message with the id of the channel you wish to unlink",
+                    "The number of comments must be a whole number. Try again.",
                     &bot,
                     &msg,
                 )
                 .await
             }
+            None => link.comment_limit,
         };
-        let channel = Channel::get_by_chat_id(channel_id, &mut conn.lock().unwrap());
-        let selected_channel = match channel {
-            Ok(real_channel) => real_channel,
-            Err(_) => {
+        let min_score = match words.next() {
+            Some(word) if word.eq_ignore_ascii_case("none") => None,
+            Some(word) => match word.parse::<i32>() {
+                Ok(min_score) => Some(min_score),
+                Err(_) => {
+                    return msg_reply(
+                        "The minimum score must be a whole number. Try again.",
+                        &bot,
+                        &msg,
+                    )
+                    .await
+                }
+            },
+            None => link.comment_min_score,
+        };
+        let depth = match words.next().map(str::parse::<i32>) {
+            Some(Ok(depth)) => depth,
+            Some(Err(_)) => {
                 return msg_reply(
-                    "Couldn't find the channel. Please send the of an already linked channel.",
+                    "The comment depth must be a whole number. Try again.",
                     &bot,
                     &msg,
                 )
-                .await;
+                .await
             }
+            None => link.comment_depth,
         };
-
+        let updated = link.set_comments(
+            enabled,
+            limit,
+            min_score,
+            depth,
+            msg.from().map(|user| user.id),
+            &mut conn.lock().unwrap(),
+        );
+        match updated {
+            Ok(_) => (),
+            Err(SettingError::Invalid(reason)) => {
+                return msg_reply(format!("{}. Try again.", reason), &bot, &msg).await
+            }
+            Err(error) => return Err(error.into()),
+        }
+        let link = ChannelSubreddit::get(&channel, &subreddit, &mut conn.lock().unwrap())?;
         msg_reply(
-            "Great. Now send the subreddit name (without the preceding /r/ part and without the trailing slashes).",
+            if enabled {
+                format!(
+                    "Posts from r/{} will get their {} in the discussion group.",
+                    subreddit.name,
+                    comments_summary(&link)
+                )
+            } else {
+                format!("Stopped mirroring the comments of r/{}.", subreddit.name)
+            },
             &bot,
             &msg,
         )
         .await?;
+        update_dialogue(&dialogue, SupState::MainMenu).await
+    }
+
+    pub(super) async fn on_sub_keywords(
+        bot: Bot,
+        dialogue: Dialogue<SupState, AppDialogue>,
+        msg: Message,
+        conn: Arc<Mutex<SqliteConnection>>,
+        me: Me,
+    ) -> TeloxideResult {
+        use crate::teloxide::channel::helpers::{channel_keyboard, get_channels_where_admins};
+
+        let from_user = match msg.from() {
+            Some(user) => user,
+            None => return msg_reply("Couldn't recognize the user. Try again.", &bot, &msg).await,
+        };
+        let channels = get_channels_where_admins(&bot, conn, &from_user.id, &me.user.id).await?;
+        if channels.is_empty() {
+            return msg_reply(
+                "No channels found. Try adding a new channel first",
+                &bot,
+                &msg,
+            )
+            .await;
+        }
+        bot.send_message(
+            msg.chat.id,
+            "Got it. Choose the channel whose keyword rules you want to change:",
+        )
+        .reply_to_message_id(msg.id)
+        .reply_markup(channel_keyboard(&channels, 0))
+        .await?;
+        update_dialogue(&dialogue, SupState::Sub(State::KeywordsReceiveChannel)).await
+    }
+
+    pub(super) async fn on_sub_keywords_channel(
+        bot: Bot,
+        dialogue: Dialogue<SupState, AppDialogue>,
+        q: CallbackQuery,
+        me: Me,
+        conn: Arc<Mutex<SqliteConnection>>,
+    ) -> TeloxideResult {
+        let selected_channel = match picked_channel(&bot, &q, &me, &conn).await? {
+            Some(selected_channel) => selected_channel,
+            None => return Ok(()),
+        };
+        let subreddits =
+            Subreddit::get_by_channel(selected_channel.clone(), &mut conn.lock().unwrap())?;
+        if subreddits.is_empty() {
+            callback_reply("This channel has no linked subreddits.", &bot, &q).await?;
+            return update_dialogue(&dialogue, SupState::MainMenu).await;
+        }
+        let subreddit_list = subreddits
+            .iter()
+            .map(|subreddit| format!("r/{}", subreddit.name))
+            .collect::<Vec<_>>()
+            .join("\n");
+        callback_menu(
+            format!("Great. Now choose the subreddit:\n\n{}", subreddit_list),
+            subreddit_keyboard(&subreddits, 0),
+            &bot,
+            &q,
+        )
+        .await?;
         update_dialogue(
             &dialogue,
-            SupState::Sub(State::UnlinkReceiveSub(selected_channel)),
+            SupState::Sub(State::KeywordsReceiveSub(selected_channel)),
         )
         .await
     }
 
-    pub(super) async fn on_sub_unlink_sub(
+    pub(super) async fn on_sub_keywords_sub(
         bot: Bot,
         dialogue: Dialogue<SupState, AppDialogue>,
-        msg: Message,
+        q: CallbackQuery,
         conn: Arc<Mutex<SqliteConnection>>,
         channel: Channel,
     ) -> TeloxideResult {
-        let sub_name = msg.text().unwrap_or_default();
-        let sub_about = SubredditApi::new(sub_name).about().await;
-        if sub_about.is_err() {
-            return msg_reply("Subreddit not found. Try again.", &bot, &msg).await;
-        }
-        let SubredditData { id, .. } = sub_about.unwrap();
         let subreddit =
-            Subreddit::get_by_sub_id(&id.unwrap_or_default(), &mut conn.lock().unwrap());
-        if subreddit.is_err() {
-            msg_reply("Couldn't unlink: Subreddit not linked.", &bot, &msg).await?;
+            match picked_subreddit(&bot, &q, &channel, &conn, subreddit_keyboard).await? {
+                Some(subreddit) => subreddit,
+                None => return Ok(()),
+            };
+        let message_content = keywords_message(&channel, &subreddit, &mut conn.lock().unwrap())?;
+        callback_reply(
+            format!(
+                "{}\n\nTo change them, send one of:\n\
+                exclude giveaway (skip posts containing the word)\n\
+                include /\\b(cats?|kittens?)\\b/ (only let through posts matching the regular expression)\n\
+                exclude case NSFL (add \"case\" to match upper and lower case exactly)\n\
+                delete 2 (remove the rule with that number)\n\n\
+                Rules are matched against the title and the self text. \
+                Send \"done\" when you're finished.",
+                message_content
+            ),
+            &bot,
+            &q,
+        )
+        .await?;
+        update_dialogue(
+            &dialogue,
+            SupState::Sub(State::KeywordsEdit(channel, subreddit)),
+        )
+        .await
+    }
+
+    pub(super) async fn on_sub_keywords_edit(
+        bot: Bot,
+        dialogue: Dialogue<SupState, AppDialogue>,
+        msg: Message,
+        conn: Arc<Mutex<SqliteConnection>>,
+        (channel, subreddit): (Channel, Subreddit),
+    ) -> TeloxideResult {
+        use crate::db::models::{KeywordRule, NewKeywordRule, RuleKind, SettingError};
+
+        if !can_manage_channel(&bot, &channel, msg.from()).await? {
+            msg_reply(not_admin_message(&channel), &bot, &msg).await?;
             return update_dialogue(&dialogue, SupState::MainMenu).await;
         }
-        ChannelSubreddit::delete(&channel, &subreddit.unwrap(), &mut conn.lock().unwrap())?;
-        msg_reply("Unlinked the subreddit from the channel.", &bot, &msg).await?;
-        update_dialogue(&dialogue, SupState::MainMenu).await
+        let text = msg.text().unwrap_or_default().trim();
+        let (command, value) = text.split_once(' ').unwrap_or((text, ""));
+        let value = value.trim();
+        match command.to_lowercase().as_str() {
+            "done" => {
+                msg_reply("Saved the keyword rules.", &bot, &msg).await?;
+                return update_dialogue(&dialogue, SupState::MainMenu).await;
+            }
+            "delete" => {
+                let deleted = {
+                    let conn = &mut conn.lock().unwrap();
+                    let link = ChannelSubreddit::get(&channel, &subreddit, conn)?;
+                    let rules = KeywordRule::get_by_link(&link, conn)?;
+                    match value
+                        .parse::<usize>()
+                        .ok()
+                        .and_then(|number| rules.get(number.checked_sub(1)?))
+                    {
+                        Some(rule) => rule.delete(msg.from().map(|user| user.id), conn)? > 0,
+                        None => false,
+                    }
+                };
+                if !deleted {
+                    return msg_reply("There's no rule with that number. Try again.", &bot, &msg)
+                        .await;
+                }
+            }
+            kind => {
+                let kind = match kind.parse::<RuleKind>() {
+                    Ok(kind) => kind,
+                    Err(_) => {
+                        return msg_reply(
+                            "Please start the message with include, exclude or delete, or send \"done\".",
+                            &bot,
+                            &msg,
+                        )
+                        .await
+                    }
+                };
+                let (case_sensitive, pattern) = match value.split_once(' ') {
+                    Some((flag, pattern)) if flag.eq_ignore_ascii_case("case") => {
+                        (true, pattern.trim())
+                    }
+                    _ => (false, value),
+                };
+                let (is_regex, pattern) = match pattern
+                    .strip_prefix('/')
+                    .and_then(|pattern| pattern.strip_suffix('/'))
+                {
+                    Some(regex) if !regex.is_empty() => (true, regex),
+                    _ => (false, pattern),
+                };
+                let inserted = {
+                    let conn = &mut conn.lock().unwrap();
+                    ChannelSubreddit::get(&channel, &subreddit, conn).map(|link| {
+                        NewKeywordRule::new(&link, kind, pattern, is_regex, case_sensitive)
+                            .insert(msg.from().map(|user| user.id), conn)
+                    })?
+                };
+                match inserted {
+                    Ok(_) => (),
+                    Err(SettingError::Invalid(reason)) => {
+                        return msg_reply(format!("{}. Try again.", reason), &bot, &msg).await
+                    }
+                    Err(error) => return Err(error.into()),
+                }
+            }
+        }
+        let message_content = keywords_message(&channel, &subreddit, &mut conn.lock().unwrap())?;
+        msg_reply(message_content, &bot, &msg).await?;
+        update_dialogue(
+            &dialogue,
+            SupState::Sub(State::KeywordsEdit(channel, subreddit)),
+        )
+        .await
+    }
+
+    fn keywords_message(
+        channel: &Channel,
+        subreddit: &Subreddit,
+        conn: &mut SqliteConnection,
+    ) -> diesel::QueryResult<String> {
+        use crate::db::models::KeywordRule;
+
+        let link = ChannelSubreddit::get(channel, subreddit, conn)?;
+        let rules = KeywordRule::get_by_link(&link, conn)?;
+        if rules.is_empty() {
+            return Ok(format!("r/{} has no keyword rules.", subreddit.name));
+        }
+        Ok(format!(
+            "Keyword rules of r/{}:\n{}",
+            subreddit.name,
+            rules
+                .iter()
+                .enumerate()
+                .map(|(index, rule)| format!("{}. {}", index + 1, rule))
+                .collect::<Vec<_>>()
+                .join("\n")
+        ))
+    }
+
+    fn comments_summary(link: &ChannelSubreddit) -> String {
+        let mut summary = format!("top {} comments", link.comment_limit);
+        if let Some(min_score) = link.comment_min_score {
+            summary += &format!(" with a score of at least {}", min_score);
+        }
+        if link.comment_depth > 0 {
+            summary += &format!(", down to {} reply levels", link.comment_depth);
+        }
+        summary
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub(crate) enum State {
     LinkReceiveChannel,
     LinkReceiveSub(Channel),
     UnlinkReceiveChannel,
     UnlinkReceiveSub(Channel),
+    SortingReceiveChannel,
+    SortingReceiveSub(Channel),
+    SortingReceiveSorting(Channel, Subreddit),
+    QuotaReceiveChannel,
+    QuotaReceiveSub(Channel),
+    QuotaReceiveQuota(Channel, Subreddit),
+    CommentsReceiveChannel,
+    CommentsReceiveSub(Channel),
+    CommentsReceiveSettings(Channel, Subreddit),
+    KeywordsReceiveChannel,
+    KeywordsReceiveSub(Channel),
+    KeywordsEdit(Channel, Subreddit),
 }
 
 pub fn schema() -> DispatcherSchema {
     use dptree::case;
-    Update::filter_message()
-        .branch(
-            case![SupState::MainMenu]
-                .filter_command::<Command>()
-                .branch(case![Command::LinkSubreddit].endpoint(listeners::on_sub_link))
-                .branch(case![Command::UnlinkSubreddit].endpoint(listeners::on_sub_unlink)),
-        )
+    dptree::entry()
         .branch(
-            case![SupState::Sub(x)]
-                .branch(case![State::LinkReceiveChannel].endpoint(listeners::on_sub_link_channel))
-                .branch(
-                    case![State::LinkReceiveSub(selected_channel)]
-                        .endpoint(listeners::on_sub_link_sub),
-                )
+            Update::filter_message()
                 .branch(
-                    case![State::UnlinkReceiveChannel].endpoint(listeners::on_sub_unlink_channel),
+                    case![SupState::MainMenu]
+                        .filter_command::<Command>()
+                        .branch(case![Command::LinkSubreddit].endpoint(listeners::on_sub_link))
+                        .branch(case![Command::UnlinkSubreddit].endpoint(listeners::on_sub_unlink))
+                        .branch(case![Command::SetSorting].endpoint(listeners::on_sub_sorting))
+                        .branch(case![Command::SetQuota].endpoint(listeners::on_sub_quota))
+                        .branch(case![Command::SetComments].endpoint(listeners::on_sub_comments))
+                        .branch(case![Command::Keywords].endpoint(listeners::on_sub_keywords)),
                 )
                 .branch(
-                    case![State::LinkReceiveSub(selected_channel)]
-                        .endpoint(listeners::on_sub_unlink_sub),
+                    case![SupState::Sub(x)]
+                        .branch(
+                            case![State::LinkReceiveSub(selected_channel)]
+                                .endpoint(listeners::on_sub_link_sub),
+                        )
+                        .branch(
+                            case![State::SortingReceiveSorting(
+                                selected_channel,
+                                selected_subreddit
+                            )]
+                            .endpoint(listeners::on_sub_sorting_sorting),
+                        )
+                        .branch(
+                            case![State::QuotaReceiveQuota(
+                                selected_channel,
+                                selected_subreddit
+                            )]
+                            .endpoint(listeners::on_sub_quota_quota),
+                        )
+                        .branch(
+                            case![State::CommentsReceiveSettings(
+                                selected_channel,
+                                selected_subreddit
+                            )]
+                            .endpoint(listeners::on_sub_comments_settings),
+                        )
+                        .branch(
+                            case![State::KeywordsEdit(selected_channel, selected_subreddit)]
+                                .endpoint(listeners::on_sub_keywords_edit),
+                        ),
                 ),
         )
+        .branch(
+            Update::filter_callback_query().branch(
+                case![SupState::Sub(x)]
+                    .branch(
+                        case![State::LinkReceiveChannel].endpoint(listeners::on_sub_link_channel),
+                    )
+                    .branch(
+                        case![State::UnlinkReceiveChannel]
+                            .endpoint(listeners::on_sub_unlink_channel),
+                    )
+                    .branch(
+                        case![State::UnlinkReceiveSub(selected_channel)]
+                            .endpoint(listeners::on_sub_unlink_sub),
+                    )
+                    .branch(
+                        case![State::SortingReceiveChannel]
+                            .endpoint(listeners::on_sub_sorting_channel),
+                    )
+                    .branch(
+                        case![State::SortingReceiveSub(selected_channel)]
+                            .endpoint(listeners::on_sub_sorting_sub),
+                    )
+                    .branch(
+                        case![State::QuotaReceiveChannel].endpoint(listeners::on_sub_quota_channel),
+                    )
+                    .branch(
+                        case![State::QuotaReceiveSub(selected_channel)]
+                            .endpoint(listeners::on_sub_quota_sub),
+                    )
+                    .branch(
+                        case![State::CommentsReceiveChannel]
+                            .endpoint(listeners::on_sub_comments_channel),
+                    )
+                    .branch(
+                        case![State::CommentsReceiveSub(selected_channel)]
+                            .endpoint(listeners::on_sub_comments_sub),
+                    )
+                    .branch(
+                        case![State::KeywordsReceiveChannel]
+                            .endpoint(listeners::on_sub_keywords_channel),
+                    )
+                    .branch(
+                        case![State::KeywordsReceiveSub(selected_channel)]
+                            .endpoint(listeners::on_sub_keywords_sub),
+                    ),
+            ),
+        )
 }